@@ -9,6 +9,7 @@ with lib;
 
 let
   cfg = config.programs.gp-gui;
+  jsonFormat = pkgs.formats.json { };
 
   # Default packages - will be overridden if gp-gui is in pkgs
   defaultGpGui =
@@ -56,12 +57,62 @@ in
         If null (default), all users can execute it.
       '';
     };
+
+    settings = mkOption {
+      inherit (jsonFormat) type;
+      default = { };
+      example = literalExpression ''
+        {
+          default_profile = "Corporate";
+          locked = [ "gateway" "csd_wrapper" "fix_openssl" ];
+        }
+      '';
+      description = ''
+        System-wide configuration written to {file}`/etc/gp-gui/config.json`.
+        Supports `default_profile`, `locked` (fields users cannot change in
+        any system profile: `gateway`, `csd_wrapper`, `fix_openssl`,
+        `pinned_certificate`) and `profiles`.
+      '';
+    };
+
+    profiles = mkOption {
+      type = types.attrsOf jsonFormat.type;
+      default = { };
+      example = literalExpression ''
+        {
+          Corporate = {
+            gateway = "vpn.example.com";
+            auth_method = "browser";
+            locked = [ "gateway" ];
+          };
+        }
+      '';
+      description = ''
+        Connection profiles provided to all users, written to
+        {file}`/etc/gp-gui/profiles.d/<name>.json`. The attribute name is the
+        profile name. Each profile accepts the fields of the user config
+        plus an optional `locked` list.
+      '';
+    };
   };
 
   config = mkIf cfg.enable {
     # Install the main package and its runtime dependencies
     environment.systemPackages = [ cfg.package ] ++ (cfg.package.runtimeDeps or [ ]);
 
+    # Organisation-provided configuration and profiles
+    environment.etc = mkMerge [
+      (mkIf (cfg.settings != { }) {
+        "gp-gui/config.json".source = jsonFormat.generate "gp-gui-config.json" cfg.settings;
+      })
+      (mapAttrs' (
+        name: profile:
+        nameValuePair "gp-gui/profiles.d/${name}.json" {
+          source = jsonFormat.generate "gp-gui-profile-${name}.json" ({ inherit name; } // profile);
+        }
+      ) cfg.profiles)
+    ];
+
     # Create setuid wrappers for both gp-gui and gpclient
     # Both need root: gp-gui for lock file, gpclient for TUN device creation
     security.wrappers = {
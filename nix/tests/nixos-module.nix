@@ -30,6 +30,12 @@ pkgsWithOverlay.testers.nixosTest {
       programs.gp-gui = {
         enable = true;
         # Package will be auto-detected from pkgs.gp-gui via overlay
+
+        settings = {
+          default_profile = "Corporate";
+          locked = [ "gateway" ];
+        };
+        profiles.Corporate.gateway = "vpn.example.com";
       };
 
       # Minimal system configuration
@@ -83,6 +89,14 @@ pkgsWithOverlay.testers.nixosTest {
     gpclient_target = machine.succeed("readlink -f /run/wrappers/bin/gpclient").strip()
     print(f"✓ gpclient wrapper target: {gpclient_target}")
 
+    # Test system configuration files
+    print("\n=== Testing system configuration ===")
+    machine.succeed("grep -q Corporate /etc/gp-gui/config.json")
+    profile = machine.succeed("cat /etc/gp-gui/profiles.d/Corporate.json")
+    assert "vpn.example.com" in profile, f"Unexpected profile: {profile}"
+    assert '"name":"Corporate"' in profile.replace(" ", ""), f"Profile name missing: {profile}"
+    print("✓ System config and profiles are rendered to /etc/gp-gui")
+
     print("✓ All NixOS module tests passed!")
   '';
 }
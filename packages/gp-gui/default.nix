@@ -50,14 +50,21 @@ craneLib.buildPackage (
   // {
     inherit cargoArtifacts;
 
+    # The certificate tests create their fixtures with openssl
+    nativeCheckInputs = [ pkgs.openssl ];
+
     postInstall = ''
-      # Wrap the GUI to ensure it can find gpauth, gpclient, and openconnect
+      # Wrap the GUI to ensure it can find gpauth, gpclient, openconnect,
+      # openssl (used to inspect client and gateway certificates) and nft
+      # (kill switch)
       wrapProgram $out/bin/gp-gui \
         --prefix PATH : ${
           pkgs.lib.makeBinPath [
             pkgs.gpauth
             pkgs.gpclient
             pkgs.openconnect
+            pkgs.openssl
+            pkgs.nftables
           ]
         } \
         --prefix LD_LIBRARY_PATH : ${
@@ -80,6 +87,8 @@ craneLib.buildPackage (
         pkgs.gpauth
         pkgs.gpclient
         pkgs.openconnect
+        pkgs.openssl
+        pkgs.nftables
       ];
       inherit cargoArtifacts;
     };
@@ -10,6 +10,8 @@
  * - Only executes the specific gp-gui binary at compile-time fixed path
  * - Sanitizes environment to prevent LD_PRELOAD and similar attacks
  * - Sets minimal safe PATH before privilege escalation
+ * - Records the invoking user's UID in GP_GUI_INVOKING_UID so gp-gui can
+ *   perform per-user file I/O (e.g. ~/.config) as that user
  * - No user-controlled paths or arguments processed
  * - Privileges are NOT dropped; the entire gp-gui process runs as root
  *
@@ -88,6 +90,15 @@ int main(int argc, char *argv[]) {
         return 1;
     }
 
+    /* Step 5: Record the invoking user (real UID) before it is replaced */
+    /* Set after clearenv() so the caller cannot supply its own value */
+    char uid_str[32];
+    snprintf(uid_str, sizeof(uid_str), "%lu", (unsigned long)getuid());
+    if (setenv("GP_GUI_INVOKING_UID", uid_str, 1) != 0) {
+        fprintf(stderr, "gp-gui-wrapper: Failed to set GP_GUI_INVOKING_UID: %s\n", strerror(errno));
+        return 1;
+    }
+
     /* Set GID before UID to avoid permission issues */
     /* Set real, effective, and saved GID to root */
     if (setgid(0) != 0) {
@@ -0,0 +1,39 @@
+//! Command-Line Arguments
+//!
+//! gp-gui accepts only a handful of options. They are parsed both at startup
+//! and when a second launch forwards its arguments to the running instance
+//! (see the `instance` module), so parsing never exits the process.
+
+use log::warn;
+
+/// Parsed command-line options.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Args {
+    /// Connection profile to select (`--profile <name>`)
+    pub profile: Option<String>,
+}
+
+impl Args {
+    /// Parse arguments, excluding the program name.
+    ///
+    /// Unknown arguments are logged and ignored.
+    pub fn parse(args: &[String]) -> Self {
+        let mut parsed = Self::default();
+        let mut iter = args.iter();
+
+        while let Some(arg) = iter.next() {
+            if let Some(value) = arg.strip_prefix("--profile=") {
+                parsed.profile = Some(value.to_string());
+            } else if arg == "--profile" {
+                match iter.next() {
+                    Some(value) => parsed.profile = Some(value.clone()),
+                    None => warn!("--profile requires a value"),
+                }
+            } else {
+                warn!("Ignoring unknown argument: {}", arg);
+            }
+        }
+
+        parsed
+    }
+}
@@ -6,45 +6,576 @@
 //! # Storage Location
 //!
 //! - Linux: `$XDG_CONFIG_HOME/gp-gui/config.json` or `~/.config/gp-gui/config.json`
-//! - Configuration includes VPN server and username (password is never saved)
+//! - When running as root on behalf of a user (setuid wrapper or sudo), the
+//!   user's home directory from the passwd database is used instead, and all
+//!   file operations run with the user's identity so files stay user-owned
+//! - Configuration holds a list of named connection profiles and the name of
+//!   the default profile (passwords are never saved)
+//! - Profiles provided by the administrator in `/etc/gp-gui` are merged in by
+//!   the `system_config` module; only the user's changes are stored here
+//!
+//! # Versioning
+//!
+//! Every file carries a `version` field. Older files are upgraded on load by a
+//! chain of migrations, one per version step, and the original file is kept as
+//! `config.json.v<N>.bak`. Files written by a newer gp-gui are read on a best
+//! effort basis but never overwritten, so downgrading cannot destroy settings
+//! the older release doesn't understand.
+//!
+//! | Version | Shape                                             |
+//! |---------|---------------------------------------------------|
+//! | 1       | `{vpn_server, username}` (no `version` field)     |
+//! | 2       | `{version, default_profile, profiles: [Profile]}` |
 //!
 //! # Security
 //!
 //! Passwords are never persisted to disk. Only non-sensitive configuration
-//! (server address and username) is saved for user convenience.
+//! (server address, username and connection options) is saved for user
+//! convenience. Profiles that opt in to "Remember password" keep it in the
+//! Secret Service keyring instead (see the `keyring` module).
+//!
+//! Writes are atomic: the new content goes to a temporary file in the same
+//! directory, is fsynced and then renamed over `config.json`, so a crash never
+//! leaves a truncated file. Files are created with mode 0600 (directory 0700)
+//! regardless of the umask, and an advisory lock on `config.json.lock`
+//! serialises concurrent writers.
 
-use anyhow::Result;
+use crate::user_helper::{self, FileOperation};
+use crate::{privileges, system_config};
+use anyhow::{Context, Result};
 use log::{info, warn};
 use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::PathBuf;
+use std::io::Write;
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Current configuration schema version
+pub const CONFIG_VERSION: u32 = 2;
+
+/// Permissions for files written to the config directory
+const CONFIG_FILE_MODE: u32 = 0o600;
+
+/// Permissions for a newly created config directory
+const CONFIG_DIR_MODE: u32 = 0o700;
+
+/// Default time to wait for the tunnel to come up, in seconds
+pub const DEFAULT_CONNECT_TIMEOUT_SECS: u64 = 60;
+
+/// Default number of days before expiry to warn about the gateway certificate
+pub const DEFAULT_EXPIRY_WARNING_DAYS: u32 = 30;
+
+/// Default number of minutes before the VPN session ends to warn about it
+pub const DEFAULT_SESSION_WARNING_MINUTES: u32 = 10;
+
+/// How the user authenticates against the portal or gateway.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AuthMethod {
+    /// Username and password, passed to gpclient on stdin
+    #[default]
+    Password,
+
+    /// SAML login in the default web browser
+    Browser,
+}
+
+impl AuthMethod {
+    /// All methods, in the order shown in the profile editor
+    pub const ALL: [AuthMethod; 2] = [AuthMethod::Password, AuthMethod::Browser];
+}
+
+impl std::fmt::Display for AuthMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            AuthMethod::Password => "Password",
+            AuthMethod::Browser => "Browser (SAML)",
+        })
+    }
+}
+
+/// Operating system reported to the portal and gateway (`gpclient --os`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ClientOs {
+    #[default]
+    Linux,
+    Windows,
+    Mac,
+}
+
+impl ClientOs {
+    /// All values, in the order shown in the profile editor
+    pub const ALL: [ClientOs; 3] = [ClientOs::Linux, ClientOs::Windows, ClientOs::Mac];
+
+    /// Value of the `--os` argument
+    pub fn as_arg(self) -> &'static str {
+        match self {
+            ClientOs::Linux => "linux",
+            ClientOs::Windows => "windows",
+            ClientOs::Mac => "mac",
+        }
+    }
+}
+
+impl std::fmt::Display for ClientOs {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ClientOs::Linux => "Linux",
+            ClientOs::Windows => "Windows",
+            ClientOs::Mac => "macOS",
+        })
+    }
+}
+
+/// Point in the connection lifecycle at which a profile's hook command runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HookEvent {
+    PreConnect,
+    PostConnect,
+    PreDisconnect,
+    PostDisconnect,
+    /// The tunnel went down without the user disconnecting
+    ConnectionLost,
+}
+
+impl HookEvent {
+    /// All events, in lifecycle order
+    pub const ALL: [HookEvent; 5] = [
+        HookEvent::PreConnect,
+        HookEvent::PostConnect,
+        HookEvent::PreDisconnect,
+        HookEvent::PostDisconnect,
+        HookEvent::ConnectionLost,
+    ];
+
+    /// Name used in the config file and passed to hooks
+    pub fn as_str(self) -> &'static str {
+        match self {
+            HookEvent::PreConnect => "pre-connect",
+            HookEvent::PostConnect => "post-connect",
+            HookEvent::PreDisconnect => "pre-disconnect",
+            HookEvent::PostDisconnect => "post-disconnect",
+            HookEvent::ConnectionLost => "connection-lost",
+        }
+    }
+}
+
+impl std::fmt::Display for HookEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A profile field that the system configuration can lock.
+///
+/// Locked fields always take the value from `/etc/gp-gui`, whatever the user
+/// configuration says, and cannot be edited in the UI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LockedField {
+    Gateway,
+    CsdWrapper,
+    FixOpenssl,
+    PinnedCertificate,
+}
+
+/// A named set of connection settings.
+///
+/// Every field except `name` and `gateway` has a default so that profiles
+/// written by hand only need to specify what differs.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Profile {
+    /// Unique, user-visible profile name (e.g., "Production")
+    pub name: String,
+
+    /// Portal or gateway address (e.g., "vpn.example.com")
+    pub gateway: String,
+
+    /// Username for VPN authentication
+    #[serde(default)]
+    pub username: String,
+
+    /// Authentication group to select on the gateway
+    #[serde(default)]
+    pub authgroup: Option<String>,
+
+    /// Connect to `gateway` directly instead of treating it as a portal
+    #[serde(default = "default_true")]
+    pub as_gateway: bool,
+
+    /// Enable legacy OpenSSL renegotiation for old gateways
+    #[serde(default = "default_true")]
+    pub fix_openssl: bool,
+
+    /// HIP report script; located automatically when unset
+    #[serde(default)]
+    pub csd_wrapper: Option<String>,
+
+    /// Seconds to wait for the tunnel to come up
+    #[serde(default = "default_connect_timeout")]
+    pub connect_timeout_secs: u64,
+
+    /// How to authenticate
+    #[serde(default)]
+    pub auth_method: AuthMethod,
+
+    /// Keep the password in the Secret Service keyring (never in this file)
+    #[serde(default)]
+    pub remember_password: bool,
+
+    /// Shell command printing the password (e.g. `pass show work/vpn`)
+    #[serde(default)]
+    pub password_command: Option<String>,
+
+    /// Operating system to report
+    #[serde(default)]
+    pub os: ClientOs,
+
+    /// GlobalProtect client version to report
+    #[serde(default)]
+    pub client_version: Option<String>,
+
+    /// HTTP user agent to send
+    #[serde(default)]
+    pub user_agent: Option<String>,
+
+    /// Submit a HIP report
+    #[serde(default)]
+    pub hip: bool,
+
+    /// Do not configure IPv6 on the tunnel
+    #[serde(default)]
+    pub disable_ipv6: bool,
+
+    /// Seconds to keep trying to reconnect after the tunnel drops
+    #[serde(default)]
+    pub reconnect_timeout_secs: Option<u32>,
+
+    /// Tunnel MTU
+    #[serde(default)]
+    pub mtu: Option<u16>,
+
+    /// Client certificate: PEM/DER file, PKCS#12 bundle or PKCS#11 URI
+    #[serde(default)]
+    pub certificate: Option<String>,
+
+    /// Private key, if not contained in `certificate`
+    #[serde(default)]
+    pub certificate_key: Option<String>,
+
+    /// Fingerprint of the gateway's TLS certificate (`sha256:<hex>`); other
+    /// certificates are refused
+    #[serde(default)]
+    pub pinned_certificate: Option<String>,
+
+    /// Warn when the gateway certificate expires within this many days
+    #[serde(default = "default_expiry_warning_days")]
+    pub expiry_warning_days: u32,
+
+    /// Proxy URL (`http://` or `socks5://`, optionally with a user name); the
+    /// proxy password is kept in the keyring
+    #[serde(default)]
+    pub proxy: Option<String>,
+
+    /// Internal `host[:port]` whose TCP connect time is graphed as latency
+    /// while connected
+    #[serde(default)]
+    pub probe_host: Option<String>,
+
+    /// Subnets (CIDR) and host names routed through the tunnel in addition
+    /// to what the gateway pushes
+    #[serde(default)]
+    pub split_include: Vec<String>,
+
+    /// Subnets (CIDR) kept on the local network even if the gateway routes
+    /// them through the tunnel
+    #[serde(default)]
+    pub split_exclude: Vec<String>,
+
+    /// Configure the tunnel's DNS in systemd-resolved, with routing-only
+    /// domains for internal zones, instead of letting vpnc-script rewrite
+    /// `/etc/resolv.conf`
+    #[serde(default)]
+    pub split_dns: bool,
+
+    /// Block traffic outside the tunnel with nftables while connected, and
+    /// after the tunnel drops until reconnecting
+    #[serde(default)]
+    pub kill_switch: bool,
+
+    /// Let the kill switch allow the directly connected local networks
+    #[serde(default)]
+    pub kill_switch_allow_lan: bool,
+
+    /// Login lifetime the gateway enforces, in minutes; overrides the
+    /// lifetime reported by gpclient
+    #[serde(default)]
+    pub session_lifetime_minutes: Option<u32>,
+
+    /// Inactivity timeout the gateway enforces, in minutes; overrides the
+    /// timeout reported by gpclient
+    #[serde(default)]
+    pub idle_timeout_minutes: Option<u32>,
+
+    /// Warn this many minutes before the session ends
+    #[serde(default = "default_session_warning_minutes")]
+    pub session_warning_minutes: u32,
+
+    /// Shell commands run as the user at points of the connection lifecycle
+    #[serde(default)]
+    pub hooks: BTreeMap<HookEvent, String>,
+
+    /// Provided by the system configuration (runtime only, never saved)
+    #[serde(skip)]
+    pub managed: bool,
+
+    /// Fields locked by the system configuration (runtime only, never saved)
+    #[serde(skip)]
+    pub locked: Vec<LockedField>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_connect_timeout() -> u64 {
+    DEFAULT_CONNECT_TIMEOUT_SECS
+}
+
+fn default_expiry_warning_days() -> u32 {
+    DEFAULT_EXPIRY_WARNING_DAYS
+}
+
+fn default_session_warning_minutes() -> u32 {
+    DEFAULT_SESSION_WARNING_MINUTES
+}
+
+impl Profile {
+    /// Create a profile with default connection options.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The profile name
+    /// * `gateway` - The VPN portal or gateway address
+    pub fn new(name: String, gateway: String) -> Self {
+        Self {
+            name,
+            gateway,
+            username: String::new(),
+            authgroup: None,
+            as_gateway: true,
+            fix_openssl: true,
+            csd_wrapper: None,
+            connect_timeout_secs: DEFAULT_CONNECT_TIMEOUT_SECS,
+            auth_method: AuthMethod::Password,
+            remember_password: false,
+            password_command: None,
+            os: ClientOs::Linux,
+            client_version: None,
+            user_agent: None,
+            hip: false,
+            disable_ipv6: false,
+            reconnect_timeout_secs: None,
+            mtu: None,
+            certificate: None,
+            certificate_key: None,
+            pinned_certificate: None,
+            expiry_warning_days: DEFAULT_EXPIRY_WARNING_DAYS,
+            proxy: None,
+            probe_host: None,
+            split_include: Vec::new(),
+            split_exclude: Vec::new(),
+            split_dns: false,
+            kill_switch: false,
+            kill_switch_allow_lan: false,
+            session_lifetime_minutes: None,
+            idle_timeout_minutes: None,
+            session_warning_minutes: DEFAULT_SESSION_WARNING_MINUTES,
+            hooks: BTreeMap::new(),
+            managed: false,
+            locked: Vec::new(),
+        }
+    }
+
+    /// Whether `field` is locked by the system configuration.
+    pub fn is_locked(&self, field: LockedField) -> bool {
+        self.locked.contains(&field)
+    }
+
+    /// Reset the locked fields of this profile to the values of `base`.
+    pub fn apply_locks(&mut self, base: &Profile) {
+        self.locked = base.locked.clone();
+        for field in &base.locked {
+            match field {
+                LockedField::Gateway => self.gateway = base.gateway.clone(),
+                LockedField::CsdWrapper => self.csd_wrapper = base.csd_wrapper.clone(),
+                LockedField::FixOpenssl => self.fix_openssl = base.fix_openssl,
+                LockedField::PinnedCertificate => {
+                    self.pinned_certificate = base.pinned_certificate.clone();
+                }
+            }
+        }
+    }
+}
 
 /// User configuration stored on disk.
 ///
 /// Contains only non-sensitive information that can be safely persisted.
 /// Passwords are never included to prevent credential exposure.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct UserConfig {
-    /// VPN server address (e.g., "vpn.example.com")
-    pub vpn_server: String,
+    /// Schema version this configuration was written with
+    pub version: u32,
 
-    /// Username for VPN authentication
-    pub username: String,
+    /// Name of the profile selected at startup
+    #[serde(default)]
+    pub default_profile: Option<String>,
+
+    /// Connection profiles, in display order
+    #[serde(default)]
+    pub profiles: Vec<Profile>,
 }
 
 impl UserConfig {
-    /// Create a new user configuration.
+    /// Configuration used when no profile is available at all.
     ///
-    /// # Arguments
-    ///
-    /// * `vpn_server` - The VPN gateway address
-    /// * `username` - The user's VPN username
-    pub fn new(vpn_server: String, username: String) -> Self {
+    /// Contains a single profile without a gateway, for the user to fill in.
+    /// Administrators provide a preset gateway through `/etc/gp-gui` instead.
+    pub fn initial() -> Self {
+        let profile = Profile::new("Default".to_string(), String::new());
+        Self {
+            version: CONFIG_VERSION,
+            default_profile: Some(profile.name.clone()),
+            profiles: vec![profile],
+        }
+    }
+
+    /// Configuration without any profiles, before system profiles are added.
+    pub fn empty() -> Self {
         Self {
-            vpn_server,
-            username,
+            version: CONFIG_VERSION,
+            default_profile: None,
+            profiles: Vec::new(),
         }
     }
+
+    /// Look up a profile by name.
+    pub fn profile(&self, name: &str) -> Option<&Profile> {
+        self.profiles.iter().find(|p| p.name == name)
+    }
+
+    /// Index of the default profile, falling back to the first profile.
+    pub fn default_index(&self) -> usize {
+        self.default_profile
+            .as_deref()
+            .and_then(|name| self.profiles.iter().position(|p| p.name == name))
+            .unwrap_or(0)
+    }
+
+    /// Names of all profiles, in display order.
+    pub fn profile_names(&self) -> Vec<String> {
+        self.profiles.iter().map(|p| p.name.clone()).collect()
+    }
+}
+
+/// A single schema upgrade, from version `N` to `N + 1`
+type Migration = fn(Value) -> Result<Value>;
+
+/// Migrations indexed by source version: `MIGRATIONS[0]` upgrades version 1.
+const MIGRATIONS: [Migration; (CONFIG_VERSION - 1) as usize] = [migrate_v1_to_v2];
+
+/// Version 1 stored a single server and username; turn it into one profile.
+fn migrate_v1_to_v2(old: Value) -> Result<Value> {
+    let field = |name: &str| old.get(name).and_then(Value::as_str).unwrap_or_default();
+
+    let mut profile = Profile::new("Default".to_string(), field("vpn_server").to_string());
+    profile.username = field("username").to_string();
+
+    Ok(json!({
+        "version": 2,
+        "default_profile": profile.name,
+        "profiles": [profile],
+    }))
+}
+
+/// Determine the schema version of a raw configuration document.
+///
+/// Files without a `version` field predate versioning: they are version 1
+/// unless they already contain profiles.
+fn schema_version(value: &Value) -> Result<u32> {
+    match value.get("version") {
+        Some(version) => version
+            .as_u64()
+            .and_then(|v| u32::try_from(v).ok())
+            .filter(|v| *v > 0)
+            .ok_or_else(|| anyhow::anyhow!("Invalid config version: {}", version)),
+        None if value.get("profiles").is_some() => Ok(2),
+        None => Ok(1),
+    }
+}
+
+/// Upgrade a raw configuration document to [`CONFIG_VERSION`].
+///
+/// # Returns
+///
+/// The upgraded document and the version it was read as. Documents from a
+/// newer gp-gui are returned unchanged.
+fn migrate(mut value: Value) -> Result<(Value, u32)> {
+    let original = schema_version(&value)?;
+
+    for version in original..CONFIG_VERSION {
+        value = MIGRATIONS[(version - 1) as usize](value)
+            .with_context(|| format!("Failed to migrate config from version {}", version))?;
+        info!(
+            "Migrated config from version {} to {}",
+            version,
+            version + 1
+        );
+    }
+
+    Ok((value, original))
+}
+
+/// Parse a configuration file, upgrading older schema versions.
+///
+/// # Returns
+///
+/// The configuration and the version of the file it was read from.
+///
+/// Files that need no migration are deserialized straight from `content`, so
+/// type errors (wrong field type, unknown enum variant) carry the line and
+/// column in the file. Errors in a migrated document have no position, as it
+/// no longer matches the file.
+fn parse_config(content: &str) -> Result<(UserConfig, u32)> {
+    let value: Value = serde_json::from_str(content)?;
+    let (value, original) = migrate(value)?;
+
+    if original > CONFIG_VERSION {
+        warn!(
+            "Config was written by a newer gp-gui (version {}, supported {}); \
+             it will not be modified",
+            original, CONFIG_VERSION
+        );
+    }
+
+    let config = if original >= CONFIG_VERSION {
+        serde_json::from_str(content)?
+    } else {
+        serde_json::from_value(value)?
+    };
+    Ok((config, original))
+}
+
+/// Keep a copy of a configuration file before it is upgraded.
+fn backup_config(path: &Path, version: u32) -> Result<PathBuf> {
+    let backup = path.with_extension(format!("json.v{}.bak", version));
+    fs::copy(path, &backup)?;
+    Ok(backup)
 }
 
 /// Get the path to the configuration file.
@@ -59,12 +590,20 @@ impl UserConfig {
 ///
 /// # File Location
 ///
-/// - First tries `$XDG_CONFIG_HOME/gp-gui/config.json`
+/// - When acting for another user, `<home>/.config/gp-gui/config.json` using
+///   the home directory from that user's passwd entry
+/// - Otherwise first tries `$XDG_CONFIG_HOME/gp-gui/config.json`
 /// - Falls back to `~/.config/gp-gui/config.json` on Linux
 ///
-/// The directory is created if it doesn't exist.
+/// The directory is created with mode 0700 if it doesn't exist. Call this with
+/// the invoking user's identity (see [`user_helper::file_operation`]) so the
+/// directory gets the right owner.
 pub fn get_config_path() -> Result<PathBuf> {
-    let config_dir = if let Ok(xdg_config) = std::env::var("XDG_CONFIG_HOME") {
+    let acting_for = privileges::invoking_user().filter(|_| privileges::is_acting_for_user());
+
+    let config_dir = if let Some(user) = acting_for {
+        user.dir.join(".config")
+    } else if let Ok(xdg_config) = std::env::var("XDG_CONFIG_HOME") {
         PathBuf::from(xdg_config)
     } else if let Some(home) = directories::BaseDirs::new() {
         home.config_dir().to_path_buf()
@@ -74,59 +613,297 @@ pub fn get_config_path() -> Result<PathBuf> {
 
     let app_config_dir = config_dir.join("gp-gui");
     if !app_config_dir.exists() {
-        fs::create_dir_all(&app_config_dir)?;
+        fs::DirBuilder::new()
+            .recursive(true)
+            .mode(CONFIG_DIR_MODE)
+            .create(&app_config_dir)?;
         info!("Created config directory: {:?}", app_config_dir);
     }
 
     Ok(app_config_dir.join("config.json"))
 }
 
+/// Get the directory for state kept between runs, such as the connection
+/// history.
+///
+/// # Returns
+///
+/// - `Ok(PathBuf)` with the gp-gui state directory
+/// - `Err` if the state directory cannot be determined or created
+///
+/// # Location
+///
+/// - When acting for another user, `<home>/.local/state/gp-gui`
+/// - Otherwise `$XDG_STATE_HOME/gp-gui`, falling back to
+///   `~/.local/state/gp-gui`
+///
+/// As with [`get_config_path`], the directory is created with mode 0700 and
+/// this should be called with the invoking user's identity.
+pub fn get_state_dir() -> Result<PathBuf> {
+    let acting_for = privileges::invoking_user().filter(|_| privileges::is_acting_for_user());
+
+    let state_dir = if let Some(user) = acting_for {
+        user.dir.join(".local").join("state")
+    } else if let Ok(xdg_state) = std::env::var("XDG_STATE_HOME") {
+        PathBuf::from(xdg_state)
+    } else if let Some(home) = directories::BaseDirs::new() {
+        home.home_dir().join(".local").join("state")
+    } else {
+        return Err(anyhow::anyhow!("Could not determine state directory"));
+    };
+
+    let app_state_dir = state_dir.join("gp-gui");
+    if !app_state_dir.exists() {
+        fs::DirBuilder::new()
+            .recursive(true)
+            .mode(CONFIG_DIR_MODE)
+            .create(&app_state_dir)?;
+        info!("Created state directory: {:?}", app_state_dir);
+    }
+
+    Ok(app_state_dir)
+}
+
+/// Set when a config file exists but could not be read, so that it is
+/// never replaced by a save
+static SAVE_BLOCKED: AtomicBool = AtomicBool::new(false);
+
+/// A config file that exists but could not be used.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigError {
+    /// Description of what went wrong
+    pub message: String,
+
+    /// 1-based position of a JSON syntax or type error in the file, if known
+    /// (type errors in a migrated older file have none)
+    pub position: Option<(usize, usize)>,
+
+    /// Where the unreadable file was moved to, if it was quarantined
+    pub quarantined: Option<PathBuf>,
+
+    /// The file is still in place and must not be overwritten by a save
+    blocks_saves: bool,
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Could not read config.json")?;
+        if let Some((line, column)) = self.position {
+            write!(f, " (line {}, column {})", line, column)?;
+        }
+        write!(f, ": {}", self.message)?;
+        match &self.quarantined {
+            Some(path) => write!(f, ". It was moved to {}", path.display()),
+            None => write!(f, ". It will not be modified"),
+        }
+    }
+}
+
+impl ConfigError {
+    fn new(error: &anyhow::Error) -> Self {
+        let position = error
+            .downcast_ref::<serde_json::Error>()
+            .filter(|e| e.line() > 0)
+            .map(|e| (e.line(), e.column()));
+
+        // serde_json appends the position to its message; it is shown separately
+        let mut message = error.to_string();
+        if let Some((line, column)) = position {
+            let suffix = format!(" at line {} column {}", line, column);
+            if let Some(stripped) = message.strip_suffix(&suffix) {
+                message = stripped.to_string();
+            }
+        }
+
+        Self {
+            message,
+            position,
+            quarantined: None,
+            blocks_saves: false,
+        }
+    }
+}
+
+/// Move an unreadable config file aside as `config.json.bad-<timestamp>`.
+fn quarantine_config(path: &Path) -> Result<PathBuf> {
+    let timestamp = chrono::Local::now().format("%Y%m%d-%H%M%S");
+    let target = path.with_extension(format!("json.bad-{}", timestamp));
+    fs::rename(path, &target)?;
+    Ok(target)
+}
+
 /// Load the user configuration from disk.
 ///
 /// # Returns
 ///
-/// - `Some(UserConfig)` if the config file exists and is valid
-/// - `None` if the file doesn't exist, cannot be read, or is invalid JSON
+/// - `Ok(Some(UserConfig))` if the config file exists and is valid
+/// - `Ok(None)` if there is no config file (or no config directory)
+/// - `Err(ConfigError)` if the file exists but cannot be read or parsed
+///
+/// Files from an older schema version are backed up and rewritten in the
+/// current format.
 ///
 /// # Errors
 ///
-/// Errors are logged but not returned. This function is designed to degrade
-/// gracefully - if config cannot be loaded, the app still functions.
-pub fn load_config() -> Option<UserConfig> {
-    match get_config_path() {
-        Ok(path) => {
-            if path.exists() {
-                match fs::read_to_string(&path) {
-                    Ok(content) => match serde_json::from_str(&content) {
-                        Ok(config) => {
-                            info!("Loaded config from {:?}", path);
-                            Some(config)
-                        }
-                        Err(e) => {
-                            warn!("Failed to parse config: {}", e);
-                            None
-                        }
-                    },
-                    Err(e) => {
-                        warn!("Failed to read config: {}", e);
-                        None
-                    }
+/// A file that fails to parse is moved aside as `config.json.bad-<timestamp>`
+/// so that it survives the next save. If it cannot be moved (or could not be
+/// read at all), later calls to [`save_config`] fail instead of overwriting it.
+/// Files written by a newer gp-gui are left in place.
+pub fn load_config() -> Result<Option<UserConfig>, ConfigError> {
+    // Reading runs in a helper process, so the decision to block saves is
+    // carried back in the error rather than set there
+    match user_helper::file_operation::<Result<Option<UserConfig>, ConfigError>>(
+        FileOperation::LoadConfig,
+    ) {
+        Ok(Err(error)) if error.blocks_saves => {
+            SAVE_BLOCKED.store(true, Ordering::SeqCst);
+            Err(error)
+        }
+        Ok(result) => result,
+        Err(e) => {
+            warn!("Failed to load config as the invoking user: {}", e);
+            SAVE_BLOCKED.store(true, Ordering::SeqCst);
+            Err(ConfigError::new(&e))
+        }
+    }
+}
+
+/// Read, migrate or quarantine the config file; run as the invoking user.
+pub fn read_config() -> Result<Option<UserConfig>, ConfigError> {
+    let path = match get_config_path() {
+        Ok(path) => path,
+        Err(e) => {
+            warn!("Failed to get config path: {}", e);
+            return Ok(None);
+        }
+    };
+
+    if !path.exists() {
+        info!("No config file found at {:?}", path);
+        return Ok(None);
+    }
+
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) => {
+            warn!("Failed to read config: {}", e);
+            return Err(ConfigError {
+                blocks_saves: true,
+                ..ConfigError::new(&e.into())
+            });
+        }
+    };
+
+    match parse_config(&content) {
+        Ok((config, version)) => {
+            info!("Loaded config from {:?}", path);
+            if version < CONFIG_VERSION {
+                upgrade_config_file(&path, &config, version);
+            }
+            Ok(Some(config))
+        }
+        Err(e) => {
+            warn!("Failed to parse config: {}", e);
+            let mut error = ConfigError::new(&e);
+
+            if on_disk_version(&path).is_some_and(|v| v > CONFIG_VERSION) {
+                // Not broken, just newer; save_config already refuses to touch it
+                return Err(error);
+            }
+
+            match quarantine_config(&path) {
+                Ok(target) => {
+                    warn!("Moved unreadable config to {:?}", target);
+                    error.quarantined = Some(target);
+                }
+                Err(e) => {
+                    warn!("Failed to quarantine config, disabling saves: {}", e);
+                    error.blocks_saves = true;
                 }
-            } else {
-                info!("No config file found at {:?}", path);
-                None
             }
+            Err(error)
         }
+    }
+}
+
+/// Back up an outdated configuration file and replace it with `config`.
+fn upgrade_config_file(path: &Path, config: &UserConfig, version: u32) {
+    match backup_config(path, version) {
+        Ok(backup) => info!("Backed up version {} config to {:?}", version, backup),
         Err(e) => {
-            warn!("Failed to get config path: {}", e);
-            None
+            // Without a backup, leave the old file alone; it is upgraded
+            // again on the next load.
+            warn!("Failed to back up config, not upgrading it: {}", e);
+            return;
         }
     }
+
+    if let Err(e) = write_config(config) {
+        warn!("Failed to write upgraded config: {}", e);
+    }
+}
+
+/// Read the schema version of the configuration file currently on disk.
+///
+/// Returns `None` if there is no readable, well-formed file.
+fn on_disk_version(path: &Path) -> Option<u32> {
+    let content = fs::read_to_string(path).ok()?;
+    let value: Value = serde_json::from_str(&content).ok()?;
+    schema_version(&value).ok()
+}
+
+/// Take the advisory lock that serialises writers of the config directory.
+///
+/// The lock is released when the returned file is dropped.
+fn lock_config(path: &Path) -> Result<fs::File> {
+    let lock_path = path.with_extension("json.lock");
+    let lock = fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .mode(CONFIG_FILE_MODE)
+        .open(&lock_path)
+        .with_context(|| format!("Failed to open {:?}", lock_path))?;
+    lock.lock()
+        .with_context(|| format!("Failed to lock {:?}", lock_path))?;
+    Ok(lock)
+}
+
+/// Replace `path` with `content` atomically.
+///
+/// Writes a temporary file next to `path` with mode 0600, fsyncs it, renames
+/// it into place and fsyncs the directory so the rename is durable.
+fn write_atomic(path: &Path, content: &[u8]) -> Result<()> {
+    let dir = path
+        .parent()
+        .ok_or_else(|| anyhow::anyhow!("Config path has no parent directory"))?;
+    let tmp_path = path.with_extension(format!("json.tmp-{}", std::process::id()));
+
+    let result = (|| -> Result<()> {
+        let mut tmp = fs::OpenOptions::new()
+            .create_new(true)
+            .write(true)
+            .mode(CONFIG_FILE_MODE)
+            .open(&tmp_path)?;
+        tmp.write_all(content)?;
+        tmp.sync_all()?;
+        fs::rename(&tmp_path, path)?;
+        fs::File::open(dir)?.sync_all()?;
+        Ok(())
+    })();
+
+    if result.is_err() {
+        // Leave no stray temporary file behind; it may not exist at all
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result
 }
 
 /// Save the user configuration to disk.
 ///
-/// Serializes the configuration to JSON and writes it to the config file.
+/// Serializes the configuration to JSON and atomically replaces the config
+/// file while holding the config lock. System profiles the user has not
+/// changed are left out (see the `system_config` module).
 ///
 /// # Arguments
 ///
@@ -140,13 +917,259 @@ pub fn load_config() -> Option<UserConfig> {
 /// # Errors
 ///
 /// Returns an error if:
+/// - The identity of the invoking user cannot be assumed
 /// - The config directory cannot be determined
+/// - The config lock cannot be taken
 /// - The config file cannot be created or written
 /// - JSON serialization fails
+/// - The file on disk was written by a newer gp-gui
+/// - The file on disk could not be read by [`load_config`]
 pub fn save_config(config: &UserConfig) -> Result<()> {
+    if SAVE_BLOCKED.load(Ordering::SeqCst) {
+        return Err(anyhow::anyhow!(
+            "The existing config could not be read, refusing to overwrite it"
+        ));
+    }
+
+    let config = system_config::user_overrides(config);
+    user_helper::file_operation(FileOperation::SaveConfig(config))
+}
+
+/// Write `config` under the config lock; run as the invoking user.
+pub fn write_config(config: &UserConfig) -> Result<()> {
     let path = get_config_path()?;
-    let content = serde_json::to_string_pretty(config)?;
-    fs::write(&path, content)?;
+    let _lock = lock_config(&path)?;
+
+    if let Some(version) = on_disk_version(&path).filter(|v| *v > CONFIG_VERSION) {
+        return Err(anyhow::anyhow!(
+            "Config was written by a newer gp-gui (version {}), refusing to overwrite it",
+            version
+        ));
+    }
+
+    let config = UserConfig {
+        version: CONFIG_VERSION,
+        ..config.clone()
+    };
+    let content = serde_json::to_string_pretty(&config)?;
+    write_atomic(&path, content.as_bytes())?;
     info!("Saved config to {:?}", path);
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(names: &[&str], default: Option<&str>) -> UserConfig {
+        UserConfig {
+            version: CONFIG_VERSION,
+            default_profile: default.map(str::to_string),
+            profiles: names
+                .iter()
+                .map(|name| Profile::new(name.to_string(), "vpn.example.com".to_string()))
+                .collect(),
+        }
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("gp-gui-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn default_index_finds_named_profile() {
+        assert_eq!(config(&["a", "b"], Some("b")).default_index(), 1);
+    }
+
+    #[test]
+    fn default_index_falls_back_to_first_profile() {
+        assert_eq!(config(&["a", "b"], Some("missing")).default_index(), 0);
+        assert_eq!(config(&["a", "b"], None).default_index(), 0);
+    }
+
+    #[test]
+    fn profile_names_keep_display_order() {
+        assert_eq!(config(&["b", "a"], None).profile_names(), ["b", "a"]);
+    }
+
+    #[test]
+    fn hand_written_profiles_get_defaults() {
+        let (config, _) = parse_config(
+            r#"{"version": 2, "profiles": [{"name": "Work", "gateway": "vpn.example.com"}]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.profiles,
+            [Profile::new(
+                "Work".to_string(),
+                "vpn.example.com".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn schema_version_detects_unversioned_files() {
+        assert_eq!(schema_version(&json!({"vpn_server": "a"})).unwrap(), 1);
+        assert_eq!(schema_version(&json!({"profiles": []})).unwrap(), 2);
+        assert_eq!(schema_version(&json!({"version": 7})).unwrap(), 7);
+        assert!(schema_version(&json!({"version": 0})).is_err());
+        assert!(schema_version(&json!({"version": "2"})).is_err());
+    }
+
+    #[test]
+    fn migrates_v1_to_current() {
+        let content = r#"{"vpn_server": "vpn.example.com", "username": "alice"}"#;
+        let (config, original) = parse_config(content).unwrap();
+
+        assert_eq!(original, 1);
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!(config.default_profile.as_deref(), Some("Default"));
+        assert_eq!(config.profiles.len(), 1);
+        assert_eq!(config.profiles[0].gateway, "vpn.example.com");
+        assert_eq!(config.profiles[0].username, "alice");
+    }
+
+    #[test]
+    fn migration_leaves_gateway_empty_when_server_is_empty() {
+        let (config, _) = parse_config(r#"{"vpn_server": "", "username": ""}"#).unwrap();
+        assert_eq!(config.profiles[0].gateway, "");
+        assert_eq!(UserConfig::initial().profiles[0].gateway, "");
+    }
+
+    #[test]
+    fn current_version_round_trips() {
+        let config = UserConfig::initial();
+        let content = serde_json::to_string_pretty(&config).unwrap();
+        let (parsed, original) = parse_config(&content).unwrap();
+
+        assert_eq!(original, CONFIG_VERSION);
+        assert_eq!(parsed, config);
+    }
+
+    #[test]
+    fn newer_versions_are_not_migrated() {
+        let content = r#"{"version": 99, "profiles": [{"name": "a", "gateway": "b"}]}"#;
+        let (config, original) = parse_config(content).unwrap();
+
+        assert_eq!(original, 99);
+        assert_eq!(config.version, 99);
+    }
+
+    #[test]
+    fn type_errors_report_their_position() {
+        let content = "{\n  \"version\": 2,\n  \"profiles\": [\n    {\"name\": \"a\", \"gateway\": \"b\", \"os\": \"beos\"}\n  ]\n}";
+        let error = parse_config(content).unwrap_err();
+        let error = ConfigError::new(&error);
+
+        assert_eq!(error.position.map(|(line, _)| line), Some(4));
+        assert!(error.message.contains("beos"), "{}", error.message);
+        assert!(!error.message.contains(" at line "), "{}", error.message);
+    }
+
+    #[test]
+    fn syntax_errors_report_their_position() {
+        let error = ConfigError::new(&parse_config("{\n  \"version\": 2,\n}").unwrap_err());
+        assert_eq!(error.position, Some((3, 1)));
+        assert!(!error.message.contains(" at line "), "{}", error.message);
+    }
+
+    #[test]
+    fn config_error_describes_position_and_quarantine() {
+        let mut error = ConfigError {
+            message: "trailing comma".to_string(),
+            position: Some((3, 1)),
+            quarantined: None,
+            blocks_saves: false,
+        };
+        assert_eq!(
+            error.to_string(),
+            "Could not read config.json (line 3, column 1): trailing comma. \
+             It will not be modified"
+        );
+
+        error.position = None;
+        error.quarantined = Some(PathBuf::from("/tmp/config.json.bad-1"));
+        assert_eq!(
+            error.to_string(),
+            "Could not read config.json: trailing comma. It was moved to /tmp/config.json.bad-1"
+        );
+    }
+
+    #[test]
+    fn quarantine_moves_file_aside() {
+        let dir = temp_dir("quarantine");
+        let path = dir.join("config.json");
+        fs::write(&path, "{").unwrap();
+
+        let target = quarantine_config(&path).unwrap();
+
+        assert!(!path.exists());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "{");
+        assert!(
+            target
+                .file_name()
+                .unwrap()
+                .to_string_lossy()
+                .starts_with("config.json.bad-")
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn write_atomic_replaces_file_with_private_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = temp_dir("write-atomic");
+        let path = dir.join("config.json");
+        fs::write(&path, "old").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+
+        write_atomic(&path, b"new").unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, CONFIG_FILE_MODE);
+        assert_eq!(
+            fs::read_dir(&dir).unwrap().count(),
+            1,
+            "temporary file left"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn config_lock_excludes_other_writers() {
+        let dir = temp_dir("lock");
+        let path = dir.join("config.json");
+
+        let lock = lock_config(&path).unwrap();
+        let other = fs::File::open(path.with_extension("json.lock")).unwrap();
+        assert!(other.try_lock().is_err());
+
+        drop(lock);
+        assert!(other.try_lock().is_ok());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn hooks_use_event_names() {
+        let content = r#"{"version": 2, "profiles": [{"name": "a", "gateway": "b",
+            "hooks": {"post-connect": "mount /mnt/share", "connection-lost": "notify"}}]}"#;
+        let (config, _) = parse_config(content).unwrap();
+        let hooks = &config.profiles[0].hooks;
+
+        assert_eq!(hooks[&HookEvent::PostConnect], "mount /mnt/share");
+        assert_eq!(hooks[&HookEvent::ConnectionLost], "notify");
+        for event in HookEvent::ALL {
+            assert_eq!(serde_json::to_value(event).unwrap(), event.as_str());
+        }
+    }
+
+    #[test]
+    fn invalid_json_is_rejected() {
+        assert!(parse_config("{").is_err());
+    }
+}
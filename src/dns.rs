@@ -0,0 +1,181 @@
+//! DNS Configuration
+//!
+//! The DNS servers and domains pushed by the gateway are applied by
+//! vpnc-script, either to the tunnel link in systemd-resolved or by rewriting
+//! `/etc/resolv.conf`. gp-gui reads them back from where they ended up, in
+//! that order, since it does not see vpnc-script's environment itself.
+
+use crate::resolved;
+use anyhow::{Context, Result};
+use log::info;
+use std::fmt;
+use std::fs;
+use std::net::IpAddr;
+
+pub const RESOLV_CONF: &str = "/etc/resolv.conf";
+
+/// Where the DNS configuration of the tunnel was found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DnsSource {
+    Resolved,
+    ResolvConf,
+}
+
+impl fmt::Display for DnsSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DnsSource::Resolved => f.write_str("systemd-resolved"),
+            DnsSource::ResolvConf => f.write_str(RESOLV_CONF),
+        }
+    }
+}
+
+/// DNS servers and domains used for the tunnel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DnsInfo {
+    pub servers: Vec<IpAddr>,
+    pub search_domains: Vec<String>,
+    /// Domains only used to route queries to the tunnel's servers
+    pub routing_domains: Vec<String>,
+    pub source: DnsSource,
+}
+
+/// The `nameserver` and `search` entries of a resolv.conf file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResolvConf {
+    pub nameservers: Vec<IpAddr>,
+    pub search: Vec<String>,
+}
+
+impl ResolvConf {
+    /// Read `/etc/resolv.conf`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read.
+    pub fn read() -> Result<Self> {
+        let content = fs::read_to_string(RESOLV_CONF)
+            .with_context(|| format!("Failed to read {}", RESOLV_CONF))?;
+        Ok(Self::parse(&content))
+    }
+
+    pub fn parse(content: &str) -> Self {
+        let mut conf = Self::default();
+        for line in content.lines() {
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some("nameserver") => {
+                    // Link-local IPv6 servers may carry a zone, e.g. "fe80::1%eth0"
+                    if let Some(address) = words.next()
+                        && let Ok(ip) = address.split('%').next().unwrap_or_default().parse()
+                    {
+                        conf.nameservers.push(ip);
+                    }
+                }
+                // The last search or domain line wins
+                Some("search") | Some("domain") => {
+                    conf.search = words.map(str::to_string).collect();
+                }
+                _ => {}
+            }
+        }
+        conf
+    }
+}
+
+/// Find the DNS configuration used for the tunnel `interface`.
+///
+/// # Returns
+///
+/// `None` if neither systemd-resolved nor `/etc/resolv.conf` name any
+/// servers.
+pub async fn of_tunnel(interface: &str) -> Option<DnsInfo> {
+    match resolved::link_dns(interface).await {
+        Ok(link) if !link.servers.is_empty() => {
+            return Some(DnsInfo {
+                servers: link.servers,
+                search_domains: link.search_domains,
+                routing_domains: link.routing_domains,
+                source: DnsSource::Resolved,
+            });
+        }
+        Ok(_) => info!("systemd-resolved has no DNS servers for {}", interface),
+        Err(e) => info!("No DNS configuration from systemd-resolved: {:#}", e),
+    }
+
+    let conf = ResolvConf::read().ok()?;
+    if conf.nameservers.is_empty() {
+        return None;
+    }
+    Some(DnsInfo {
+        servers: conf.nameservers,
+        search_domains: conf.search,
+        routing_domains: Vec::new(),
+        source: DnsSource::ResolvConf,
+    })
+}
+
+/// Resolve `host` to its addresses, without duplicates.
+///
+/// # Errors
+///
+/// Returns an error if the name cannot be resolved.
+pub async fn lookup(host: &str) -> Result<Vec<IpAddr>> {
+    let mut addresses = Vec::new();
+    for address in tokio::net::lookup_host((host, 0))
+        .await
+        .with_context(|| format!("Failed to resolve {}", host))?
+    {
+        if !addresses.contains(&address.ip()) {
+            addresses.push(address.ip());
+        }
+    }
+    Ok(addresses)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_nameservers_and_search_domains() {
+        let conf = ResolvConf::parse(
+            "# Generated by vpnc-script\n\
+             nameserver 10.0.0.53\n\
+             nameserver\tfe80::1%eth0\n\
+             nameserver not-an-address\n\
+             nameserver\n\
+             search corp.example.com example.com\n\
+             options edns0\n",
+        );
+
+        assert_eq!(
+            conf.nameservers,
+            [
+                "10.0.0.53".parse::<IpAddr>().unwrap(),
+                "fe80::1".parse().unwrap()
+            ]
+        );
+        assert_eq!(conf.search, ["corp.example.com", "example.com"]);
+    }
+
+    #[test]
+    fn last_search_or_domain_line_wins() {
+        let conf = ResolvConf::parse("search a.example\ndomain b.example\n");
+        assert_eq!(conf.search, ["b.example"]);
+        assert_eq!(ResolvConf::parse(""), ResolvConf::default());
+    }
+
+    #[test]
+    fn sources_are_named_for_display() {
+        assert_eq!(DnsSource::Resolved.to_string(), "systemd-resolved");
+        assert_eq!(DnsSource::ResolvConf.to_string(), "/etc/resolv.conf");
+    }
+
+    #[tokio::test]
+    async fn lookup_removes_duplicates() {
+        let addresses = lookup("127.0.0.1").await.unwrap();
+        assert_eq!(addresses, ["127.0.0.1".parse::<IpAddr>().unwrap()]);
+        assert!(lookup("").await.is_err());
+    }
+}
@@ -0,0 +1,588 @@
+//! DNS Check
+//!
+//! A diagnostic run from the Connected view that finds out which resolvers
+//! are actually used for internal and external names, and whether the DNS
+//! configuration on disk matches the tunnel. It reports:
+//!
+//! - leaks: internal names, or with a full tunnel any names, resolved
+//!   outside the tunnel, and VPN DNS servers reached past it
+//! - stale entries: servers in `/etc/resolv.conf`, or in the copy of it that
+//!   vpnc-script restores on disconnect, left by an earlier connection whose
+//!   teardown never ran, e.g. after gpclient was killed with SIGKILL
+//!
+//! Which link answered a query is known from systemd-resolved when it is
+//! running; otherwise the routes to the servers in `/etc/resolv.conf` tell
+//! whether queries go through the tunnel.
+
+use crate::dns::{self, DnsInfo, DnsSource, RESOLV_CONF, ResolvConf};
+use crate::gateway_certificate;
+use crate::resolved;
+use crate::routes::{self, TunnelMode};
+use crate::split_dns;
+use nix::net::if_::{if_indextoname, if_nametoindex};
+use std::fmt;
+use std::fs;
+use std::net::IpAddr;
+
+/// Copy of resolv.conf vpnc-script saves before rewriting it
+pub const VPNC_RESOLV_BACKUP: &str = "/var/run/vpnc/resolv.conf-backup";
+
+/// Name resolved to find the resolver used for public names
+const EXTERNAL_NAME: &str = "example.com";
+
+/// Internal names resolved at most, to keep the check quick
+const MAX_INTERNAL_NAMES: usize = 5;
+
+/// Result of one check, from best to worst.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Outcome {
+    Ok,
+    Warning,
+    Leak,
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::Ok => f.write_str("OK"),
+            Outcome::Warning => f.write_str("Warning"),
+            Outcome::Leak => f.write_str("Leak"),
+        }
+    }
+}
+
+/// One line of the report.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub outcome: Outcome,
+    pub message: String,
+}
+
+/// Findings of a DNS check, in the order the checks ran.
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    pub findings: Vec<Finding>,
+}
+
+impl Report {
+    /// The worst outcome of all findings
+    pub fn worst(&self) -> Outcome {
+        self.findings
+            .iter()
+            .map(|finding| finding.outcome)
+            .max()
+            .unwrap_or(Outcome::Ok)
+    }
+
+    fn push(&mut self, outcome: Outcome, message: impl Into<String>) {
+        self.findings.push(Finding {
+            outcome,
+            message: message.into(),
+        });
+    }
+}
+
+/// What the check needs to know about the connection.
+#[derive(Debug, Clone)]
+pub struct Connection {
+    /// Tunnel interface
+    pub interface: String,
+    /// DNS configuration detected for the tunnel
+    pub dns: Option<DnsInfo>,
+    /// Whether the profile uses split DNS, leaving resolv.conf untouched
+    pub split_dns: bool,
+    /// The profile's latency probe host, an internal name
+    pub probe_host: Option<String>,
+}
+
+/// Run all checks for the current connection.
+pub async fn run(connection: Connection) -> Report {
+    let mut report = Report::default();
+    let interface = connection.interface.as_str();
+
+    let tunnel = interface.to_string();
+    let mode = routes::blocking(move || routes::via_interface(&tunnel))
+        .await
+        .map(|routes| TunnelMode::of(&routes))
+        .unwrap_or(TunnelMode::Split);
+    let pushed = if connection.split_dns {
+        split_dns::recorded()
+    } else {
+        None
+    };
+
+    // What the gateway pushed, as far as it can be told
+    let mut servers: Vec<IpAddr> = Vec::new();
+    let mut domains: Vec<String> = Vec::new();
+    if let Some(pushed) = &pushed {
+        servers.extend(&pushed.servers);
+        domains.extend(pushed.default_domains.iter().cloned());
+        domains.extend(pushed.split_domains.iter().cloned());
+    }
+    if let Some(dns) = &connection.dns {
+        for server in &dns.servers {
+            if !servers.contains(server) {
+                servers.push(*server);
+            }
+        }
+        domains.extend(dns.search_domains.iter().cloned());
+        domains.extend(dns.routing_domains.iter().cloned());
+    }
+    if servers.is_empty() {
+        report.push(Outcome::Warning, "No DNS servers are known for the tunnel");
+    }
+
+    let rewritten = !connection.split_dns
+        && connection
+            .dns
+            .as_ref()
+            .is_some_and(|dns| dns.source == DnsSource::ResolvConf);
+    // Both look up routes over netlink
+    let (tunnel, split_dns) = (interface.to_string(), connection.split_dns);
+    let mut report = routes::blocking(move || {
+        check_resolv_conf(&mut report, &tunnel, &servers, mode, rewritten, split_dns);
+        check_backup(&mut report, &tunnel, &servers, rewritten);
+        report
+    })
+    .await;
+
+    let names = internal_names(connection.probe_host.as_deref(), domains);
+    check_resolution(&mut report, interface, &names, mode).await;
+
+    report
+}
+
+/// Names to resolve to check internal DNS: the probe host and the tunnel's
+/// domains, without duplicates.
+fn internal_names(probe_host: Option<&str>, domains: Vec<String>) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    if let Some(host) = probe_host {
+        names.push(gateway_certificate::host_and_port(host).0);
+    }
+    for domain in domains {
+        let domain = domain
+            .trim_start_matches('~')
+            .trim_end_matches('.')
+            .to_string();
+        if !domain.is_empty() && !names.contains(&domain) {
+            names.push(domain);
+        }
+    }
+    names.truncate(MAX_INTERNAL_NAMES);
+    names
+}
+
+/// Compare the servers in `/etc/resolv.conf` with the tunnel's and check
+/// the routes to them.
+fn check_resolv_conf(
+    report: &mut Report,
+    interface: &str,
+    servers: &[IpAddr],
+    mode: TunnelMode,
+    rewritten: bool,
+    split_dns: bool,
+) {
+    let conf = match ResolvConf::read() {
+        Ok(conf) => conf,
+        Err(e) => {
+            report.push(Outcome::Warning, format!("{:#}", e));
+            return;
+        }
+    };
+    if conf.nameservers.is_empty() {
+        report.push(
+            Outcome::Warning,
+            format!("{} names no DNS servers", RESOLV_CONF),
+        );
+        return;
+    }
+    if conf.nameservers.iter().all(IpAddr::is_loopback) {
+        report.push(
+            Outcome::Ok,
+            format!(
+                "{} uses the local resolver {}",
+                RESOLV_CONF,
+                join(&conf.nameservers)
+            ),
+        );
+        return;
+    }
+
+    for server in conf.nameservers.iter().filter(|s| !s.is_loopback()) {
+        let route = match routes::route_to(*server) {
+            Ok(route) => route,
+            Err(e) => {
+                report.push(Outcome::Warning, format!("{:#}", e));
+                continue;
+            }
+        };
+        let device = route.interface.unwrap_or_default();
+        let (outcome, message) = server_outcome(
+            *server,
+            servers.contains(server),
+            &device,
+            device == interface,
+            mode,
+            rewritten,
+            split_dns,
+        );
+        report.push(outcome, message);
+    }
+
+    // vpnc-script replaces the servers with exactly the pushed ones
+    if rewritten {
+        let missing: Vec<IpAddr> = servers
+            .iter()
+            .filter(|server| !conf.nameservers.contains(server))
+            .copied()
+            .collect();
+        if !missing.is_empty() {
+            report.push(
+                Outcome::Warning,
+                format!(
+                    "VPN DNS servers missing from {}: {}",
+                    RESOLV_CONF,
+                    join(&missing)
+                ),
+            );
+        }
+    }
+}
+
+/// Judge a server named in `/etc/resolv.conf` by whether the gateway pushed
+/// it and whether its route goes through the tunnel.
+fn server_outcome(
+    server: IpAddr,
+    from_vpn: bool,
+    device: &str,
+    via_tunnel: bool,
+    mode: TunnelMode,
+    rewritten: bool,
+    split_dns: bool,
+) -> (Outcome, String) {
+    match (from_vpn, via_tunnel) {
+        (true, _) if split_dns => (
+            Outcome::Warning,
+            format!(
+                "{} names VPN DNS server {}, although split DNS leaves it alone; \
+                 it is left over from an earlier connection",
+                RESOLV_CONF, server
+            ),
+        ),
+        (true, true) => (
+            Outcome::Ok,
+            format!("VPN DNS server {} is reached through the tunnel", server),
+        ),
+        (true, false) => (
+            Outcome::Leak,
+            format!(
+                "VPN DNS server {} is reached via {}, outside the tunnel",
+                server, device
+            ),
+        ),
+        (false, true) => (
+            Outcome::Ok,
+            format!("DNS server {} is reached through the tunnel", server),
+        ),
+        (false, false) if rewritten => (
+            Outcome::Warning,
+            format!(
+                "{} names {}, which the gateway did not push; it is probably left \
+                 over from an earlier connection",
+                RESOLV_CONF, server
+            ),
+        ),
+        (false, false) if mode == TunnelMode::Full => (
+            Outcome::Leak,
+            format!(
+                "DNS server {} is reached via {}, outside the full tunnel",
+                server, device
+            ),
+        ),
+        (false, false) => (
+            Outcome::Ok,
+            format!("Local DNS server {} is reached via {}", server, device),
+        ),
+    }
+}
+
+/// Look for VPN servers in the copy of resolv.conf that vpnc-script
+/// restores on disconnect.
+fn check_backup(report: &mut Report, interface: &str, servers: &[IpAddr], rewritten: bool) {
+    let Ok(content) = fs::read_to_string(VPNC_RESOLV_BACKUP) else {
+        return;
+    };
+
+    // Saved while a tunnel was up: the teardown of that tunnel never ran
+    let stale: Vec<IpAddr> = ResolvConf::parse(&content)
+        .nameservers
+        .into_iter()
+        .filter(|server| {
+            servers.contains(server)
+                || routes::route_to(*server)
+                    .is_ok_and(|route| route.interface.as_deref() == Some(interface))
+        })
+        .collect();
+
+    if !stale.is_empty() {
+        report.push(
+            Outcome::Warning,
+            format!(
+                "{} names VPN DNS servers ({}); it was saved during an earlier connection \
+                 and would be restored on disconnect",
+                VPNC_RESOLV_BACKUP,
+                join(&stale)
+            ),
+        );
+    } else if !rewritten {
+        report.push(
+            Outcome::Warning,
+            format!(
+                "{} is left over from an earlier connection; vpnc-script did not \
+                 rewrite {} for this one",
+                VPNC_RESOLV_BACKUP, RESOLV_CONF
+            ),
+        );
+    }
+}
+
+/// Resolve internal names and a public one, and report which link answered.
+async fn check_resolution(
+    report: &mut Report,
+    interface: &str,
+    internal: &[String],
+    mode: TunnelMode,
+) {
+    if !resolved::is_running().await {
+        // Without resolved the servers in resolv.conf, checked above, are used
+        for name in internal {
+            match dns::lookup(name).await {
+                Ok(addresses) => report.push(
+                    Outcome::Ok,
+                    format!("{} resolves to {}", name, join(&addresses)),
+                ),
+                Err(e) => report.push(Outcome::Warning, format!("{:#}", e)),
+            }
+        }
+        return;
+    }
+
+    let tunnel = if_nametoindex(interface)
+        .ok()
+        .and_then(|index| i32::try_from(index).ok());
+
+    for name in internal {
+        match resolved::resolve_hostname(name).await {
+            Ok(answers) => {
+                let links = links(&answers);
+                if answers.iter().any(|(index, _)| Some(*index) == tunnel) {
+                    report.push(
+                        Outcome::Ok,
+                        format!("{} is resolved through the tunnel", name),
+                    );
+                } else if answers.iter().all(|(index, _)| *index == 0) {
+                    report.push(
+                        Outcome::Warning,
+                        format!("{} is answered locally, e.g. from /etc/hosts", name),
+                    );
+                } else {
+                    report.push(
+                        Outcome::Leak,
+                        format!("{} is resolved via {}, not the tunnel", name, links),
+                    );
+                }
+            }
+            Err(e) => report.push(Outcome::Warning, format!("{:#}", e)),
+        }
+    }
+
+    match resolved::resolve_hostname(EXTERNAL_NAME).await {
+        Ok(answers) => {
+            let links = links(&answers);
+            if answers.iter().any(|(index, _)| Some(*index) == tunnel) {
+                report.push(Outcome::Ok, "Public names are resolved through the tunnel");
+            } else if mode == TunnelMode::Full {
+                report.push(
+                    Outcome::Leak,
+                    format!(
+                        "Public names are resolved via {}, outside the full tunnel",
+                        links
+                    ),
+                );
+            } else {
+                report.push(
+                    Outcome::Ok,
+                    format!("Public names are resolved via {} (split tunnel)", links),
+                );
+            }
+        }
+        Err(e) => report.push(Outcome::Warning, format!("{:#}", e)),
+    }
+}
+
+/// Names of the links that answered, e.g. "eth0, wlan0".
+fn links(answers: &[(i32, IpAddr)]) -> String {
+    let mut names: Vec<String> = Vec::new();
+    for (index, _) in answers {
+        let name = u32::try_from(*index)
+            .ok()
+            .filter(|index| *index != 0)
+            .and_then(|index| if_indextoname(index).ok())
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "no link".to_string());
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    names.join(", ")
+}
+
+fn join(addresses: &[IpAddr]) -> String {
+    addresses
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_report_is_ok() {
+        assert_eq!(Report::default().worst(), Outcome::Ok);
+    }
+
+    #[test]
+    fn worst_outcome_wins() {
+        let mut report = Report::default();
+        report.push(Outcome::Ok, "fine");
+        report.push(Outcome::Warning, "odd");
+        assert_eq!(report.worst(), Outcome::Warning);
+
+        report.push(Outcome::Leak, "leak");
+        report.push(Outcome::Ok, "fine again");
+        assert_eq!(report.worst(), Outcome::Leak);
+    }
+
+    #[test]
+    fn outcomes_are_ordered_by_severity() {
+        assert!(Outcome::Ok < Outcome::Warning);
+        assert!(Outcome::Warning < Outcome::Leak);
+        assert_eq!(Outcome::Leak.to_string(), "Leak");
+    }
+
+    #[test]
+    fn joins_addresses() {
+        let addresses: Vec<IpAddr> =
+            vec!["10.0.0.53".parse().unwrap(), "fd00::53".parse().unwrap()];
+        assert_eq!(join(&addresses), "10.0.0.53, fd00::53");
+        assert_eq!(join(&[]), "");
+    }
+
+    fn outcome(from_vpn: bool, via_tunnel: bool, mode: TunnelMode, rewritten: bool) -> Outcome {
+        let device = if via_tunnel { "tun0" } else { "eth0" };
+        let server = "10.0.0.53".parse().unwrap();
+        server_outcome(server, from_vpn, device, via_tunnel, mode, rewritten, false).0
+    }
+
+    #[test]
+    fn vpn_servers_must_use_the_tunnel() {
+        assert_eq!(outcome(true, true, TunnelMode::Split, true), Outcome::Ok);
+        assert_eq!(outcome(true, false, TunnelMode::Split, true), Outcome::Leak);
+
+        let (result, message) = server_outcome(
+            "10.0.0.53".parse().unwrap(),
+            true,
+            "eth0",
+            false,
+            TunnelMode::Full,
+            true,
+            false,
+        );
+        assert_eq!(result, Outcome::Leak);
+        assert_eq!(
+            message,
+            "VPN DNS server 10.0.0.53 is reached via eth0, outside the tunnel"
+        );
+    }
+
+    #[test]
+    fn local_servers_only_leak_with_a_full_tunnel() {
+        assert_eq!(outcome(false, false, TunnelMode::Split, false), Outcome::Ok);
+        assert_eq!(
+            outcome(false, false, TunnelMode::Full, false),
+            Outcome::Leak
+        );
+        assert_eq!(outcome(false, true, TunnelMode::Full, false), Outcome::Ok);
+    }
+
+    #[test]
+    fn unpushed_servers_in_rewritten_resolv_conf_are_stale() {
+        assert_eq!(
+            outcome(false, false, TunnelMode::Full, true),
+            Outcome::Warning
+        );
+        assert_eq!(
+            outcome(false, false, TunnelMode::Split, true),
+            Outcome::Warning
+        );
+    }
+
+    #[test]
+    fn vpn_servers_with_split_dns_are_stale() {
+        let server = "10.0.0.53".parse().unwrap();
+        for via_tunnel in [false, true] {
+            let (result, message) = server_outcome(
+                server,
+                true,
+                "tun0",
+                via_tunnel,
+                TunnelMode::Split,
+                false,
+                true,
+            );
+            assert_eq!(result, Outcome::Warning);
+            assert!(message.contains("left over"), "{}", message);
+        }
+    }
+
+    #[test]
+    fn internal_names_come_from_probe_host_and_domains() {
+        let domains = [
+            "corp.example.com.",
+            "~lab.example.com",
+            "corp.example.com",
+            "~.",
+        ]
+        .map(str::to_string)
+        .to_vec();
+
+        assert_eq!(
+            internal_names(Some("intranet.corp.example.com:8443"), domains),
+            [
+                "intranet.corp.example.com",
+                "corp.example.com",
+                "lab.example.com"
+            ]
+        );
+    }
+
+    #[test]
+    fn internal_names_are_limited() {
+        let domains = (0..10).map(|i| format!("d{}.example.com", i)).collect();
+        let names = internal_names(None, domains);
+
+        assert_eq!(names.len(), MAX_INTERNAL_NAMES);
+        assert_eq!(names[0], "d0.example.com");
+    }
+
+    #[test]
+    fn names_answering_links() {
+        let address: IpAddr = "127.0.0.1".parse().unwrap();
+        assert_eq!(links(&[(1, address), (1, address)]), "lo");
+        assert_eq!(links(&[(0, address), (1, address)]), "no link, lo");
+        assert_eq!(links(&[]), "");
+    }
+}
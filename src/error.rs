@@ -0,0 +1,82 @@
+//! VPN Errors
+//!
+//! Failures that gp-gui detects itself, before or around gpclient, and that
+//! deserve a specific message instead of gpclient's generic "authentication
+//! failed". Most other errors are reported through `anyhow` with context.
+
+use chrono::{DateTime, Utc};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VpnError {
+    /// The gateway's TLS certificate could not be retrieved
+    CertificateUnavailable { gateway: String, reason: String },
+
+    /// The gateway presented a certificate the system does not trust, e.g. a
+    /// captive portal intercepting the connection
+    UntrustedCertificate { gateway: String, reason: String },
+
+    /// The gateway's certificate has expired
+    CertificateExpired {
+        gateway: String,
+        not_after: DateTime<Utc>,
+    },
+
+    /// The gateway's certificate differs from the one pinned in the profile
+    PinMismatch {
+        gateway: String,
+        pinned: String,
+        presented: String,
+    },
+}
+
+impl VpnError {
+    /// Short identifier of the kind of error, as stored in the connection
+    /// history
+    pub fn kind(&self) -> &'static str {
+        match self {
+            VpnError::CertificateUnavailable { .. } => "certificate-unavailable",
+            VpnError::UntrustedCertificate { .. } => "untrusted-certificate",
+            VpnError::CertificateExpired { .. } => "certificate-expired",
+            VpnError::PinMismatch { .. } => "pin-mismatch",
+        }
+    }
+}
+
+impl fmt::Display for VpnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VpnError::CertificateUnavailable { gateway, reason } => {
+                write!(
+                    f,
+                    "Could not retrieve the certificate of {}: {}",
+                    gateway, reason
+                )
+            }
+            VpnError::UntrustedCertificate { gateway, reason } => write!(
+                f,
+                "The certificate of {} is not trusted ({}). A captive portal or proxy may be \
+                 intercepting the connection",
+                gateway, reason
+            ),
+            VpnError::CertificateExpired { gateway, not_after } => write!(
+                f,
+                "The certificate of {} expired on {}. Contact your VPN administrator",
+                gateway,
+                not_after.format("%Y-%m-%d")
+            ),
+            VpnError::PinMismatch {
+                gateway,
+                pinned,
+                presented,
+            } => write!(
+                f,
+                "The certificate of {} has changed (pinned {}, presented {}). Refusing to \
+                 connect; re-pin it in the profile editor only if the change is expected",
+                gateway, pinned, presented
+            ),
+        }
+    }
+}
+
+impl std::error::Error for VpnError {}
@@ -0,0 +1,187 @@
+//! Native File Chooser
+//!
+//! Files are picked through the XDG desktop portal
+//! (`org.freedesktop.portal.FileChooser`), which shows the desktop's own
+//! dialog on GNOME, KDE and wlroots compositors alike. The portal lives on the
+//! user's session bus, so when gp-gui runs as root the dialog is opened by a
+//! helper process running as the invoking user (see the `user_helper` module).
+
+use crate::{privileges, user_helper};
+use anyhow::{Context, Result};
+use futures::StreamExt;
+use std::collections::HashMap;
+use std::io::Write;
+use zbus::zvariant::{OwnedObjectPath, OwnedValue, Value};
+use zbus::{Connection, Proxy};
+
+const PORTAL: &str = "org.freedesktop.portal.Desktop";
+const PORTAL_PATH: &str = "/org/freedesktop/portal/desktop";
+const FILE_CHOOSER_INTERFACE: &str = "org.freedesktop.portal.FileChooser";
+const REQUEST_INTERFACE: &str = "org.freedesktop.portal.Request";
+
+/// Let the user pick an existing file.
+///
+/// # Returns
+///
+/// - `Ok(Some(path))` with the chosen file
+/// - `Ok(None)` if the dialog was cancelled
+/// - `Err` if no portal is available
+pub async fn choose_file(title: &str) -> Result<Option<String>> {
+    if privileges::is_acting_for_user() {
+        let output = user_helper::run(&["choose-file", title], None).await?;
+        let path = String::from_utf8(output).context("File chooser returned invalid UTF-8")?;
+        return Ok((!path.is_empty()).then_some(path));
+    }
+
+    request("OpenFile", title, None).await
+}
+
+/// Let the user pick where to save a file.
+///
+/// # Arguments
+///
+/// * `title` - Title of the dialog
+/// * `name` - Suggested file name
+///
+/// # Returns
+///
+/// - `Ok(Some(path))` with the chosen path
+/// - `Ok(None)` if the dialog was cancelled
+/// - `Err` if no portal is available
+pub async fn choose_save_file(title: &str, name: &str) -> Result<Option<String>> {
+    if privileges::is_acting_for_user() {
+        let output = user_helper::run(&["save-file", title, name], None).await?;
+        let path = String::from_utf8(output).context("File chooser returned invalid UTF-8")?;
+        return Ok((!path.is_empty()).then_some(path));
+    }
+
+    request("SaveFile", title, Some(name)).await
+}
+
+/// Show the portal's `OpenFile` or `SaveFile` dialog.
+async fn request(method: &str, title: &str, name: Option<&str>) -> Result<Option<String>> {
+    let connection = Connection::session()
+        .await
+        .context("Failed to connect to the session bus")?;
+
+    // Subscribe to the response before asking, using the request path the
+    // portal derives from our unique name and handle token
+    let token = format!("gp_gui_{}", std::process::id());
+    let sender = connection
+        .unique_name()
+        .context("Session bus connection has no unique name")?
+        .trim_start_matches(':')
+        .replace('.', "_");
+    let handle =
+        OwnedObjectPath::try_from(format!("{}/request/{}/{}", PORTAL_PATH, sender, token))?;
+
+    let request = Proxy::new_owned(connection.clone(), PORTAL, handle, REQUEST_INTERFACE).await?;
+    let mut responses = request.receive_signal("Response").await?;
+
+    let chooser = Proxy::new(&connection, PORTAL, PORTAL_PATH, FILE_CHOOSER_INTERFACE).await?;
+    let mut options: HashMap<&str, Value> = HashMap::from([
+        ("handle_token", Value::from(token.as_str())),
+        ("modal", Value::from(true)),
+    ]);
+    if let Some(name) = name {
+        options.insert("current_name", Value::from(name));
+    }
+    let _: OwnedObjectPath = chooser
+        .call(method, &("", title, options))
+        .await
+        .context("No file chooser portal available")?;
+
+    let message = responses
+        .next()
+        .await
+        .context("File chooser closed unexpectedly")?;
+    let (response, results): (u32, HashMap<String, OwnedValue>) = message.body().deserialize()?;
+
+    // 0 = success, 1 = cancelled by the user, 2 = other
+    if response != 0 {
+        return Ok(None);
+    }
+
+    let uris: Vec<String> = results
+        .get("uris")
+        .context("File chooser returned no file")?
+        .try_clone()?
+        .try_into()?;
+
+    uris.first().map(|uri| path_from_uri(uri)).transpose()
+}
+
+/// Convert a `file://` URI to a path, decoding percent escapes.
+fn path_from_uri(uri: &str) -> Result<String> {
+    let encoded = uri
+        .strip_prefix("file://")
+        .with_context(|| format!("Not a local file: {}", uri))?;
+
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut iter = encoded.bytes();
+    while let Some(byte) = iter.next() {
+        if byte == b'%' {
+            let hex = [iter.next(), iter.next()];
+            let [Some(high), Some(low)] = hex else {
+                return Err(anyhow::anyhow!("Malformed file URI: {}", uri));
+            };
+            let hex = std::str::from_utf8(&[high, low])?.to_string();
+            bytes.push(u8::from_str_radix(&hex, 16)?);
+        } else {
+            bytes.push(byte);
+        }
+    }
+
+    String::from_utf8(bytes).context("File path is not valid UTF-8")
+}
+
+/// Helper side of `gp-gui --user-helper choose-file <title>`.
+///
+/// Writes the chosen path to stdout, or nothing if cancelled.
+pub async fn helper(args: &[String]) -> Result<()> {
+    let title = args.first().map(String::as_str).unwrap_or("Choose File");
+    print_path(request("OpenFile", title, None).await?)
+}
+
+/// Helper side of `gp-gui --user-helper save-file <title> <name>`.
+///
+/// Writes the chosen path to stdout, or nothing if cancelled.
+pub async fn save_helper(args: &[String]) -> Result<()> {
+    let title = args.first().map(String::as_str).unwrap_or("Save File");
+    let name = args.get(1).map(String::as_str).unwrap_or_default();
+    print_path(request("SaveFile", title, Some(name)).await?)
+}
+
+fn print_path(path: Option<String>) -> Result<()> {
+    if let Some(path) = path {
+        let mut stdout = std::io::stdout();
+        stdout.write_all(path.as_bytes())?;
+        stdout.flush()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_file_uris() {
+        assert_eq!(
+            path_from_uri("file:///home/alice/My%20Certs/vpn.p12").unwrap(),
+            "/home/alice/My Certs/vpn.p12"
+        );
+        assert_eq!(
+            path_from_uri("file:///home/j%C3%B6rg/cert.pem").unwrap(),
+            "/home/jörg/cert.pem"
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_uris() {
+        assert!(path_from_uri("https://example.com/cert.pem").is_err());
+        assert!(path_from_uri("file:///cert%2").is_err());
+        assert!(path_from_uri("file:///cert%zz").is_err());
+        assert!(path_from_uri("file:///cert%ff").is_err());
+    }
+}
@@ -0,0 +1,540 @@
+//! Gateway Certificates
+//!
+//! Before a connection is handed to gpclient, gp-gui retrieves the TLS
+//! certificate chain of the gateway with `openssl s_client`, running as the
+//! invoking user, and checks the gateway's own certificate:
+//!
+//! - if the profile pins a certificate, any other certificate is refused
+//! - an expired certificate, or one the system does not trust, is refused,
+//!   pinned or not
+//! - a certificate expiring within the profile's warning period is reported
+//!
+//! Refusals are reported as [`VpnError`]s, so the user sees what is wrong
+//! instead of gpclient's generic "authentication failed".
+//!
+//! # Pins
+//!
+//! A pin is the SHA-256 fingerprint of the gateway's certificate, written as
+//! `sha256:<hex>` like openconnect's `--servercert`. The check here runs over
+//! its own connection, so the gateway could present another certificate to
+//! gpclient; the pin is therefore also passed to gpclient as `--servercert`,
+//! which makes openconnect refuse any other certificate for the tunnel.
+//!
+//! A pin does not replace the system's trust decision: gpclient still
+//! verifies the chain itself, so a gateway with a private CA needs that CA
+//! installed system-wide. A renewed certificate has a new fingerprint and
+//! needs to be pinned again.
+
+use crate::certificate;
+use crate::config::Profile;
+use crate::error::VpnError;
+use crate::proxy::Proxy;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use std::net::IpAddr;
+use tokio::time::{Duration, timeout};
+
+/// Port used when the gateway address does not name one
+const DEFAULT_PORT: u16 = 443;
+
+/// Time allowed for retrieving the certificate chain
+const INSPECT_TIMEOUT_SECS: u64 = 15;
+
+const PEM_BEGIN: &str = "-----BEGIN CERTIFICATE-----";
+const PEM_END: &str = "-----END CERTIFICATE-----";
+
+/// One certificate of the chain presented by the gateway.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CertificateInfo {
+    pub subject: String,
+    pub issuer: String,
+    /// Subject alternative names, e.g. "DNS:vpn.example.com"
+    pub alt_names: Vec<String>,
+    pub not_after: DateTime<Utc>,
+    /// SHA-256 fingerprint, `sha256:<hex>`
+    pub pin: String,
+}
+
+/// Certificate chain presented by a gateway.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GatewayCertificates {
+    /// Certificates in the order sent, the gateway's own first
+    pub chain: Vec<CertificateInfo>,
+    /// Why the system does not trust the chain, `None` if it does
+    pub verify_error: Option<String>,
+}
+
+impl GatewayCertificates {
+    /// The gateway's own certificate
+    pub fn leaf(&self) -> Option<&CertificateInfo> {
+        self.chain.first()
+    }
+}
+
+/// Retrieve the certificate chain of `gateway`.
+///
+/// # Arguments
+///
+/// * `gateway` - Gateway address as stored in the profile, optionally with
+///   port or `https://` prefix
+/// * `proxy` - Proxy to connect through, if any
+///
+/// # Errors
+///
+/// [`VpnError::CertificateUnavailable`] if the gateway cannot be reached or
+/// does not complete the TLS handshake.
+pub async fn inspect(
+    gateway: &str,
+    proxy: Option<&Proxy>,
+) -> Result<GatewayCertificates, VpnError> {
+    let unavailable = |reason: String| VpnError::CertificateUnavailable {
+        gateway: gateway.to_string(),
+        reason,
+    };
+
+    let (host, port) = host_and_port(gateway);
+    if host.is_empty() {
+        return Err(unavailable("no gateway address".to_string()));
+    }
+
+    // openssl cannot use a proxy itself, so it connects to a local relay
+    let relay = match proxy {
+        Some(proxy) => Some(
+            proxy
+                .relay(&host, port)
+                .await
+                .map_err(|e| unavailable(format!("{:#}", e)))?,
+        ),
+        None => None,
+    };
+
+    let address = match &relay {
+        Some(relay) => relay.address.to_string(),
+        None if host.contains(':') => format!("[{}]:{}", host, port),
+        None => format!("{}:{}", host, port),
+    };
+    let mut args = vec!["s_client", "-connect", &address, "-showcerts"];
+    if host.parse::<IpAddr>().is_ok() {
+        args.extend(["-verify_ip", &host]);
+    } else {
+        args.extend(["-servername", &host, "-verify_hostname", &host]);
+    }
+
+    // stdin is closed right away, so s_client exits after the handshake
+    let output = timeout(
+        Duration::from_secs(INSPECT_TIMEOUT_SECS),
+        certificate::openssl(&args, None),
+    )
+    .await
+    .map_err(|_| unavailable(format!("no answer within {} seconds", INSPECT_TIMEOUT_SECS)))?
+    .map_err(|e| unavailable(format!("{:#}", e)))?;
+    let output = String::from_utf8_lossy(&output);
+
+    let mut chain = Vec::new();
+    for pem in pem_blocks(&output) {
+        chain.push(
+            describe(pem)
+                .await
+                .map_err(|e| unavailable(format!("{:#}", e)))?,
+        );
+    }
+    if chain.is_empty() {
+        return Err(unavailable("the gateway sent no certificate".to_string()));
+    }
+
+    Ok(GatewayCertificates {
+        chain,
+        verify_error: verify_error(&output),
+    })
+}
+
+/// Check the inspected certificates against `profile` before connecting.
+///
+/// # Returns
+///
+/// A warning to show the user if the certificate expires within the
+/// profile's warning period, or could not be retrieved for a profile without
+/// a pin.
+///
+/// # Errors
+///
+/// See the module documentation.
+pub fn verify(
+    profile: &Profile,
+    inspected: &Result<GatewayCertificates, VpnError>,
+) -> Result<Option<String>, VpnError> {
+    let gateway = profile.gateway.clone();
+
+    let certificates = match inspected {
+        Ok(certificates) => certificates,
+        // Without a pin there is nothing to enforce; gpclient does its own checks
+        Err(e) if profile.pinned_certificate.is_none() => {
+            return Ok(Some(format!("{}, connecting without checking it", e)));
+        }
+        Err(e) => return Err(e.clone()),
+    };
+    let Some(leaf) = certificates.leaf() else {
+        return Err(VpnError::CertificateUnavailable {
+            gateway,
+            reason: "the gateway sent no certificate".to_string(),
+        });
+    };
+
+    if let Some(pinned) = &profile.pinned_certificate
+        && *pinned != leaf.pin
+    {
+        return Err(VpnError::PinMismatch {
+            gateway,
+            pinned: pinned.clone(),
+            presented: leaf.pin.clone(),
+        });
+    }
+
+    let now = Utc::now();
+    if leaf.not_after < now {
+        return Err(VpnError::CertificateExpired {
+            gateway,
+            not_after: leaf.not_after,
+        });
+    }
+
+    if let Some(reason) = &certificates.verify_error {
+        return Err(VpnError::UntrustedCertificate {
+            gateway,
+            reason: reason.clone(),
+        });
+    }
+
+    let days = (leaf.not_after - now).num_days();
+    Ok((days < i64::from(profile.expiry_warning_days)).then(|| {
+        format!(
+            "The certificate of {} expires on {} (in {} days)",
+            gateway,
+            leaf.not_after.format("%Y-%m-%d"),
+            days
+        )
+    }))
+}
+
+/// Split a gateway address into host and port.
+///
+/// Accepts e.g. "vpn.example.com", "vpn.example.com:8443",
+/// "https://vpn.example.com/global-protect" and "[2001:db8::1]:443".
+pub fn host_and_port(gateway: &str) -> (String, u16) {
+    let address = gateway.trim();
+    let address = address
+        .strip_prefix("https://")
+        .or_else(|| address.strip_prefix("http://"))
+        .unwrap_or(address);
+    let address = address.split('/').next().unwrap_or_default();
+
+    let (host, port) = if let Some(bracketed) = address.strip_prefix('[') {
+        match bracketed.split_once(']') {
+            Some((host, rest)) => (host, rest.strip_prefix(':')),
+            None => (bracketed, None),
+        }
+    } else {
+        match address.split_once(':') {
+            // More than one colon: a bare IPv6 address
+            Some((host, port)) if !port.contains(':') => (host, Some(port)),
+            _ => (address, None),
+        }
+    };
+
+    let port = port
+        .and_then(|port| port.parse().ok())
+        .unwrap_or(DEFAULT_PORT);
+    (host.to_string(), port)
+}
+
+/// PEM certificates in `s_client -showcerts` output.
+fn pem_blocks(output: &str) -> Vec<&str> {
+    let mut blocks = Vec::new();
+    let mut rest = output;
+    while let Some(start) = rest.find(PEM_BEGIN) {
+        let Some(length) = rest[start..].find(PEM_END) else {
+            break;
+        };
+        let end = start + length + PEM_END.len();
+        blocks.push(&rest[start..end]);
+        rest = &rest[end..];
+    }
+    blocks
+}
+
+/// Reason for a failed verification in `s_client` output, e.g. "self-signed
+/// certificate in certificate chain" for "Verify return code: 19 (...)".
+fn verify_error(output: &str) -> Option<String> {
+    let Some(result) = output
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("Verify return code: "))
+    else {
+        return Some("the certificate could not be verified".to_string());
+    };
+    if result.starts_with("0 ") {
+        return None;
+    }
+
+    let reason = result
+        .split_once('(')
+        .and_then(|(_, reason)| reason.strip_suffix(')'))
+        .unwrap_or(result);
+    Some(reason.to_string())
+}
+
+/// Read the details of a PEM certificate.
+async fn describe(pem: &str) -> Result<CertificateInfo> {
+    let args = [
+        "x509",
+        "-noout",
+        "-subject",
+        "-issuer",
+        "-enddate",
+        "-fingerprint",
+        "-sha256",
+        "-ext",
+        "subjectAltName",
+        "-nameopt",
+        "RFC2253",
+    ];
+    let output = certificate::openssl(&args, Some(pem.as_bytes()))
+        .await
+        .context("Failed to read gateway certificate")?;
+    let output = String::from_utf8_lossy(&output);
+
+    let mut subject = None;
+    let mut issuer = String::new();
+    let mut not_after = None;
+    let mut pin = None;
+    let mut alt_names = Vec::new();
+
+    let mut lines = output.lines();
+    while let Some(line) = lines.next() {
+        if let Some(value) = line.strip_prefix("subject=") {
+            subject = Some(value.trim().to_string());
+        } else if let Some(value) = line.strip_prefix("issuer=") {
+            issuer = value.trim().to_string();
+        } else if let Some(value) = line.strip_prefix("notAfter=") {
+            not_after = Some(certificate::parse_date(value.trim())?);
+        } else if let Some((_, fingerprint)) = line.split_once("Fingerprint=") {
+            let hex = fingerprint.trim().replace(':', "").to_ascii_lowercase();
+            pin = Some(format!("sha256:{}", hex));
+        } else if line.starts_with("X509v3 Subject Alternative Name") {
+            // The names follow on the next, indented line
+            alt_names = lines
+                .next()
+                .unwrap_or_default()
+                .split(',')
+                .map(|name| name.trim().to_string())
+                .filter(|name| !name.is_empty())
+                .collect();
+        }
+    }
+
+    Ok(CertificateInfo {
+        subject: subject.context("Certificate has no subject")?,
+        issuer,
+        alt_names,
+        not_after: not_after.context("Certificate has no expiry date")?,
+        pin: pin.context("Certificate has no fingerprint")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    const PIN: &str = "sha256:0123456789abcdef";
+
+    fn profile(pin: Option<&str>) -> Profile {
+        let mut profile = Profile::new("Work".to_string(), "vpn.example.com".to_string());
+        profile.pinned_certificate = pin.map(str::to_string);
+        profile
+    }
+
+    fn certificates(expires_in: Duration, verify_error: Option<&str>) -> GatewayCertificates {
+        GatewayCertificates {
+            chain: vec![CertificateInfo {
+                subject: "CN=vpn.example.com".to_string(),
+                issuer: "CN=Example CA".to_string(),
+                alt_names: vec!["DNS:vpn.example.com".to_string()],
+                not_after: Utc::now() + expires_in,
+                pin: PIN.to_string(),
+            }],
+            verify_error: verify_error.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn trusted_certificate_passes() {
+        let inspected = Ok(certificates(Duration::days(365), None));
+        assert_eq!(verify(&profile(None), &inspected), Ok(None));
+        assert_eq!(verify(&profile(Some(PIN)), &inspected), Ok(None));
+    }
+
+    #[test]
+    fn pin_mismatch_is_refused() {
+        let inspected = Ok(certificates(Duration::days(365), None));
+        let error = verify(&profile(Some("sha256:ffff")), &inspected).unwrap_err();
+
+        assert_eq!(
+            error,
+            VpnError::PinMismatch {
+                gateway: "vpn.example.com".to_string(),
+                pinned: "sha256:ffff".to_string(),
+                presented: PIN.to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn expired_certificate_is_refused_even_if_pinned() {
+        let inspected = Ok(certificates(-Duration::days(1), None));
+
+        for pin in [None, Some(PIN)] {
+            let error = verify(&profile(pin), &inspected).unwrap_err();
+            assert!(matches!(error, VpnError::CertificateExpired { .. }));
+        }
+    }
+
+    #[test]
+    fn untrusted_certificate_is_refused_even_if_pinned() {
+        let inspected = Ok(certificates(
+            Duration::days(365),
+            Some("self-signed certificate"),
+        ));
+
+        for pin in [None, Some(PIN)] {
+            let error = verify(&profile(pin), &inspected).unwrap_err();
+            assert!(matches!(error, VpnError::UntrustedCertificate { .. }));
+        }
+    }
+
+    #[test]
+    fn expiring_certificate_is_reported() {
+        let inspected = Ok(certificates(Duration::days(5) + Duration::hours(1), None));
+        let warning = verify(&profile(None), &inspected).unwrap().unwrap();
+
+        assert!(warning.starts_with("The certificate of vpn.example.com expires on"));
+        assert!(warning.ends_with("(in 5 days)"), "{}", warning);
+    }
+
+    #[test]
+    fn unavailable_certificate_is_only_fatal_with_a_pin() {
+        let error = VpnError::CertificateUnavailable {
+            gateway: "vpn.example.com".to_string(),
+            reason: "Connection refused".to_string(),
+        };
+        let inspected = Err(error.clone());
+
+        let warning = verify(&profile(None), &inspected).unwrap().unwrap();
+        assert!(warning.ends_with("connecting without checking it"));
+        assert_eq!(verify(&profile(Some(PIN)), &inspected), Err(error));
+    }
+
+    #[test]
+    fn splits_host_and_port() {
+        let split = |gateway| host_and_port(gateway);
+        assert_eq!(
+            split("vpn.example.com"),
+            ("vpn.example.com".to_string(), 443)
+        );
+        assert_eq!(
+            split(" vpn.example.com:8443 "),
+            ("vpn.example.com".to_string(), 8443)
+        );
+        assert_eq!(
+            split("https://vpn.example.com/global-protect"),
+            ("vpn.example.com".to_string(), 443)
+        );
+        assert_eq!(
+            split("[2001:db8::1]:4443"),
+            ("2001:db8::1".to_string(), 4443)
+        );
+        assert_eq!(split("2001:db8::1"), ("2001:db8::1".to_string(), 443));
+        assert_eq!(
+            split("vpn.example.com:https"),
+            ("vpn.example.com".to_string(), 443)
+        );
+        assert_eq!(split(""), (String::new(), 443));
+    }
+
+    #[test]
+    fn finds_pem_blocks() {
+        let output = format!(
+            "depth=0\n{b}\nAAA\n{e}\nsubject=x\n{b}\nBBB\n{e}\n{b}\ntruncated",
+            b = PEM_BEGIN,
+            e = PEM_END
+        );
+        let blocks = pem_blocks(&output);
+
+        assert_eq!(blocks.len(), 2);
+        assert!(blocks[0].contains("AAA") && blocks[1].contains("BBB"));
+        assert!(blocks.iter().all(|b| b.ends_with(PEM_END)));
+    }
+
+    #[test]
+    fn reads_verify_result() {
+        assert_eq!(verify_error("    Verify return code: 0 (ok)\n"), None);
+        assert_eq!(
+            verify_error("Verify return code: 19 (self-signed certificate in certificate chain)"),
+            Some("self-signed certificate in certificate chain".to_string())
+        );
+        assert_eq!(
+            verify_error("no result"),
+            Some("the certificate could not be verified".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn describes_certificate() {
+        let output = std::process::Command::new("openssl")
+            .args([
+                "req",
+                "-x509",
+                "-newkey",
+                "rsa:2048",
+                "-nodes",
+                "-days",
+                "30",
+                "-subj",
+                "/CN=vpn.example.com",
+                "-addext",
+                "subjectAltName=DNS:vpn.example.com,IP:192.0.2.1",
+                "-keyout",
+                "/dev/null",
+            ])
+            .stderr(std::process::Stdio::null())
+            .output()
+            .unwrap();
+        let pem = String::from_utf8(output.stdout).unwrap();
+        let fingerprint = certificate::openssl(
+            &["x509", "-noout", "-fingerprint", "-sha256"],
+            Some(pem.as_bytes()),
+        )
+        .await
+        .unwrap();
+        let fingerprint = String::from_utf8(fingerprint).unwrap();
+
+        let info = describe(&pem).await.unwrap();
+
+        assert_eq!(info.subject, "CN=vpn.example.com");
+        assert_eq!(info.issuer, "CN=vpn.example.com");
+        assert_eq!(
+            info.alt_names,
+            ["DNS:vpn.example.com", "IP Address:192.0.2.1"]
+        );
+        assert_eq!((info.not_after - Utc::now()).num_days(), 29);
+        assert!(info.pin.starts_with("sha256:") && info.pin.len() == 7 + 64);
+        let hex = info.pin.strip_prefix("sha256:").unwrap();
+        assert!(
+            fingerprint
+                .replace(':', "")
+                .to_ascii_lowercase()
+                .contains(hex),
+            "{}",
+            fingerprint
+        );
+    }
+}
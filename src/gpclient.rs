@@ -1,8 +1,14 @@
 //! GlobalProtect VPN client wrapper for Iced
 
+use crate::certificate;
+use crate::config::{AuthMethod, ClientOs, Profile};
+use crate::killswitch;
+use crate::privileges;
+use crate::secret::Secret;
+use crate::session;
 use anyhow::{Context, Result};
 use log::{info, warn};
-use serde::{Deserialize, Serialize};
+use std::path::Path;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicU32, Ordering};
 use tokio::io::AsyncWriteExt;
@@ -16,15 +22,64 @@ const LOCK_FILE: &str = "/var/run/gpclient.lock";
 /// Global storage for the gpclient process ID, used for cleanup on exit
 static GPCLIENT_PID: AtomicU32 = AtomicU32::new(0);
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone)]
 pub struct VpnConfig {
     pub gateway: String,
     pub username: String,
-    pub password: String,
+    pub password: Secret,
     pub authgroup: Option<String>,
     pub as_gateway: bool,
     pub fix_openssl: bool,
     pub csd_wrapper: Option<String>,
+    pub connect_timeout_secs: u64,
+    pub auth_method: AuthMethod,
+    pub os: ClientOs,
+    pub client_version: Option<String>,
+    pub user_agent: Option<String>,
+    pub hip: bool,
+    pub disable_ipv6: bool,
+    pub reconnect_timeout_secs: Option<u32>,
+    pub mtu: Option<u16>,
+    pub certificate: Option<String>,
+    pub certificate_key: Option<String>,
+    /// Proxy URL including credentials
+    pub proxy: Option<Secret>,
+    /// vpnc-script to use instead of openconnect's default
+    pub script: Option<String>,
+    /// Fingerprint the gateway's certificate must have, `sha256:<hex>`
+    pub pinned_certificate: Option<String>,
+}
+
+impl VpnConfig {
+    /// Build the connection settings for a profile.
+    ///
+    /// `password` is ignored by gpclient unless the profile uses
+    /// [`AuthMethod::Password`].
+    pub fn from_profile(profile: &Profile, password: Secret) -> Self {
+        Self {
+            gateway: profile.gateway.clone(),
+            username: profile.username.clone(),
+            password,
+            authgroup: profile.authgroup.clone(),
+            as_gateway: profile.as_gateway,
+            fix_openssl: profile.fix_openssl,
+            csd_wrapper: profile.csd_wrapper.clone(),
+            connect_timeout_secs: profile.connect_timeout_secs,
+            auth_method: profile.auth_method,
+            os: profile.os,
+            client_version: profile.client_version.clone(),
+            user_agent: profile.user_agent.clone(),
+            hip: profile.hip,
+            disable_ipv6: profile.disable_ipv6,
+            reconnect_timeout_secs: profile.reconnect_timeout_secs,
+            mtu: profile.mtu,
+            certificate: profile.certificate.clone(),
+            certificate_key: profile.certificate_key.clone(),
+            proxy: None,
+            script: None,
+            pinned_certificate: profile.pinned_certificate.clone(),
+        }
+    }
 }
 
 impl Default for VpnConfig {
@@ -32,11 +87,25 @@ impl Default for VpnConfig {
         Self {
             gateway: String::new(),
             username: String::new(),
-            password: String::new(),
+            password: Secret::default(),
             authgroup: None,
             as_gateway: true,
             fix_openssl: true,
             csd_wrapper: None,
+            connect_timeout_secs: crate::config::DEFAULT_CONNECT_TIMEOUT_SECS,
+            auth_method: AuthMethod::Password,
+            os: ClientOs::Linux,
+            client_version: None,
+            user_agent: None,
+            hip: false,
+            disable_ipv6: false,
+            reconnect_timeout_secs: None,
+            mtu: None,
+            certificate: None,
+            certificate_key: None,
+            proxy: None,
+            script: None,
+            pinned_certificate: None,
         }
     }
 }
@@ -126,7 +195,7 @@ impl GpclientProcess {
         // Clear the global PID
         GPCLIENT_PID.store(0, Ordering::SeqCst);
 
-        cleanup_lock_file();
+        cleanup_files();
         Ok(())
     }
 }
@@ -167,10 +236,16 @@ impl Drop for GpclientProcess {
         GPCLIENT_PID.store(0, Ordering::SeqCst);
 
         // Always try to cleanup lock file
-        cleanup_lock_file();
+        cleanup_files();
     }
 }
 
+/// Remove the files that only live as long as gpclient runs.
+fn cleanup_files() {
+    cleanup_lock_file();
+    certificate::remove_unlocked();
+}
+
 fn cleanup_lock_file() {
     match std::fs::remove_file(LOCK_FILE) {
         Ok(_) => info!("Successfully removed lock file"),
@@ -185,6 +260,111 @@ pub fn create_vpn_state() -> VpnState {
     Arc::new(Mutex::new(GpclientProcess::new()))
 }
 
+/// Arguments for `gpclient` that connect with `config`.
+///
+/// `csd_wrapper` is the resolved HIP script, if any, run as `csd_uid`.
+fn connect_args(config: &VpnConfig, csd_wrapper: Option<&str>, csd_uid: u32) -> Vec<String> {
+    let mut args: Vec<String> = Vec::new();
+    let mut arg = |value: &str| args.push(value.to_string());
+
+    if config.fix_openssl {
+        arg("--fix-openssl");
+    }
+
+    arg("connect");
+
+    if config.as_gateway {
+        arg("--as-gateway");
+    }
+
+    if let Some(ref authgroup) = config.authgroup {
+        arg("--authgroup");
+        arg(authgroup);
+    }
+
+    arg("--os");
+    arg(config.os.as_arg());
+
+    if let Some(ref version) = config.client_version {
+        arg("--client-version");
+        arg(version);
+    }
+
+    if let Some(ref user_agent) = config.user_agent {
+        arg("--user-agent");
+        arg(user_agent);
+    }
+
+    if config.hip {
+        arg("--hip");
+    }
+
+    if config.disable_ipv6 {
+        arg("--disable-ipv6");
+    }
+
+    if let Some(secs) = config.reconnect_timeout_secs {
+        arg("--reconnect-timeout");
+        arg(&secs.to_string());
+    }
+
+    if let Some(mtu) = config.mtu {
+        arg("--mtu");
+        arg(&mtu.to_string());
+    }
+
+    if let Some(ref script) = config.script {
+        arg("--script");
+        arg(script);
+    }
+
+    // Checked again by openconnect on the tunnel's own connection
+    if let Some(ref pin) = config.pinned_certificate {
+        arg("--servercert");
+        arg(pin);
+    }
+
+    if let Some(ref certificate) = config.certificate {
+        arg("--certificate");
+        arg(certificate);
+
+        if let Some(ref key) = config.certificate_key {
+            arg("--sslkey");
+            arg(key);
+        }
+
+        // Never --key-password: arguments are readable by every local user.
+        // Protected keys arrive here already decrypted (see certificate.rs).
+    }
+
+    if let Some(wrapper) = csd_wrapper.filter(|w| !w.is_empty()) {
+        arg("--csd-wrapper");
+        arg(wrapper);
+        // gpclient runs the wrapper as root unless told otherwise
+        arg("--csd-uid");
+        arg(&csd_uid.to_string());
+    }
+
+    match config.auth_method {
+        AuthMethod::Password => {
+            arg("--user");
+            arg(&config.username);
+            arg("--passwd-on-stdin");
+        }
+        AuthMethod::Browser => {
+            if !config.username.is_empty() {
+                arg("--user");
+                arg(&config.username);
+            }
+            arg("--browser");
+            arg("default");
+        }
+    }
+
+    arg(&config.gateway);
+    args
+}
+
 pub async fn connect_vpn(state: VpnState, config: VpnConfig) -> Result<String> {
     info!("Starting VPN connection to {}", config.gateway);
 
@@ -198,35 +378,45 @@ pub async fn connect_vpn(state: VpnState, config: VpnConfig) -> Result<String> {
 
     // Build command outside the lock
     let mut cmd = Command::new(GPCLIENT_BINARY);
-    cmd.stdin(std::process::Stdio::piped())
-        .stdout(std::process::Stdio::inherit())
-        .stderr(std::process::Stdio::inherit());
-
-    if config.fix_openssl {
-        cmd.arg("--fix-openssl");
-    }
-
-    cmd.arg("connect");
-
-    if config.as_gateway {
-        cmd.arg("--as-gateway");
-    }
+    let stdin = match config.auth_method {
+        AuthMethod::Password => std::process::Stdio::piped(),
+        AuthMethod::Browser => std::process::Stdio::null(),
+    };
+    // Piped to pick up the session limits, then passed on (see session.rs)
+    cmd.stdin(stdin)
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped());
 
     // Use config csd_wrapper if provided, otherwise try to find it dynamically
     let csd_wrapper = config.csd_wrapper.clone().or_else(find_csd_wrapper);
-    if let Some(ref wrapper) = csd_wrapper
-        && !wrapper.is_empty()
-    {
-        cmd.arg("--csd-wrapper").arg(wrapper);
+    // Both run as root and may come from a profile the user can edit
+    let programs = [&csd_wrapper, &config.script].into_iter().flatten();
+    for program in programs.filter(|program| !program.is_empty()) {
+        privileges::check_root_owned(Path::new(program))
+            .with_context(|| format!("Refusing to run {}", program))?;
+    }
+    let csd_uid = privileges::invoking_uid().as_raw();
+    cmd.args(connect_args(&config, csd_wrapper.as_deref(), csd_uid));
+
+    // gpclient has no proxy option; its portal and gateway requests honour
+    // the standard variables, the tunnel opened by openconnect does not (see
+    // proxy.rs). The environment, unlike the arguments, is only readable by
+    // the process owner.
+    if let Some(ref proxy) = config.proxy {
+        for variable in ["https_proxy", "HTTPS_PROXY", "all_proxy", "ALL_PROXY"] {
+            cmd.env(variable, proxy.expose());
+        }
     }
-
-    cmd.arg("--user")
-        .arg(&config.username)
-        .arg("--passwd-on-stdin")
-        .arg(&config.gateway);
 
     // Spawn child outside the lock
+    session::reset();
     let mut child = cmd.spawn().context("Failed to spawn gpclient")?;
+    if let Some(stdout) = child.stdout.take() {
+        tokio::spawn(session::watch_output(stdout, false));
+    }
+    if let Some(stderr) = child.stderr.take() {
+        tokio::spawn(session::watch_output(stderr, true));
+    }
 
     // Store the PID globally for cleanup on exit
     if let Some(pid) = child.id() {
@@ -237,7 +427,7 @@ pub async fn connect_vpn(state: VpnState, config: VpnConfig) -> Result<String> {
     // Write password outside the lock
     if let Some(mut stdin) = child.stdin.take() {
         stdin
-            .write_all(config.password.as_bytes())
+            .write_all(config.password.expose().as_bytes())
             .await
             .context("Failed to write password")?;
         stdin.write_all(b"\n").await?;
@@ -252,16 +442,9 @@ pub async fn connect_vpn(state: VpnState, config: VpnConfig) -> Result<String> {
         process.child = Some(child);
     } // Drop lock immediately
 
-    // Save config without holding the lock
-    let user_config =
-        crate::config::UserConfig::new(config.gateway.clone(), config.username.clone());
-    if let Err(e) = crate::config::save_config(&user_config) {
-        warn!("Failed to save VPN config: {}", e);
-    }
-
     // Wait for connection to establish or fail
-    // Poll for up to 60 seconds (allow time for slow networks)
-    for i in 0..120 {
+    // Poll every 500ms up to the profile's timeout (allow time for slow networks)
+    for i in 0..config.connect_timeout_secs * 2 {
         sleep(Duration::from_millis(500)).await;
 
         // Check if process has exited (indicates failure)
@@ -282,7 +465,7 @@ pub async fn connect_vpn(state: VpnState, config: VpnConfig) -> Result<String> {
                 None => "Connection failed: Process terminated by signal".to_string(),
             };
 
-            cleanup_lock_file();
+            cleanup_files();
             return Err(anyhow::anyhow!("{}", error_msg));
         }
         drop(process);
@@ -303,17 +486,55 @@ pub async fn connect_vpn(state: VpnState, config: VpnConfig) -> Result<String> {
     drop(process);
 
     Err(anyhow::anyhow!(
-        "Connection timeout: VPN did not establish within 60 seconds"
+        "Connection timeout: VPN did not establish within {} seconds",
+        config.connect_timeout_secs
     ))
 }
 
+/// Process ID of the running gpclient, if any
+pub fn pid() -> Option<u32> {
+    match GPCLIENT_PID.load(Ordering::SeqCst) {
+        0 => None,
+        pid => Some(pid),
+    }
+}
+
+/// Check whether the connected gpclient process is still running.
+///
+/// # Returns
+///
+/// `false` if gpclient has exited on its own, e.g. because the gateway ended
+/// the session or the network went away. The process state is cleared, so a
+/// new connection can be started.
+pub async fn is_running(state: VpnState) -> bool {
+    let mut process = state.lock().await;
+    let Some(ref mut child) = process.child else {
+        return false;
+    };
+
+    match child.try_wait() {
+        Ok(None) => true,
+        Ok(Some(status)) => {
+            warn!("gpclient exited unexpectedly: {}", status);
+            process.child = None;
+            GPCLIENT_PID.store(0, Ordering::SeqCst);
+            cleanup_files();
+            false
+        }
+        Err(e) => {
+            warn!("Failed to check gpclient process: {}", e);
+            true
+        }
+    }
+}
+
 pub async fn disconnect_vpn(state: VpnState) -> Result<String> {
     info!("Disconnecting VPN");
 
     let mut process = state.lock().await;
 
     if !process.is_connected() {
-        cleanup_lock_file();
+        cleanup_files();
         return Ok("Already disconnected".to_string());
     }
 
@@ -382,8 +603,129 @@ pub fn cleanup_on_exit() {
         }
     }
 
-    // Clean up lock file
-    cleanup_lock_file();
+    // Clean up lock file and unlocked key
+    cleanup_files();
+
+    // Nothing may stay blocked once gp-gui is gone
+    if killswitch::is_active()
+        && let Err(e) = killswitch::disable()
+    {
+        warn!("cleanup_on_exit: {:#}", e);
+    }
 
     info!("Cleanup complete");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> VpnConfig {
+        VpnConfig {
+            gateway: "vpn.example.com".to_string(),
+            username: "alice".to_string(),
+            ..VpnConfig::default()
+        }
+    }
+
+    #[test]
+    fn default_password_login() {
+        assert_eq!(
+            connect_args(&config(), None, 1000),
+            [
+                "--fix-openssl",
+                "connect",
+                "--as-gateway",
+                "--os",
+                "linux",
+                "--user",
+                "alice",
+                "--passwd-on-stdin",
+                "vpn.example.com",
+            ]
+        );
+    }
+
+    #[test]
+    fn advanced_options_are_passed() {
+        let config = VpnConfig {
+            fix_openssl: false,
+            as_gateway: false,
+            authgroup: Some("staff".to_string()),
+            os: ClientOs::Windows,
+            client_version: Some("6.1.0".to_string()),
+            user_agent: Some("PAN GlobalProtect".to_string()),
+            hip: true,
+            disable_ipv6: true,
+            reconnect_timeout_secs: Some(30),
+            mtu: Some(1400),
+            script: Some("/run/gp-gui/vpnc-script".to_string()),
+            pinned_certificate: Some("sha256:00ff".to_string()),
+            auth_method: AuthMethod::Browser,
+            ..config()
+        };
+
+        assert_eq!(
+            connect_args(&config, Some("/usr/libexec/hipreport.sh"), 1000),
+            [
+                "connect",
+                "--authgroup",
+                "staff",
+                "--os",
+                "windows",
+                "--client-version",
+                "6.1.0",
+                "--user-agent",
+                "PAN GlobalProtect",
+                "--hip",
+                "--disable-ipv6",
+                "--reconnect-timeout",
+                "30",
+                "--mtu",
+                "1400",
+                "--script",
+                "/run/gp-gui/vpnc-script",
+                "--servercert",
+                "sha256:00ff",
+                "--csd-wrapper",
+                "/usr/libexec/hipreport.sh",
+                "--csd-uid",
+                "1000",
+                "--user",
+                "alice",
+                "--browser",
+                "default",
+                "vpn.example.com",
+            ]
+        );
+    }
+
+    #[test]
+    fn browser_login_omits_empty_username() {
+        let config = VpnConfig {
+            username: String::new(),
+            auth_method: AuthMethod::Browser,
+            ..config()
+        };
+        let args = connect_args(&config, Some(""), 1000);
+
+        assert!(!args.iter().any(|a| a == "--user" || a.starts_with("--csd")));
+        assert!(!args.iter().any(|a| a == "--passwd-on-stdin"));
+    }
+
+    #[test]
+    fn certificate_and_key_are_passed() {
+        let config = VpnConfig {
+            certificate: Some("/home/alice/cert.pem".to_string()),
+            certificate_key: Some("/home/alice/key.pem".to_string()),
+            ..config()
+        };
+        let args = connect_args(&config, None, 1000).join(" ");
+
+        assert!(
+            args.contains("--certificate /home/alice/cert.pem --sslkey /home/alice/key.pem"),
+            "{}",
+            args
+        );
+    }
+}
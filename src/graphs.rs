@@ -0,0 +1,180 @@
+//! Sparklines
+//!
+//! Small line graphs of the connection history for the Connected view, drawn
+//! on an Iced canvas. They have no axes: the newest sample is at the right
+//! edge, the full width spans the history capacity, and each graph is scaled
+//! to its peak value, which the caller shows in the label.
+
+use iced::widget::canvas::{self, Frame, Geometry, Path, Stroke};
+use iced::{Color, Point, Rectangle, Renderer, Theme, mouse};
+
+/// Kind of a line, which sets its colour.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Series {
+    Received,
+    Sent,
+    Latency,
+}
+
+impl Series {
+    /// Colour of the line in `theme`
+    pub fn color(self, theme: &Theme) -> Color {
+        let palette = theme.palette();
+        match self {
+            Series::Received => palette.success,
+            Series::Sent => palette.primary,
+            Series::Latency => palette.warning,
+        }
+    }
+}
+
+/// A graph of one or more series over the same time span.
+#[derive(Debug, Clone)]
+pub struct Sparkline {
+    lines: Vec<(Series, Vec<Option<f32>>)>,
+    /// Number of samples the full width represents
+    capacity: usize,
+}
+
+impl Sparkline {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            lines: Vec::new(),
+            capacity,
+        }
+    }
+
+    /// Add a line; `None` values leave a gap.
+    pub fn line(mut self, series: Series, values: impl IntoIterator<Item = Option<f32>>) -> Self {
+        self.lines.push((series, values.into_iter().collect()));
+        self
+    }
+
+    /// Points of a line in a `width` x `height` frame, split at gaps.
+    fn segments(&self, values: &[Option<f32>], width: f32, height: f32) -> Vec<Vec<Point>> {
+        // Keep a flat line at zero on the baseline instead of scaling it up
+        let peak = self.peak().max(f32::EPSILON);
+        let step = width / self.capacity.saturating_sub(1).max(1) as f32;
+        let offset = self.capacity.saturating_sub(values.len());
+
+        let mut segments = Vec::new();
+        let mut current = Vec::new();
+        for (index, value) in values.iter().enumerate() {
+            let Some(value) = value else {
+                if !current.is_empty() {
+                    segments.push(std::mem::take(&mut current));
+                }
+                continue;
+            };
+            current.push(Point::new(
+                (offset + index) as f32 * step,
+                height - 1.0 - value / peak * (height - 2.0),
+            ));
+        }
+        if !current.is_empty() {
+            segments.push(current);
+        }
+        segments
+    }
+
+    /// Largest value of all lines, which the graph is scaled to
+    pub fn peak(&self) -> f32 {
+        self.lines
+            .iter()
+            .flat_map(|(_, values)| values.iter().flatten())
+            .fold(0.0, |peak, value| peak.max(*value))
+    }
+}
+
+impl<Message> canvas::Program<Message> for Sparkline {
+    type State = ();
+
+    fn draw(
+        &self,
+        _state: &(),
+        renderer: &Renderer,
+        theme: &Theme,
+        bounds: Rectangle,
+        _cursor: mouse::Cursor,
+    ) -> Vec<Geometry> {
+        let mut frame = Frame::new(renderer, bounds.size());
+        let (width, height) = (bounds.width, bounds.height);
+
+        let baseline_color = Color {
+            a: 0.3,
+            ..theme.palette().text
+        };
+        frame.stroke(
+            &Path::line(Point::new(0.0, height), Point::new(width, height)),
+            Stroke::default().with_color(baseline_color).with_width(1.0),
+        );
+
+        for (series, values) in &self.lines {
+            let path = Path::new(|builder| {
+                for segment in self.segments(values, width, height) {
+                    let mut points = segment.into_iter();
+                    if let Some(first) = points.next() {
+                        builder.move_to(first);
+                    }
+                    for point in points {
+                        builder.line_to(point);
+                    }
+                }
+            });
+            frame.stroke(
+                &path,
+                Stroke::default()
+                    .with_color(series.color(theme))
+                    .with_width(1.5),
+            );
+        }
+
+        vec![frame.into_geometry()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn peak_covers_all_lines_and_skips_gaps() {
+        let sparkline = Sparkline::new(4)
+            .line(Series::Received, [Some(1.0), None, Some(3.0)])
+            .line(Series::Sent, [Some(2.0)]);
+        assert_eq!(sparkline.peak(), 3.0);
+        assert_eq!(Sparkline::new(4).peak(), 0.0);
+    }
+
+    #[test]
+    fn newest_sample_is_at_the_right_edge() {
+        let sparkline = Sparkline::new(5).line(Series::Received, [Some(0.0), Some(4.0)]);
+        let segments = sparkline.segments(&[Some(0.0), Some(4.0)], 100.0, 22.0);
+
+        // Two samples of five fill the last quarter; the peak touches the top
+        assert_eq!(
+            segments,
+            [vec![Point::new(75.0, 21.0), Point::new(100.0, 1.0)]]
+        );
+    }
+
+    #[test]
+    fn gaps_split_the_line() {
+        let values = [Some(1.0), Some(1.0), None, Some(1.0), None];
+        let sparkline = Sparkline::new(5).line(Series::Latency, values);
+        let segments = sparkline.segments(&values, 40.0, 12.0);
+
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[0].len(), 2);
+        assert_eq!(segments[1], [Point::new(30.0, 1.0)]);
+    }
+
+    #[test]
+    fn zero_traffic_stays_on_the_baseline() {
+        let values = [Some(0.0), Some(0.0)];
+        let sparkline = Sparkline::new(2).line(Series::Sent, values);
+        let segments = sparkline.segments(&values, 10.0, 12.0);
+
+        assert!(segments[0].iter().all(|point| point.y == 11.0));
+    }
+}
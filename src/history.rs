@@ -0,0 +1,488 @@
+//! Connection History
+//!
+//! Every connection attempt is appended as one JSON line to `history.jsonl`
+//! in the XDG state directory (`~/.local/state/gp-gui`), next to the config
+//! in the config directory. The file is only ever appended to, so a crash
+//! loses at most the attempt in progress, and lines written by a newer
+//! gp-gui that cannot be read are skipped rather than lost.
+//!
+//! The History view filters the records by profile, outcome and date, and
+//! exports the filtered records as CSV or JSON, e.g. to report VPN hours.
+
+use crate::config;
+use crate::statistics::Counters;
+use crate::user_helper::{self, FileOperation};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, NaiveDate, SubsecRound};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+
+const HISTORY_FILE: &str = "history.jsonl";
+const HISTORY_FILE_MODE: u32 = 0o600;
+
+/// How a connection attempt ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Outcome {
+    /// Connected and disconnected by the user
+    Disconnected,
+    /// Connected, then the connection was lost
+    Lost,
+    /// Never connected
+    Failed,
+    /// gp-gui exited while connecting or connected
+    Exited,
+}
+
+impl Outcome {
+    pub const ALL: [Outcome; 4] = [
+        Outcome::Disconnected,
+        Outcome::Lost,
+        Outcome::Failed,
+        Outcome::Exited,
+    ];
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::Disconnected => f.write_str("Disconnected"),
+            Outcome::Lost => f.write_str("Connection lost"),
+            Outcome::Failed => f.write_str("Failed"),
+            Outcome::Exited => f.write_str("gp-gui exited"),
+        }
+    }
+}
+
+/// One connection attempt.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    pub profile: String,
+    pub gateway: String,
+    /// When the attempt started, before authentication
+    pub started: DateTime<Local>,
+    /// When the tunnel came up, `None` if it never did
+    #[serde(default)]
+    pub connected: Option<DateTime<Local>>,
+    pub ended: DateTime<Local>,
+    /// Seconds the tunnel was up
+    #[serde(default)]
+    pub duration_secs: u64,
+    #[serde(default)]
+    pub rx_bytes: u64,
+    #[serde(default)]
+    pub tx_bytes: u64,
+    pub outcome: Outcome,
+    /// Kind of a [`crate::error::VpnError`], e.g. `pin-mismatch`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_kind: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// A connection attempt in progress.
+#[derive(Debug, Clone)]
+pub struct Attempt {
+    pub profile: String,
+    pub gateway: String,
+    pub started: DateTime<Local>,
+    pub connected: Option<DateTime<Local>>,
+}
+
+impl Attempt {
+    pub fn start(profile: &config::Profile) -> Self {
+        Self {
+            profile: profile.name.clone(),
+            gateway: profile.gateway.trim().to_string(),
+            started: Local::now(),
+            connected: None,
+        }
+    }
+
+    /// Close the attempt.
+    ///
+    /// # Arguments
+    ///
+    /// * `outcome` - How the attempt ended
+    /// * `counters` - Last traffic counters of the tunnel, ignored if it
+    ///   never came up
+    /// * `error` - Error message and [`crate::error::VpnError`] kind, if any
+    pub fn finish(
+        self,
+        outcome: Outcome,
+        counters: Counters,
+        error: Option<(String, Option<&str>)>,
+    ) -> Record {
+        let ended = Local::now().trunc_subsecs(0);
+        let connected = self.connected.map(|connected| connected.trunc_subsecs(0));
+        let duration_secs = connected
+            .map(|connected| (ended - connected).num_seconds().max(0) as u64)
+            .unwrap_or_default();
+        let counters = if connected.is_some() {
+            counters
+        } else {
+            Counters::default()
+        };
+        let (error, error_kind) = match error {
+            Some((message, kind)) => (Some(message), kind.map(str::to_string)),
+            None => (None, None),
+        };
+
+        Record {
+            profile: self.profile,
+            gateway: self.gateway,
+            started: self.started.trunc_subsecs(0),
+            connected,
+            ended,
+            duration_secs,
+            rx_bytes: counters.rx_bytes,
+            tx_bytes: counters.tx_bytes,
+            outcome,
+            error_kind,
+            error,
+        }
+    }
+}
+
+/// Which records the History view shows.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Filter {
+    pub profile: Option<String>,
+    pub outcome: Option<Outcome>,
+    /// First and last day of attempts to include
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+}
+
+impl Filter {
+    pub fn matches(&self, record: &Record) -> bool {
+        let day = record.started.date_naive();
+        self.profile.as_ref().is_none_or(|p| *p == record.profile)
+            && self.outcome.is_none_or(|o| o == record.outcome)
+            && self.from.is_none_or(|from| day >= from)
+            && self.to.is_none_or(|to| day <= to)
+    }
+}
+
+/// Append a record to the history file.
+///
+/// # Errors
+///
+/// Returns an error if the state directory or the file cannot be written.
+pub fn append(record: &Record) -> Result<()> {
+    let line = serde_json::to_string(record)? + "\n";
+    user_helper::file_operation::<()>(FileOperation::AppendHistory(line))?;
+    info!(
+        "Recorded connection attempt: {} {}",
+        record.profile, record.outcome
+    );
+    Ok(())
+}
+
+/// Read all records, oldest first.
+///
+/// # Errors
+///
+/// Returns an error if the history file exists but cannot be read.
+pub fn load() -> Result<Vec<Record>> {
+    let content: String = user_helper::file_operation(FileOperation::LoadHistory)?;
+    Ok(parse(&content))
+}
+
+/// Append a serialized record to the history file; run as the invoking user.
+pub fn append_line(line: &str) -> Result<()> {
+    let path = history_path()?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .mode(HISTORY_FILE_MODE)
+        .open(&path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    // One write per line, so concurrent appends do not interleave
+    file.write_all(line.as_bytes())
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Content of the history file, empty if there is none; run as the
+/// invoking user.
+pub fn read_file() -> Result<String> {
+    let path = history_path()?;
+    match fs::read_to_string(&path) {
+        Ok(content) => Ok(content),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(String::new()),
+        Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+/// Records of the history file's lines, skipping those that cannot be read.
+fn parse(content: &str) -> Vec<Record> {
+    let mut records = Vec::new();
+    for (number, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(line) {
+            Ok(record) => records.push(record),
+            Err(e) => warn!("Skipping line {} of {}: {}", number + 1, HISTORY_FILE, e),
+        }
+    }
+    records
+}
+
+/// Records as CSV with a header line.
+pub fn to_csv(records: &[&Record]) -> String {
+    let mut csv = String::from(
+        "profile,gateway,started,connected,ended,duration_secs,rx_bytes,tx_bytes,outcome,\
+         error_kind,error\n",
+    );
+    for record in records {
+        let outcome = serde_json::to_value(record.outcome)
+            .ok()
+            .and_then(|value| value.as_str().map(str::to_string))
+            .unwrap_or_default();
+        let fields = [
+            record.profile.clone(),
+            record.gateway.clone(),
+            record.started.to_rfc3339(),
+            record
+                .connected
+                .map(|connected| connected.to_rfc3339())
+                .unwrap_or_default(),
+            record.ended.to_rfc3339(),
+            record.duration_secs.to_string(),
+            record.rx_bytes.to_string(),
+            record.tx_bytes.to_string(),
+            outcome,
+            record.error_kind.clone().unwrap_or_default(),
+            record.error.clone().unwrap_or_default(),
+        ];
+        let fields: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+        csv.push_str(&fields.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+/// Records as a pretty-printed JSON array.
+///
+/// # Errors
+///
+/// Returns an error if serialization fails.
+pub fn to_json(records: &[&Record]) -> Result<String> {
+    Ok(serde_json::to_string_pretty(records)? + "\n")
+}
+
+/// Write exported records to `path` as the invoking user.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be written.
+pub fn export(path: &str, content: &str) -> Result<()> {
+    user_helper::file_operation::<()>(FileOperation::Write {
+        path: path.to_string(),
+        content: content.to_string(),
+    })?;
+    info!("Exported connection history to {}", path);
+    Ok(())
+}
+
+fn history_path() -> Result<PathBuf> {
+    Ok(config::get_state_dir()?.join(HISTORY_FILE))
+}
+
+/// Characters that make spreadsheet applications treat a cell as a formula
+const FORMULA_PREFIXES: [char; 6] = ['=', '+', '-', '@', '\t', '\r'];
+
+/// Quote a CSV field if it contains a separator, quote or line break.
+///
+/// Fields starting like a formula get a leading `'`, so profile names,
+/// gateways and error messages are shown as text when the export is opened
+/// in a spreadsheet.
+fn csv_field(value: &str) -> String {
+    let value = if value.starts_with(FORMULA_PREFIXES) {
+        format!("'{}", value)
+    } else {
+        value.to_string()
+    };
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn record(profile: &str, outcome: Outcome, error: Option<&str>) -> Record {
+        let started = Local.with_ymd_and_hms(2026, 10, 1, 9, 0, 0).unwrap();
+        Record {
+            profile: profile.to_string(),
+            gateway: "vpn.example.com".to_string(),
+            started,
+            connected: Some(started + chrono::Duration::seconds(5)),
+            ended: started + chrono::Duration::hours(1),
+            duration_secs: 3595,
+            rx_bytes: 1024,
+            tx_bytes: 512,
+            outcome,
+            error_kind: error.map(|_| "auth-failed".to_string()),
+            error: error.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn plain_fields_are_unchanged() {
+        assert_eq!(csv_field("Production"), "Production");
+        assert_eq!(csv_field(""), "");
+        assert_eq!(csv_field("a-b"), "a-b");
+    }
+
+    #[test]
+    fn fields_with_separators_are_quoted() {
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+    }
+
+    #[test]
+    fn formulas_are_neutralised() {
+        assert_eq!(csv_field("=HYPERLINK(\"x\")"), "\"'=HYPERLINK(\"\"x\"\")\"");
+        assert_eq!(csv_field("+1"), "'+1");
+        assert_eq!(csv_field("-2"), "'-2");
+        assert_eq!(csv_field("@SUM(A1)"), "'@SUM(A1)");
+        assert_eq!(csv_field("\tcmd"), "'\tcmd");
+    }
+
+    #[test]
+    fn csv_has_header_and_one_line_per_record() {
+        let ok = record("Work", Outcome::Disconnected, None);
+        let failed = record("=cmd", Outcome::Failed, Some("Bad password, try again"));
+        let csv = to_csv(&[&ok, &failed]);
+        let lines: Vec<&str> = csv.lines().collect();
+
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("profile,gateway,started,"));
+        assert_eq!(lines[0].split(',').count(), 11);
+        assert!(lines[1].starts_with("Work,vpn.example.com,2026-10-01T09:00:00"));
+        assert!(lines[1].ends_with(",3595,1024,512,disconnected,,"));
+        assert!(lines[2].starts_with("'=cmd,"));
+        assert!(lines[2].ends_with(",failed,auth-failed,\"Bad password, try again\""));
+    }
+
+    #[test]
+    fn json_export_round_trips() {
+        let records = [record("Work", Outcome::Lost, None)];
+        let json = to_json(&records.iter().collect::<Vec<_>>()).unwrap();
+        let parsed: Vec<Record> = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, records);
+    }
+
+    #[test]
+    fn filter_matches_profile_outcome_and_days() {
+        let record = record("Work", Outcome::Lost, None);
+        let day = |d| NaiveDate::from_ymd_opt(2026, 10, d).unwrap();
+
+        assert!(Filter::default().matches(&record));
+        let filter = Filter {
+            profile: Some("Work".to_string()),
+            outcome: Some(Outcome::Lost),
+            from: Some(day(1)),
+            to: Some(day(1)),
+        };
+        assert!(filter.matches(&record));
+        assert!(
+            !Filter {
+                profile: Some("Home".to_string()),
+                ..filter.clone()
+            }
+            .matches(&record)
+        );
+        assert!(
+            !Filter {
+                outcome: Some(Outcome::Failed),
+                ..filter.clone()
+            }
+            .matches(&record)
+        );
+        assert!(
+            !Filter {
+                from: Some(day(2)),
+                ..filter.clone()
+            }
+            .matches(&record)
+        );
+        let september = NaiveDate::from_ymd_opt(2026, 9, 30).unwrap();
+        assert!(
+            !Filter {
+                to: Some(september),
+                ..filter
+            }
+            .matches(&record)
+        );
+    }
+
+    #[test]
+    fn outcomes_use_kebab_case_names() {
+        let names: Vec<String> = Outcome::ALL
+            .iter()
+            .map(|outcome| serde_json::to_string(outcome).unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            ["\"disconnected\"", "\"lost\"", "\"failed\"", "\"exited\""]
+        );
+    }
+
+    #[test]
+    fn unreadable_lines_are_skipped() {
+        let line = serde_json::to_string(&record("Work", Outcome::Lost, None)).unwrap();
+        let content = format!("{line}\n\n{{\"profile\": 1}}\nnot json\n{line}\n");
+
+        assert_eq!(parse(&content).len(), 2);
+        assert!(parse("").is_empty());
+    }
+
+    #[test]
+    fn older_lines_get_defaults() {
+        let content = r#"{"profile":"Work","gateway":"vpn.example.com","started":"2026-10-01T09:00:00+00:00","ended":"2026-10-01T09:00:10+00:00","outcome":"failed"}"#;
+        let record = &parse(content)[0];
+
+        assert_eq!(record.connected, None);
+        assert_eq!((record.duration_secs, record.rx_bytes), (0, 0));
+        assert_eq!(record.error, None);
+    }
+
+    #[test]
+    fn finished_attempts_count_traffic_only_once_connected() {
+        let profile = config::Profile::new("Work".to_string(), " vpn.example.com ".to_string());
+        let counters = Counters {
+            rx_bytes: 100,
+            tx_bytes: 50,
+            ..Counters::default()
+        };
+
+        let failed = Attempt::start(&profile).finish(
+            Outcome::Failed,
+            counters,
+            Some(("Bad password".to_string(), Some("auth-failed"))),
+        );
+        assert_eq!(failed.gateway, "vpn.example.com");
+        assert_eq!((failed.connected, failed.rx_bytes), (None, 0));
+        assert_eq!(failed.error_kind.as_deref(), Some("auth-failed"));
+
+        let mut attempt = Attempt::start(&profile);
+        attempt.connected = Some(Local::now() - chrono::Duration::seconds(90));
+        let lost = attempt.finish(Outcome::Lost, counters, None);
+        assert_eq!((lost.rx_bytes, lost.tx_bytes), (100, 50));
+        assert!((89..=91).contains(&lost.duration_secs));
+        assert_eq!((lost.error, lost.error_kind), (None, None));
+    }
+}
@@ -0,0 +1,230 @@
+//! Connection Hooks
+//!
+//! A profile can name a shell command for each [`HookEvent`], e.g. to mount
+//! internal shares after connecting or refresh Kerberos tickets. Hooks run
+//! with `/bin/sh -c` as the invoking user, never as root, and receive the
+//! session in environment variables:
+//!
+//! | Variable            | Value                                   |
+//! |---------------------|-----------------------------------------|
+//! | `GP_GUI_EVENT`      | e.g. `post-connect`                     |
+//! | `GP_GUI_PROFILE`    | Profile name                            |
+//! | `GP_GUI_GATEWAY`    | Portal or gateway address               |
+//! | `GP_GUI_USERNAME`   | VPN username                            |
+//! | `GP_GUI_INTERFACE`  | Tunnel interface, once known            |
+//! | `GP_GUI_IP`         | IPv4 address of the tunnel, once known  |
+//! | `GP_GUI_IP6`        | IPv6 address of the tunnel, once known  |
+//!
+//! `pre-*` hooks complete before gp-gui continues; a failing hook is reported
+//! but does not stop connecting or disconnecting. The output is shown in the
+//! log pane and never written to the application log, as it may contain
+//! anything. A hook is finished when its shell exits, so it can leave
+//! background processes running, e.g. `my-proxy &`.
+
+use crate::config::{HookEvent, Profile};
+use crate::privileges;
+use crate::tunnel::TunnelInfo;
+use anyhow::{Context, Result};
+use log::info;
+use std::process::Stdio;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::process::Command;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::{Duration, timeout};
+
+/// Time a hook may run before it is killed
+const HOOK_TIMEOUT_SECS: u64 = 120;
+
+/// Time allowed for remaining output once the shell has exited; background
+/// processes started by the hook may keep its output open indefinitely
+const OUTPUT_GRACE_MS: u64 = 500;
+
+/// Session details passed to a hook.
+#[derive(Debug, Clone)]
+pub struct HookContext {
+    pub event: HookEvent,
+    pub profile: String,
+    pub gateway: String,
+    pub username: String,
+    pub tunnel: Option<TunnelInfo>,
+}
+
+impl HookContext {
+    pub fn new(event: HookEvent, profile: &Profile, tunnel: Option<TunnelInfo>) -> Self {
+        Self {
+            event,
+            profile: profile.name.clone(),
+            gateway: profile.gateway.clone(),
+            username: profile.username.clone(),
+            tunnel,
+        }
+    }
+}
+
+/// Outcome of a hook run.
+#[derive(Debug, Clone)]
+pub struct HookReport {
+    pub event: HookEvent,
+    /// Lines written to stdout and stderr
+    pub output: Vec<String>,
+    pub result: Result<(), String>,
+}
+
+/// Run `command` for the event in `context`.
+///
+/// Never fails itself; errors are part of the report.
+pub async fn run(command: String, context: HookContext) -> HookReport {
+    info!(
+        "Running {} hook for profile '{}'",
+        context.event, context.profile
+    );
+
+    let mut output = Vec::new();
+    let result = execute(&command, &context, &mut output)
+        .await
+        .map_err(|e| format!("{:#}", e));
+
+    HookReport {
+        event: context.event,
+        output,
+        result,
+    }
+}
+
+async fn execute(command: &str, context: &HookContext, output: &mut Vec<String>) -> Result<()> {
+    let mut cmd = Command::new("/bin/sh");
+    cmd.arg("-c")
+        .arg(command)
+        .env("GP_GUI_EVENT", context.event.as_str())
+        .env("GP_GUI_PROFILE", &context.profile)
+        .env("GP_GUI_GATEWAY", &context.gateway)
+        .env("GP_GUI_USERNAME", &context.username)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    if let Some(tunnel) = &context.tunnel {
+        cmd.env("GP_GUI_INTERFACE", &tunnel.interface);
+        if let Some(ip) = tunnel.ipv4() {
+            cmd.env("GP_GUI_IP", ip.to_string());
+        }
+        if let Some(ip) = tunnel.ipv6() {
+            cmd.env("GP_GUI_IP6", ip.to_string());
+        }
+    }
+    privileges::run_as_invoking_user(&mut cmd)?;
+
+    let mut child = cmd.spawn().context("Failed to start hook")?;
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let readers = [
+        forward_lines(
+            child.stdout.take().context("Hook stdout missing")?,
+            sender.clone(),
+        ),
+        forward_lines(child.stderr.take().context("Hook stderr missing")?, sender),
+    ];
+
+    let status = timeout(Duration::from_secs(HOOK_TIMEOUT_SECS), child.wait()).await;
+    if status.is_ok() {
+        let drain = async {
+            while let Some(line) = receiver.recv().await {
+                output.push(line);
+            }
+        };
+        let _ = timeout(Duration::from_millis(OUTPUT_GRACE_MS), drain).await;
+    }
+    for reader in readers {
+        reader.abort();
+    }
+    while let Ok(line) = receiver.try_recv() {
+        output.push(line);
+    }
+
+    let status = status
+        .map_err(|_| anyhow::anyhow!("Timed out after {} seconds", HOOK_TIMEOUT_SECS))?
+        .context("Failed to wait for hook")?;
+    if !status.success() {
+        return Err(anyhow::anyhow!("Exited with {}", status));
+    }
+    Ok(())
+}
+
+/// Send each line of `stream` to `sender` as it arrives.
+fn forward_lines(
+    stream: impl AsyncRead + Unpin + Send + 'static,
+    sender: mpsc::UnboundedSender<String>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut lines = BufReader::new(stream).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context(tunnel: Option<TunnelInfo>) -> HookContext {
+        let mut profile = Profile::new("Work".to_string(), "vpn.example.com".to_string());
+        profile.username = "alice".to_string();
+        HookContext::new(HookEvent::PostConnect, &profile, tunnel)
+    }
+
+    #[tokio::test]
+    async fn passes_session_in_environment() {
+        let tunnel = TunnelInfo {
+            interface: "tun0".to_string(),
+            addresses: vec!["10.0.0.2".parse().unwrap(), "fd00::2".parse().unwrap()],
+        };
+        let command = "echo $GP_GUI_EVENT $GP_GUI_PROFILE $GP_GUI_GATEWAY $GP_GUI_USERNAME; \
+                       echo $GP_GUI_INTERFACE $GP_GUI_IP $GP_GUI_IP6";
+
+        let report = run(command.to_string(), context(Some(tunnel))).await;
+
+        assert_eq!(report.event, HookEvent::PostConnect);
+        assert_eq!(report.result, Ok(()));
+        assert_eq!(
+            report.output,
+            [
+                "post-connect Work vpn.example.com alice",
+                "tun0 10.0.0.2 fd00::2"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn tunnel_variables_are_unset_without_tunnel() {
+        let command = "echo ${GP_GUI_INTERFACE-unset} ${GP_GUI_IP-unset}";
+        let report = run(command.to_string(), context(None)).await;
+        assert_eq!(report.output, ["unset unset"]);
+    }
+
+    #[tokio::test]
+    async fn failure_keeps_output() {
+        let report = run(
+            "echo starting; echo broken >&2; exit 4".to_string(),
+            context(None),
+        )
+        .await;
+
+        assert_eq!(report.result, Err("Exited with exit status: 4".to_string()));
+        let mut output = report.output;
+        output.sort();
+        assert_eq!(output, ["broken", "starting"]);
+    }
+
+    #[tokio::test]
+    async fn background_processes_do_not_block_the_hook() {
+        let started = std::time::Instant::now();
+        let report = run("sleep 30 & echo started".to_string(), context(None)).await;
+
+        assert_eq!(report.result, Ok(()));
+        assert_eq!(report.output, ["started"]);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
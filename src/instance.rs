@@ -0,0 +1,246 @@
+//! Single-Instance Enforcement
+//!
+//! Only one gp-gui may run at a time: every instance assumes it owns the
+//! gpclient lock file and tears the tunnel down on exit, so two GUIs would
+//! disconnect each other.
+//!
+//! # Mechanism
+//!
+//! The first instance binds an abstract Unix socket
+//! (`@gp-gui.instance.<uid>`, keyed by the invoking user). Abstract sockets
+//! live in the kernel only, so there is no stale socket file to clean up after
+//! a crash. A later launch fails to bind, connects to the
+//! socket instead, forwards its command-line arguments as a single JSON line
+//! and exits. The running instance receives the arguments and raises its
+//! window.
+//!
+//! # Access Control
+//!
+//! Abstract sockets have no file permissions, so anyone on the host can
+//! connect. The socket name keeps different users' instances apart, and the
+//! primary instance checks the peer credentials (`SO_PEERCRED`) of every
+//! connection: only the invoking user, or root (a relaunch through the setuid
+//! wrapper), may raise the window.
+
+use crate::privileges;
+use anyhow::{Context, Result};
+use log::{info, warn};
+use std::io::Write;
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::net::{SocketAddr, UnixListener, UnixStream};
+use std::sync::Mutex;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
+use tokio::time::{Duration, timeout};
+
+/// Prefix of the abstract socket name (without the leading NUL byte)
+const SOCKET_PREFIX: &str = "gp-gui.instance";
+
+/// Time a connection has to send its request; the arguments are written
+/// right after connecting, so only a stuck or malicious client takes longer
+const REQUEST_TIMEOUT_MS: u64 = 2000;
+
+/// Longest request read; command lines are far shorter
+const MAX_REQUEST_LEN: u64 = 64 * 1024;
+
+/// Listener of the primary instance, handed over to the UI subscription
+static LISTENER: Mutex<Option<UnixListener>> = Mutex::new(None);
+
+/// Outcome of trying to become the single running instance.
+pub enum Instance {
+    /// This process is the only instance and owns the socket
+    Primary,
+
+    /// Another instance is running and has received our arguments
+    Secondary,
+}
+
+/// Become the primary instance, or forward `args` to the one already running.
+///
+/// # Arguments
+///
+/// * `args` - Command-line arguments (without the program name)
+///
+/// # Returns
+///
+/// - `Ok(Instance::Primary)` if the socket was bound by this process
+/// - `Ok(Instance::Secondary)` if the arguments were forwarded
+/// - `Err` if neither binding nor forwarding succeeded
+pub fn acquire(args: &[String]) -> Result<Instance> {
+    let name = format!("{}.{}", SOCKET_PREFIX, privileges::invoking_uid());
+    let addr = SocketAddr::from_abstract_name(name.as_bytes())?;
+
+    match UnixListener::bind_addr(&addr) {
+        Ok(listener) => {
+            listener.set_nonblocking(true)?;
+            *LISTENER.lock().unwrap_or_else(|e| e.into_inner()) = Some(listener);
+            info!("Acquired single-instance socket");
+            Ok(Instance::Primary)
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::AddrInUse => {
+            info!("Another instance is running, forwarding arguments");
+            let mut stream =
+                UnixStream::connect_addr(&addr).context("Failed to connect to running instance")?;
+            let mut line = serde_json::to_string(args)?;
+            line.push('\n');
+            stream
+                .write_all(line.as_bytes())
+                .context("Failed to forward arguments to running instance")?;
+            Ok(Instance::Secondary)
+        }
+        Err(e) => Err(e).context("Failed to bind single-instance socket"),
+    }
+}
+
+/// Take the listener bound by [`acquire`].
+///
+/// Returns `None` if this process is not the primary instance or the listener
+/// was already taken.
+pub fn take_listener() -> Option<tokio::net::UnixListener> {
+    let listener = LISTENER.lock().unwrap_or_else(|e| e.into_inner()).take()?;
+
+    match tokio::net::UnixListener::from_std(listener) {
+        Ok(listener) => Some(listener),
+        Err(e) => {
+            warn!("Failed to register single-instance socket: {}", e);
+            None
+        }
+    }
+}
+
+/// Wait for the next launch attempt and return the arguments it forwarded.
+///
+/// Malformed requests and requests from other users are logged and skipped.
+/// Requests are read one at a time, so each gets a deadline and a size
+/// limit; a client that connects and never writes cannot block later ones.
+pub async fn next_activation(listener: &tokio::net::UnixListener) -> Vec<String> {
+    receive(listener, Duration::from_millis(REQUEST_TIMEOUT_MS)).await
+}
+
+async fn receive(listener: &tokio::net::UnixListener, deadline: Duration) -> Vec<String> {
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                warn!("Failed to accept activation request: {}", e);
+                continue;
+            }
+        };
+
+        match stream.peer_cred() {
+            Ok(cred) if is_trusted_peer(cred.uid()) => {}
+            Ok(cred) => {
+                warn!("Ignoring activation request from uid {}", cred.uid());
+                continue;
+            }
+            Err(e) => {
+                warn!("Failed to read activation peer credentials: {}", e);
+                continue;
+            }
+        }
+
+        let mut line = String::new();
+        let mut reader = BufReader::new(stream.take(MAX_REQUEST_LEN));
+        match timeout(deadline, reader.read_line(&mut line)).await {
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => {
+                warn!("Failed to read activation request: {}", e);
+                continue;
+            }
+            Err(_) => {
+                warn!("Activation request timed out");
+                continue;
+            }
+        }
+
+        match serde_json::from_str(&line) {
+            Ok(args) => return args,
+            Err(e) => warn!("Ignoring malformed activation request: {}", e),
+        }
+    }
+}
+
+/// Whether a connection from `uid` may activate this instance.
+fn is_trusted_peer(uid: u32) -> bool {
+    uid == 0 || uid == privileges::invoking_uid().as_raw()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncWriteExt;
+
+    fn listener(name: &str) -> (tokio::net::UnixListener, SocketAddr) {
+        let name = format!("gp-gui.test.{}.{}", std::process::id(), name);
+        let addr = SocketAddr::from_abstract_name(name.as_bytes()).unwrap();
+        let listener = UnixListener::bind_addr(&addr).unwrap();
+        listener.set_nonblocking(true).unwrap();
+        (tokio::net::UnixListener::from_std(listener).unwrap(), addr)
+    }
+
+    async fn send(addr: &SocketAddr, data: &[u8]) {
+        let stream = UnixStream::connect_addr(addr).unwrap();
+        stream.set_nonblocking(true).unwrap();
+        let mut stream = tokio::net::UnixStream::from_std(stream).unwrap();
+        stream.write_all(data).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn receives_forwarded_arguments() {
+        let (listener, addr) = listener("args");
+        send(&addr, b"[\"--connect\",\"work\"]\n").await;
+
+        assert_eq!(next_activation(&listener).await, ["--connect", "work"]);
+    }
+
+    #[tokio::test]
+    async fn skips_malformed_requests() {
+        let (listener, addr) = listener("malformed");
+        send(&addr, b"not json\n").await;
+        send(&addr, b"[]\n").await;
+
+        assert!(next_activation(&listener).await.is_empty());
+    }
+
+    #[test]
+    fn only_root_and_the_invoking_user_are_trusted() {
+        let own = privileges::invoking_uid().as_raw();
+        assert!(is_trusted_peer(0));
+        assert!(is_trusted_peer(own));
+        assert!(!is_trusted_peer(own.wrapping_add(4242)));
+    }
+
+    #[tokio::test]
+    async fn silent_clients_time_out() {
+        let (listener, addr) = listener("silent");
+        let _silent = UnixStream::connect_addr(&addr).unwrap();
+        send(&addr, b"[\"--connect\",\"work\"]\n").await;
+
+        let args = timeout(
+            Duration::from_secs(5),
+            receive(&listener, Duration::from_millis(100)),
+        )
+        .await
+        .expect("blocked by the silent client");
+        assert_eq!(args, ["--connect", "work"]);
+    }
+
+    #[tokio::test]
+    async fn oversized_requests_are_cut_off() {
+        let (listener, addr) = listener("oversized");
+        let mut request = b"[\"".to_vec();
+        request.resize(MAX_REQUEST_LEN as usize * 2, b'a');
+        request.extend_from_slice(b"\"]\n");
+        let stream = UnixStream::connect_addr(&addr).unwrap();
+        stream.set_nonblocking(true).unwrap();
+        let mut stream = tokio::net::UnixStream::from_std(stream).unwrap();
+        // Fails once the request is dropped
+        let writer = tokio::spawn(async move { stream.write_all(&request).await });
+
+        let next = receive(&listener, Duration::from_millis(100));
+        assert!(
+            timeout(Duration::from_millis(500), next).await.is_err(),
+            "oversized request accepted"
+        );
+        writer.abort();
+    }
+}
@@ -0,0 +1,705 @@
+//! Password Storage in the Secret Service Keyring
+//!
+//! Profiles can opt in to "Remember password". The password is then stored in
+//! the freedesktop Secret Service (GNOME Keyring, KWallet, ...) of the user's
+//! session, one item per profile, and looked up again at connect time. Nothing
+//! is ever written to `config.json`.
+//!
+//! # Items
+//!
+//! Items live in the default collection and are identified by the attributes
+//! `application = "gp-gui"` and `profile = <profile name>`, so they can also
+//! be inspected with `secret-tool search application gp-gui`. Proxy passwords
+//! use `proxy-profile = <profile name>` and client key passphrases
+//! `key-profile = <profile name>` instead, so that a search for one kind never
+//! matches another. "Remember password" covers both the VPN password and the
+//! key passphrase.
+//!
+//! The Secret Service API is spoken directly over D-Bus with zbus, using the
+//! `plain` session algorithm: the secret crosses the local session bus
+//! unencrypted, which is only readable by the user who owns the bus. Unlock
+//! and delete prompts are shown by the keyring daemon and awaited here.
+//!
+//! # Running as Root
+//!
+//! The session bus only accepts connections from the user that owns it. When
+//! gp-gui runs as root on behalf of a user, every keyring operation is
+//! delegated to a helper process running as that user (see the `user_helper`
+//! module). Secrets travel over the helper's stdin/stdout pipes only.
+
+use crate::secret::Secret;
+use crate::{privileges, user_helper};
+use anyhow::{Context, Result};
+use futures::StreamExt;
+use log::info;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use zbus::zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Value};
+use zbus::{Connection, Proxy};
+
+/// Value of the `application` attribute on all items
+const APPLICATION: &str = "gp-gui";
+
+const SERVICE: &str = "org.freedesktop.secrets";
+const SERVICE_PATH: &str = "/org/freedesktop/secrets";
+const DEFAULT_COLLECTION: &str = "/org/freedesktop/secrets/aliases/default";
+
+const SERVICE_INTERFACE: &str = "org.freedesktop.Secret.Service";
+const COLLECTION_INTERFACE: &str = "org.freedesktop.Secret.Collection";
+const ITEM_INTERFACE: &str = "org.freedesktop.Secret.Item";
+const PROMPT_INTERFACE: &str = "org.freedesktop.Secret.Prompt";
+
+/// A secret as received over D-Bus: session, parameters, value, content type
+type DbusSecret = (OwnedObjectPath, Vec<u8>, Vec<u8>, String);
+
+/// What a keyring item holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SecretKind {
+    /// VPN password of a profile
+    Password,
+    /// Password for the proxy of a profile
+    Proxy,
+    /// Passphrase of the client certificate key of a profile
+    KeyPassphrase,
+}
+
+impl SecretKind {
+    /// Attribute holding the profile name
+    fn attribute(self) -> &'static str {
+        match self {
+            SecretKind::Password => "profile",
+            SecretKind::Proxy => "proxy-profile",
+            SecretKind::KeyPassphrase => "key-profile",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            SecretKind::Password => "VPN password",
+            SecretKind::Proxy => "proxy password",
+            SecretKind::KeyPassphrase => "key passphrase",
+        }
+    }
+
+    /// Name used on the helper command line
+    fn as_arg(self) -> &'static str {
+        match self {
+            SecretKind::Password => "password",
+            SecretKind::Proxy => "proxy",
+            SecretKind::KeyPassphrase => "key",
+        }
+    }
+
+    fn from_arg(arg: &str) -> Option<Self> {
+        match arg {
+            "password" => Some(SecretKind::Password),
+            "proxy" => Some(SecretKind::Proxy),
+            "key" => Some(SecretKind::KeyPassphrase),
+            _ => None,
+        }
+    }
+}
+
+fn attributes(kind: SecretKind, profile: &str) -> HashMap<&str, &str> {
+    HashMap::from([("application", APPLICATION), (kind.attribute(), profile)])
+}
+
+fn label(kind: SecretKind, profile: &str) -> String {
+    format!("gp-gui {} ({})", kind.label(), profile)
+}
+
+/// An open session with the Secret Service.
+struct Keyring {
+    connection: Connection,
+    service: Proxy<'static>,
+    session: OwnedObjectPath,
+}
+
+impl Keyring {
+    async fn open() -> Result<Self> {
+        let connection = Connection::session()
+            .await
+            .context("Failed to connect to the session bus")?;
+        Self::on(connection).await
+    }
+
+    /// Open a session with the Secret Service reachable over `connection`.
+    async fn on(connection: Connection) -> Result<Self> {
+        let service =
+            Proxy::new_owned(connection.clone(), SERVICE, SERVICE_PATH, SERVICE_INTERFACE).await?;
+
+        let (_, session): (OwnedValue, OwnedObjectPath) = service
+            .call("OpenSession", &("plain", Value::from("")))
+            .await
+            .context("No Secret Service available (is a keyring daemon running?)")?;
+
+        Ok(Self {
+            connection,
+            service,
+            session,
+        })
+    }
+
+    async fn proxy(&self, path: &str, interface: &'static str) -> Result<Proxy<'static>> {
+        let path = ObjectPath::try_from(path)?.into_owned();
+        Ok(Proxy::new_owned(self.connection.clone(), SERVICE, path, interface).await?)
+    }
+
+    /// Wait for the user to answer a prompt, unless `prompt` is the null path.
+    async fn complete_prompt(&self, prompt: &OwnedObjectPath) -> Result<()> {
+        if prompt.as_str() == "/" {
+            return Ok(());
+        }
+
+        let proxy = self.proxy(prompt.as_str(), PROMPT_INTERFACE).await?;
+        let mut completed = proxy.receive_signal("Completed").await?;
+        proxy.call_method("Prompt", &("",)).await?;
+
+        let message = completed
+            .next()
+            .await
+            .context("Keyring prompt disappeared")?;
+        let (dismissed, _): (bool, OwnedValue) = message.body().deserialize()?;
+        if dismissed {
+            return Err(anyhow::anyhow!("Keyring prompt was dismissed"));
+        }
+        Ok(())
+    }
+
+    async fn unlock(&self, objects: Vec<OwnedObjectPath>) -> Result<()> {
+        if objects.is_empty() {
+            return Ok(());
+        }
+        let (_, prompt): (Vec<OwnedObjectPath>, OwnedObjectPath) =
+            self.service.call("Unlock", &(objects,)).await?;
+        self.complete_prompt(&prompt).await
+    }
+
+    /// Find (and unlock) the items of `kind` stored for `profile`.
+    async fn find(&self, kind: SecretKind, profile: &str) -> Result<Vec<OwnedObjectPath>> {
+        let (mut unlocked, locked): (Vec<OwnedObjectPath>, Vec<OwnedObjectPath>) = self
+            .service
+            .call("SearchItems", &(attributes(kind, profile),))
+            .await?;
+
+        self.unlock(locked.clone()).await?;
+        unlocked.extend(locked);
+        Ok(unlocked)
+    }
+
+    async fn store(&self, kind: SecretKind, profile: &str, password: &Secret) -> Result<()> {
+        let collection = OwnedObjectPath::try_from(DEFAULT_COLLECTION)?;
+        self.unlock(vec![collection]).await?;
+
+        let properties: HashMap<&str, Value> = HashMap::from([
+            (
+                "org.freedesktop.Secret.Item.Label",
+                Value::from(label(kind, profile)),
+            ),
+            (
+                "org.freedesktop.Secret.Item.Attributes",
+                Value::from(attributes(kind, profile)),
+            ),
+        ]);
+        // Sent by reference so no unzeroed copy of the password is made
+        let secret = (
+            &self.session,
+            &[] as &[u8],
+            password.expose().as_bytes(),
+            "text/plain",
+        );
+
+        let collection = self.proxy(DEFAULT_COLLECTION, COLLECTION_INTERFACE).await?;
+        let (_, prompt): (OwnedObjectPath, OwnedObjectPath) = collection
+            .call("CreateItem", &(properties, secret, true))
+            .await?;
+        self.complete_prompt(&prompt).await
+    }
+
+    async fn lookup(&self, kind: SecretKind, profile: &str) -> Result<Option<Secret>> {
+        let Some(item) = self.find(kind, profile).await?.into_iter().next() else {
+            return Ok(None);
+        };
+
+        let item = self.proxy(item.as_str(), ITEM_INTERFACE).await?;
+        let (_, _, value, _): DbusSecret = item.call("GetSecret", &(&self.session,)).await?;
+        let password = Secret::from_utf8(value).context("Stored password is not valid UTF-8")?;
+        Ok(Some(password))
+    }
+
+    async fn forget(&self, kind: SecretKind, profile: &str) -> Result<()> {
+        for item in self.find(kind, profile).await? {
+            let item = self.proxy(item.as_str(), ITEM_INTERFACE).await?;
+            let prompt: OwnedObjectPath = item.call("Delete", &()).await?;
+            self.complete_prompt(&prompt).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Store the password for `profile`, replacing any previous one.
+pub async fn store_password(profile: &str, password: &Secret) -> Result<()> {
+    store(SecretKind::Password, profile, password).await
+}
+
+/// Look up the stored password for `profile`.
+///
+/// # Returns
+///
+/// - `Ok(Some(password))` if an item exists
+/// - `Ok(None)` if nothing is stored for this profile
+/// - `Err` if the keyring is unavailable or could not be unlocked
+pub async fn lookup_password(profile: &str) -> Result<Option<Secret>> {
+    lookup(SecretKind::Password, profile).await
+}
+
+/// Remove the stored password and key passphrase for `profile`, if any.
+pub async fn forget_password(profile: &str) -> Result<()> {
+    forget(SecretKind::Password, profile).await?;
+    forget(SecretKind::KeyPassphrase, profile).await
+}
+
+/// Store a secret of `kind` for `profile`, replacing any previous one.
+pub async fn store(kind: SecretKind, profile: &str, secret: &Secret) -> Result<()> {
+    if privileges::is_acting_for_user() {
+        run_helper("store", kind, profile, Some(secret)).await?;
+        return Ok(());
+    }
+
+    Keyring::open().await?.store(kind, profile, secret).await?;
+    info!(
+        "Stored {} for profile '{}' in keyring",
+        kind.label(),
+        profile
+    );
+    Ok(())
+}
+
+/// Look up the secret of `kind` stored for `profile`.
+///
+/// See [`lookup_password`] for the return values.
+pub async fn lookup(kind: SecretKind, profile: &str) -> Result<Option<Secret>> {
+    if privileges::is_acting_for_user() {
+        let secret = run_helper("lookup", kind, profile, None).await?;
+        return Ok((!secret.is_empty()).then_some(secret));
+    }
+
+    Keyring::open().await?.lookup(kind, profile).await
+}
+
+/// Remove the secret of `kind` stored for `profile`, if any.
+pub async fn forget(kind: SecretKind, profile: &str) -> Result<()> {
+    if privileges::is_acting_for_user() {
+        run_helper("forget", kind, profile, None).await?;
+        return Ok(());
+    }
+
+    Keyring::open().await?.forget(kind, profile).await?;
+    info!(
+        "Removed {} for profile '{}' from keyring",
+        kind.label(),
+        profile
+    );
+    Ok(())
+}
+
+/// Move a stored secret of `kind` after a profile was renamed.
+pub async fn rename(kind: SecretKind, old: &str, new: &str) -> Result<()> {
+    if let Some(secret) = lookup(kind, old).await? {
+        store(kind, new, &secret).await?;
+        forget(kind, old).await?;
+    }
+    Ok(())
+}
+
+/// Run a keyring operation in a helper process as the invoking user.
+async fn run_helper(
+    operation: &str,
+    kind: SecretKind,
+    profile: &str,
+    input: Option<&Secret>,
+) -> Result<Secret> {
+    let output = user_helper::run(&["keyring", operation, kind.as_arg(), profile], input).await?;
+    Secret::from_utf8(output).context("Keyring helper returned invalid UTF-8")
+}
+
+/// Helper side of
+/// `gp-gui --user-helper keyring <store|lookup|forget> <password|proxy|key> <profile>`.
+///
+/// `store` reads the secret from stdin and `lookup` writes it to stdout.
+pub async fn helper(args: &[String]) -> Result<()> {
+    let usage =
+        || anyhow::anyhow!("usage: keyring <store|lookup|forget> <password|proxy|key> <profile>");
+    let [operation, kind, profile] = args else {
+        return Err(usage());
+    };
+    let kind = SecretKind::from_arg(kind).ok_or_else(usage)?;
+
+    match operation.as_str() {
+        "store" => {
+            // Preallocated so reading does not reallocate and leave copies
+            let mut input = Vec::with_capacity(4096);
+            std::io::stdin().read_to_end(&mut input)?;
+            let secret = Secret::from_utf8(input).context("Password is not valid UTF-8")?;
+            store(kind, profile, &secret).await
+        }
+        "lookup" => {
+            if let Some(secret) = lookup(kind, profile).await? {
+                let mut stdout = std::io::stdout();
+                stdout.write_all(secret.expose().as_bytes())?;
+                stdout.flush()?;
+            }
+            Ok(())
+        }
+        "forget" => forget(kind, profile).await,
+        other => Err(anyhow::anyhow!("Unknown keyring operation: {}", other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixStream;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn items_are_identified_by_application_kind_and_profile() {
+        assert_eq!(
+            attributes(SecretKind::Password, "Work"),
+            HashMap::from([("application", "gp-gui"), ("profile", "Work")])
+        );
+        assert_eq!(
+            attributes(SecretKind::Proxy, "Work"),
+            HashMap::from([("application", "gp-gui"), ("proxy-profile", "Work")])
+        );
+    }
+
+    #[test]
+    fn label_names_kind_and_profile() {
+        assert_eq!(
+            label(SecretKind::Password, "Work"),
+            "gp-gui VPN password (Work)"
+        );
+        assert_eq!(
+            label(SecretKind::Proxy, "Work"),
+            "gp-gui proxy password (Work)"
+        );
+    }
+
+    #[test]
+    fn kinds_round_trip_through_helper_arguments() {
+        for kind in [
+            SecretKind::Password,
+            SecretKind::Proxy,
+            SecretKind::KeyPassphrase,
+        ] {
+            assert_eq!(SecretKind::from_arg(kind.as_arg()), Some(kind));
+        }
+        assert_eq!(SecretKind::from_arg("profile"), None);
+    }
+
+    /// An item held by [`FakeService`]
+    struct FakeItem {
+        label: String,
+        attributes: HashMap<String, String>,
+        secret: Vec<u8>,
+        locked: bool,
+    }
+
+    type Items = Arc<Mutex<HashMap<u32, FakeItem>>>;
+
+    /// A minimal Secret Service that keeps items in memory.
+    ///
+    /// Prompts are never needed: the null path is returned instead.
+    struct FakeService {
+        items: Items,
+    }
+
+    struct FakeCollection {
+        items: Items,
+        next_id: u32,
+    }
+
+    struct FakeItemObject {
+        id: u32,
+        items: Items,
+    }
+
+    fn item_path(id: u32) -> OwnedObjectPath {
+        OwnedObjectPath::try_from(format!("/org/freedesktop/secrets/collection/login/{id}"))
+            .unwrap()
+    }
+
+    fn item_id(path: &ObjectPath) -> Option<u32> {
+        path.as_str()
+            .strip_prefix("/org/freedesktop/secrets/collection/login/")?
+            .parse()
+            .ok()
+    }
+
+    fn null_path() -> OwnedObjectPath {
+        OwnedObjectPath::try_from("/").unwrap()
+    }
+
+    #[zbus::interface(name = "org.freedesktop.Secret.Service")]
+    impl FakeService {
+        #[zbus(out_args("output", "result"))]
+        fn open_session(
+            &self,
+            algorithm: &str,
+            _input: OwnedValue,
+        ) -> zbus::fdo::Result<(OwnedValue, OwnedObjectPath)> {
+            if algorithm != "plain" {
+                return Err(zbus::fdo::Error::NotSupported(algorithm.into()));
+            }
+            let session = OwnedObjectPath::try_from("/org/freedesktop/secrets/session/1").unwrap();
+            Ok((OwnedValue::from(0u8), session))
+        }
+
+        #[zbus(out_args("unlocked", "locked"))]
+        fn search_items(
+            &self,
+            attributes: HashMap<String, String>,
+        ) -> (Vec<OwnedObjectPath>, Vec<OwnedObjectPath>) {
+            let items = self.items.lock().unwrap();
+            let mut ids: Vec<_> = items
+                .iter()
+                .filter(|(_, item)| {
+                    attributes
+                        .iter()
+                        .all(|(name, value)| item.attributes.get(name) == Some(value))
+                })
+                .map(|(id, item)| (*id, item.locked))
+                .collect();
+            ids.sort();
+
+            let (locked, unlocked): (Vec<_>, Vec<_>) = ids.into_iter().partition(|(_, l)| *l);
+            let paths =
+                |ids: Vec<(u32, bool)>| ids.into_iter().map(|(id, _)| item_path(id)).collect();
+            (paths(unlocked), paths(locked))
+        }
+
+        #[zbus(out_args("unlocked", "prompt"))]
+        fn unlock(&self, objects: Vec<OwnedObjectPath>) -> (Vec<OwnedObjectPath>, OwnedObjectPath) {
+            let mut items = self.items.lock().unwrap();
+            for id in objects.iter().filter_map(|path| item_id(path)) {
+                if let Some(item) = items.get_mut(&id) {
+                    item.locked = false;
+                }
+            }
+            (objects, null_path())
+        }
+    }
+
+    #[zbus::interface(name = "org.freedesktop.Secret.Collection")]
+    impl FakeCollection {
+        #[zbus(out_args("item", "prompt"))]
+        async fn create_item(
+            &mut self,
+            properties: HashMap<String, OwnedValue>,
+            secret: (OwnedObjectPath, Vec<u8>, Vec<u8>, String),
+            replace: bool,
+            #[zbus(object_server)] server: &zbus::ObjectServer,
+        ) -> zbus::fdo::Result<(OwnedObjectPath, OwnedObjectPath)> {
+            let property = |name: &str| {
+                properties
+                    .get(name)
+                    .and_then(|value| value.try_clone().ok())
+                    .ok_or_else(|| zbus::fdo::Error::InvalidArgs(name.into()))
+            };
+            let label = String::try_from(property("org.freedesktop.Secret.Item.Label")?)
+                .map_err(zbus::Error::from)?;
+            let attributes: HashMap<String, String> =
+                HashMap::try_from(property("org.freedesktop.Secret.Item.Attributes")?)
+                    .map_err(zbus::Error::from)?;
+
+            let existing = self
+                .items
+                .lock()
+                .unwrap()
+                .iter()
+                .find(|(_, item)| replace && item.attributes == attributes)
+                .map(|(id, _)| *id);
+            let id = existing.unwrap_or_else(|| {
+                self.next_id += 1;
+                self.next_id
+            });
+
+            let item = FakeItem {
+                label,
+                attributes,
+                secret: secret.2,
+                locked: false,
+            };
+            self.items.lock().unwrap().insert(id, item);
+            if existing.is_none() {
+                let object = FakeItemObject {
+                    id,
+                    items: self.items.clone(),
+                };
+                server.at(item_path(id), object).await?;
+            }
+            Ok((item_path(id), null_path()))
+        }
+    }
+
+    #[zbus::interface(name = "org.freedesktop.Secret.Item")]
+    impl FakeItemObject {
+        fn get_secret(
+            &self,
+            session: OwnedObjectPath,
+        ) -> zbus::fdo::Result<(OwnedObjectPath, Vec<u8>, Vec<u8>, String)> {
+            let items = self.items.lock().unwrap();
+            let item = items
+                .get(&self.id)
+                .ok_or_else(|| zbus::fdo::Error::UnknownObject(self.id.to_string()))?;
+            if item.locked {
+                return Err(zbus::fdo::Error::AccessDenied("Item is locked".into()));
+            }
+            Ok((session, vec![], item.secret.clone(), "text/plain".into()))
+        }
+
+        fn delete(&self) -> OwnedObjectPath {
+            self.items.lock().unwrap().remove(&self.id);
+            null_path()
+        }
+    }
+
+    /// Open a [`Keyring`] on a private connection to a fresh [`FakeService`].
+    async fn fake_keyring() -> (Keyring, Items, Connection) {
+        let items = Items::default();
+        let (server, client) = UnixStream::pair().unwrap();
+
+        let service = FakeService {
+            items: items.clone(),
+        };
+        let collection = FakeCollection {
+            items: items.clone(),
+            next_id: 0,
+        };
+        let server = zbus::connection::Builder::async_io_unix_stream(server)
+            .server(zbus::Guid::generate())
+            .unwrap()
+            .p2p()
+            .serve_at(SERVICE_PATH, service)
+            .unwrap()
+            .serve_at(DEFAULT_COLLECTION, collection)
+            .unwrap()
+            .build();
+        let client = zbus::connection::Builder::async_io_unix_stream(client)
+            .p2p()
+            .build();
+        let (server, client) = futures::try_join!(server, client).unwrap();
+
+        (Keyring::on(client).await.unwrap(), items, server)
+    }
+
+    fn secret(value: &str) -> Secret {
+        Secret::from_utf8(value.as_bytes().to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn stored_passwords_can_be_looked_up() {
+        let (keyring, items, _server) = fake_keyring().await;
+
+        keyring
+            .store(SecretKind::Password, "Work", &secret("hunter2"))
+            .await
+            .unwrap();
+        let found = keyring.lookup(SecretKind::Password, "Work").await.unwrap();
+        assert_eq!(found.unwrap().expose(), "hunter2");
+
+        let items = items.lock().unwrap();
+        let item = items.values().next().unwrap();
+        assert_eq!(item.label, "gp-gui VPN password (Work)");
+        assert_eq!(item.attributes["profile"], "Work");
+    }
+
+    #[tokio::test]
+    async fn storing_again_replaces_the_item() {
+        let (keyring, items, _server) = fake_keyring().await;
+
+        for password in ["old", "new"] {
+            keyring
+                .store(SecretKind::Password, "Work", &secret(password))
+                .await
+                .unwrap();
+        }
+        let found = keyring.lookup(SecretKind::Password, "Work").await.unwrap();
+        assert_eq!(found.unwrap().expose(), "new");
+        assert_eq!(items.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn kinds_and_profiles_do_not_mix() {
+        let (keyring, _items, _server) = fake_keyring().await;
+
+        keyring
+            .store(SecretKind::Password, "Work", &secret("vpn"))
+            .await
+            .unwrap();
+        keyring
+            .store(SecretKind::Proxy, "Work", &secret("proxy"))
+            .await
+            .unwrap();
+
+        let proxy = keyring.lookup(SecretKind::Proxy, "Work").await.unwrap();
+        assert_eq!(proxy.unwrap().expose(), "proxy");
+        let vpn = keyring.lookup(SecretKind::Password, "Work").await.unwrap();
+        assert_eq!(vpn.unwrap().expose(), "vpn");
+        let other = keyring.lookup(SecretKind::Password, "Home").await.unwrap();
+        assert!(other.is_none());
+    }
+
+    #[tokio::test]
+    async fn locked_items_are_unlocked_before_reading() {
+        let (keyring, items, _server) = fake_keyring().await;
+
+        keyring
+            .store(SecretKind::Password, "Work", &secret("hunter2"))
+            .await
+            .unwrap();
+        for item in items.lock().unwrap().values_mut() {
+            item.locked = true;
+        }
+
+        let found = keyring.lookup(SecretKind::Password, "Work").await.unwrap();
+        assert_eq!(found.unwrap().expose(), "hunter2");
+    }
+
+    #[tokio::test]
+    async fn forgetting_removes_only_that_item() {
+        let (keyring, items, _server) = fake_keyring().await;
+
+        keyring
+            .store(SecretKind::Password, "Work", &secret("vpn"))
+            .await
+            .unwrap();
+        keyring
+            .store(SecretKind::Proxy, "Work", &secret("proxy"))
+            .await
+            .unwrap();
+        keyring.forget(SecretKind::Password, "Work").await.unwrap();
+
+        assert!(
+            keyring
+                .lookup(SecretKind::Password, "Work")
+                .await
+                .unwrap()
+                .is_none()
+        );
+        let items = items.lock().unwrap();
+        assert_eq!(items.len(), 1);
+        assert!(
+            items
+                .values()
+                .all(|item| item.attributes.contains_key("proxy-profile"))
+        );
+    }
+
+    #[tokio::test]
+    async fn forgetting_nothing_succeeds() {
+        let (keyring, _items, _server) = fake_keyring().await;
+        keyring.forget(SecretKind::Proxy, "Work").await.unwrap();
+    }
+}
@@ -0,0 +1,469 @@
+//! Kill Switch
+//!
+//! nftables rules that drop outgoing and forwarded traffic unless it goes
+//! through the tunnel, to the VPN gateway, over loopback or, if the profile
+//! allows it, to the local network. DHCP and IPv6 neighbour discovery are
+//! always allowed so the uplink itself keeps working.
+//!
+//! The rules live in their own table, `inet gp_gui_kill_switch`, and are
+//! installed once the tunnel is up. They are deliberately left in place when
+//! the tunnel drops and while reconnecting or re-authenticating (the old
+//! rules still allow the gateway), and replaced atomically by the rules for
+//! the new tunnel, so nothing leaks even if the reconnect fails. They are only
+//! removed when the user disconnects or turns the kill switch off, and by
+//! `gpclient::cleanup_on_exit`. Rules left behind by a gp-gui that crashed are
+//! removed at the next start.
+//!
+//! The gateway is allowed by the addresses gpclient's sockets are connected
+//! to, which covers a portal redirecting to another gateway, plus the
+//! addresses the profile's server name resolved to.
+//!
+//! # Limits
+//!
+//! Nothing is blocked before the first tunnel is up: while gpclient
+//! authenticates and connects, all traffic leaves as usual. A gateway-only
+//! ruleset at that point would also block DNS, the portal's redirects and the
+//! identity provider of a browser (SAML) login, none of which are known in
+//! advance. The profile editor states this next to the kill switch option.
+
+use crate::routes;
+use anyhow::{Context, Result};
+use log::info;
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::Path;
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+const NFT_BINARY: &str = "nft";
+const TABLE: &str = "inet gp_gui_kill_switch";
+
+/// Socket tables of a process, by address family
+const SOCKET_TABLES: [(&str, bool); 4] = [
+    ("tcp", false),
+    ("udp", false),
+    ("tcp6", true),
+    ("udp6", true),
+];
+
+/// What the kill switch lets through.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rules {
+    /// Tunnel interface
+    pub interface: String,
+    /// Addresses of the VPN gateway
+    pub gateways: Vec<IpAddr>,
+    /// Local subnets, empty unless the LAN is allowed
+    pub lan: Vec<(IpAddr, u8)>,
+}
+
+impl Rules {
+    /// Collect the rules for a connection.
+    ///
+    /// # Arguments
+    ///
+    /// * `interface` - Tunnel interface
+    /// * `pid` - gpclient process, whose peers are allowed as gateways
+    /// * `gateway_addresses` - Resolved addresses of the profile's server
+    /// * `allow_lan` - Whether to allow the directly connected subnets
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the routing table cannot be read.
+    pub fn collect(
+        interface: String,
+        pid: Option<u32>,
+        gateway_addresses: &[IpAddr],
+        allow_lan: bool,
+    ) -> Result<Self> {
+        let mut gateways = gateway_addresses.to_vec();
+        for peer in pid.map(peers).unwrap_or_default() {
+            if !gateways.contains(&peer) {
+                gateways.push(peer);
+            }
+        }
+
+        let mut lan = Vec::new();
+        if allow_lan {
+            for route in routes::list()? {
+                let local = route.gateway.is_none()
+                    && !route.is_default()
+                    && route
+                        .interface
+                        .as_ref()
+                        .is_some_and(|name| *name != interface && name != "lo");
+                if local && !lan.contains(&(route.destination, route.prefix_len)) {
+                    lan.push((route.destination, route.prefix_len));
+                }
+            }
+        }
+
+        Ok(Self {
+            interface,
+            gateways,
+            lan,
+        })
+    }
+
+    /// nft script replacing the kill switch table with these rules.
+    pub fn ruleset(&self) -> String {
+        // Shared by the output and forward chains. Gateways and LAN get rules
+        // of their own, as a gateway inside the LAN would overlap in a set.
+        let mut allowed = vec![format!("oifname \"{}\" accept", self.interface)];
+        let gateways: Vec<(IpAddr, u8)> = self
+            .gateways
+            .iter()
+            .map(|ip| (*ip, if ip.is_ipv4() { 32 } else { 128 }))
+            .collect();
+        for subnets in [&gateways, &self.lan] {
+            for (family, ipv6) in [("ip", false), ("ip6", true)] {
+                let elements: Vec<String> = subnets
+                    .iter()
+                    .filter(|(address, _)| address.is_ipv6() == ipv6)
+                    .map(|(address, prefix_len)| format!("{}/{}", address, prefix_len))
+                    .collect();
+                if !elements.is_empty() {
+                    allowed.push(format!(
+                        "{} daddr {{ {} }} accept",
+                        family,
+                        elements.join(", ")
+                    ));
+                }
+            }
+        }
+
+        let mut output = vec![
+            "type filter hook output priority filter; policy drop;".to_string(),
+            "oifname \"lo\" accept".to_string(),
+            "udp sport 68 udp dport 67 accept".to_string(),
+            "udp sport 546 udp dport 547 accept".to_string(),
+            "icmpv6 type { nd-router-solicit, nd-neighbor-solicit, nd-neighbor-advert } accept"
+                .to_string(),
+        ];
+        output.extend(allowed.iter().cloned());
+        let mut forward = vec![
+            "type filter hook forward priority filter; policy drop;".to_string(),
+            "ct state established,related accept".to_string(),
+        ];
+        forward.extend(allowed);
+
+        let chain = |name: &str, rules: &[String]| {
+            let mut chain = format!("  chain {} {{\n", name);
+            for rule in rules {
+                chain.push_str(&format!("    {}\n", rule));
+            }
+            chain.push_str("  }\n");
+            chain
+        };
+        format!(
+            "table {table}\ndelete table {table}\ntable {table} {{\n{}{}}}\n",
+            chain("output", &output),
+            chain("forward", &forward),
+            table = TABLE
+        )
+    }
+}
+
+/// Install or replace the kill switch rules.
+///
+/// # Errors
+///
+/// Returns an error if nft is missing or rejects the rules, e.g. without
+/// root privileges.
+pub async fn enable(rules: Rules) -> Result<()> {
+    let mut child = Command::new(NFT_BINARY)
+        .args(["-f", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to run {}", NFT_BINARY))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(rules.ruleset().as_bytes())
+            .await
+            .context("Failed to pass the rules to nft")?;
+    }
+    let output = child
+        .wait_with_output()
+        .await
+        .with_context(|| format!("Failed to run {}", NFT_BINARY))?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "nft rejected the kill switch rules: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    info!(
+        "Kill switch enabled for {} (gateways {:?}, LAN {:?})",
+        rules.interface, rules.gateways, rules.lan
+    );
+    Ok(())
+}
+
+/// Remove the kill switch rules, if installed.
+///
+/// # Errors
+///
+/// Returns an error if nft is missing or fails.
+pub fn disable() -> Result<()> {
+    // Declaring the table first makes the deletion succeed if it is missing
+    let script = format!("table {table}\ndelete table {table}\n", table = TABLE);
+    let mut child = std::process::Command::new(NFT_BINARY)
+        .args(["-f", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to run {}", NFT_BINARY))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        use std::io::Write;
+        stdin
+            .write_all(script.as_bytes())
+            .context("Failed to pass the rules to nft")?;
+    }
+    let output = child
+        .wait_with_output()
+        .with_context(|| format!("Failed to run {}", NFT_BINARY))?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "Failed to remove the kill switch rules: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    info!("Kill switch disabled");
+    Ok(())
+}
+
+/// Whether the kill switch rules are installed.
+pub fn is_active() -> bool {
+    std::process::Command::new(NFT_BINARY)
+        .args(["list", "table"])
+        .args(TABLE.split(' '))
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// Remote addresses of the connected sockets of process `pid`.
+fn peers(pid: u32) -> Vec<IpAddr> {
+    let proc_dir = Path::new("/proc").join(pid.to_string());
+    let Ok(fds) = fs::read_dir(proc_dir.join("fd")) else {
+        return Vec::new();
+    };
+    let inodes: Vec<String> = fds
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let target = fs::read_link(entry.path()).ok()?;
+            let target = target.to_str()?;
+            Some(
+                target
+                    .strip_prefix("socket:[")?
+                    .strip_suffix(']')?
+                    .to_string(),
+            )
+        })
+        .collect();
+
+    let mut peers = Vec::new();
+    for (table, ipv6) in SOCKET_TABLES {
+        let Ok(content) = fs::read_to_string(proc_dir.join("net").join(table)) else {
+            continue;
+        };
+        // sl local_address rem_address st tx_queue:rx_queue tr:tm->when
+        // retrnsmt uid timeout inode ...
+        for line in content.lines().skip(1) {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (Some(remote), Some(inode)) = (fields.get(2), fields.get(9)) else {
+                continue;
+            };
+            if !inodes.iter().any(|i| i == inode) {
+                continue;
+            }
+            let Some(address) = remote
+                .split(':')
+                .next()
+                .and_then(|hex| parse_hex(hex, ipv6))
+            else {
+                continue;
+            };
+            if !address.is_unspecified() && !address.is_loopback() && !peers.contains(&address) {
+                peers.push(address);
+            }
+        }
+    }
+    peers
+}
+
+/// Parse an address from `/proc/net`, printed as native-endian 32-bit words.
+fn parse_hex(hex: &str, ipv6: bool) -> Option<IpAddr> {
+    let words = hex
+        .as_bytes()
+        .chunks(8)
+        .map(|chunk| {
+            let word = std::str::from_utf8(chunk).ok()?;
+            u32::from_str_radix(word, 16).ok().map(u32::to_ne_bytes)
+        })
+        .collect::<Option<Vec<[u8; 4]>>>()?;
+
+    match (ipv6, words.as_slice()) {
+        (false, [word]) => Some(IpAddr::V4(Ipv4Addr::from(*word))),
+        (true, [a, b, c, d]) => {
+            let mut octets = [0u8; 16];
+            for (i, word) in [a, b, c, d].into_iter().enumerate() {
+                octets[i * 4..i * 4 + 4].copy_from_slice(word);
+            }
+            let address = Ipv6Addr::from(octets);
+            // IPv4 peers of dual-stack sockets
+            Some(match address.to_ipv4_mapped() {
+                Some(v4) => IpAddr::V4(v4),
+                None => IpAddr::V6(address),
+            })
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(gateways: &[&str], lan: &[(&str, u8)]) -> Rules {
+        Rules {
+            interface: "tun0".to_string(),
+            gateways: gateways.iter().map(|ip| ip.parse().unwrap()).collect(),
+            lan: lan
+                .iter()
+                .map(|(ip, prefix_len)| (ip.parse().unwrap(), *prefix_len))
+                .collect(),
+        }
+    }
+
+    /// Rules of `chain` in `ruleset`, without the chain's type line
+    fn chain<'a>(ruleset: &'a str, chain: &str) -> Vec<&'a str> {
+        let start = format!("chain {} {{", chain);
+        ruleset
+            .lines()
+            .map(str::trim)
+            .skip_while(|line| *line != start)
+            .skip(2)
+            .take_while(|line| *line != "}")
+            .collect()
+    }
+
+    #[test]
+    fn replaces_table_with_ipv4_gateway_rules() {
+        assert_eq!(
+            rules(&["192.0.2.1"], &[]).ruleset(),
+            "table inet gp_gui_kill_switch\n\
+             delete table inet gp_gui_kill_switch\n\
+             table inet gp_gui_kill_switch {\n\
+             \x20 chain output {\n\
+             \x20   type filter hook output priority filter; policy drop;\n\
+             \x20   oifname \"lo\" accept\n\
+             \x20   udp sport 68 udp dport 67 accept\n\
+             \x20   udp sport 546 udp dport 547 accept\n\
+             \x20   icmpv6 type { nd-router-solicit, nd-neighbor-solicit, nd-neighbor-advert } accept\n\
+             \x20   oifname \"tun0\" accept\n\
+             \x20   ip daddr { 192.0.2.1/32 } accept\n\
+             \x20 }\n\
+             \x20 chain forward {\n\
+             \x20   type filter hook forward priority filter; policy drop;\n\
+             \x20   ct state established,related accept\n\
+             \x20   oifname \"tun0\" accept\n\
+             \x20   ip daddr { 192.0.2.1/32 } accept\n\
+             \x20 }\n\
+             }\n"
+        );
+    }
+
+    #[test]
+    fn gateways_are_grouped_by_family() {
+        let ruleset = rules(&["192.0.2.1", "2001:db8::1", "192.0.2.2"], &[]).ruleset();
+        let forward = chain(&ruleset, "forward");
+
+        assert_eq!(
+            forward[1..],
+            [
+                "oifname \"tun0\" accept",
+                "ip daddr { 192.0.2.1/32, 192.0.2.2/32 } accept",
+                "ip6 daddr { 2001:db8::1/128 } accept",
+            ]
+        );
+        assert_eq!(chain(&ruleset, "output")[4..], forward[1..]);
+    }
+
+    #[test]
+    fn lan_is_only_allowed_when_listed() {
+        let closed = rules(&["2001:db8::1"], &[]).ruleset();
+        assert!(!closed.contains("192.168.1.0/24"));
+
+        let open = rules(&["2001:db8::1"], &[("192.168.1.0", 24), ("fd00::", 64)]).ruleset();
+        assert_eq!(
+            chain(&open, "output")[5..],
+            [
+                "ip6 daddr { 2001:db8::1/128 } accept",
+                "ip daddr { 192.168.1.0/24 } accept",
+                "ip6 daddr { fd00::/64 } accept",
+            ]
+        );
+    }
+
+    #[test]
+    fn gateway_inside_lan_gets_its_own_rule() {
+        // One set with both elements would be rejected by nft as overlapping
+        let ruleset = rules(&["192.168.1.1"], &[("192.168.1.0", 24)]).ruleset();
+        assert_eq!(
+            chain(&ruleset, "output")[5..],
+            [
+                "ip daddr { 192.168.1.1/32 } accept",
+                "ip daddr { 192.168.1.0/24 } accept",
+            ]
+        );
+    }
+
+    #[test]
+    fn without_gateways_only_the_tunnel_is_allowed() {
+        let ruleset = rules(&[], &[]).ruleset();
+        assert_eq!(chain(&ruleset, "output")[4..], ["oifname \"tun0\" accept"]);
+        assert!(!ruleset.contains("daddr"));
+    }
+
+    #[cfg(target_endian = "little")]
+    #[test]
+    fn parses_proc_net_addresses() {
+        assert_eq!(
+            parse_hex("0100007F", false),
+            Some("127.0.0.1".parse().unwrap())
+        );
+        assert_eq!(
+            parse_hex("B80D0120000000000000000001000000", true),
+            Some("2001:db8::1".parse().unwrap())
+        );
+        // IPv4 peer of a dual-stack socket
+        assert_eq!(
+            parse_hex("0000000000000000FFFF00000102000A", true),
+            Some("10.0.2.1".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_proc_net_addresses() {
+        assert_eq!(parse_hex("0100007F", true), None);
+        assert_eq!(parse_hex("B80D0120000000000000000001000000", false), None);
+        assert_eq!(parse_hex("XYZ0007F", false), None);
+    }
+
+    #[test]
+    fn loopback_peers_are_ignored() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let _stream = std::net::TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        assert!(peers(std::process::id()).iter().all(|ip| !ip.is_loopback()));
+    }
+}
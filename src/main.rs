@@ -1,36 +1,112 @@
+use anyhow::Context;
+use iced::futures::{SinkExt, Stream};
 use iced::{
-    Element, Event, Length, Size, Subscription, Task, Theme, keyboard,
+    Alignment, Element, Event, Length, Size, Subscription, Task, Theme, keyboard,
     widget::operation::{focus_next, focus_previous},
-    widget::{Id, Space, button, column, container, row, text, text_input},
+    widget::{
+        Column, Id, Space, button, canvas, checkbox, column, container, pick_list, row, scrollable,
+        text, text_input,
+    },
     window,
 };
-use log::info;
+use log::{info, warn};
+use std::collections::VecDeque;
 
+mod certificate;
+mod cli;
 mod config;
+mod dns;
+mod dns_check;
+mod error;
+mod file_chooser;
+mod gateway_certificate;
 mod gpclient;
+mod graphs;
+mod history;
+mod hooks;
+mod instance;
+mod keyring;
+mod killswitch;
+mod notification;
+mod password_command;
+mod privileges;
+mod proxy;
+mod resolved;
+mod routes;
+mod secret;
+mod session;
+mod split_dns;
+mod split_tunnel;
+mod statistics;
+mod system_config;
+mod tunnel;
+mod user_helper;
+
+/// Smallest MTU accepted for the tunnel (the IPv4 minimum)
+const MIN_MTU: u16 = 576;
+
+/// Lines kept in the log pane
+const MAX_LOG_LINES: usize = 500;
+
+/// How often, while connected, the tunnel statistics are refreshed and
+/// gpclient is checked for having exited
+const TICK_SECS: u64 = 1;
+
+/// Time span of the throughput and latency graphs
+const GRAPH_MINUTES: u64 = 5;
+
+/// Height of each graph in the Connected view
+const GRAPH_HEIGHT: f32 = 40.0;
+
+/// ID of the main routing table, shown without a table label
+const ROUTE_TABLE_MAIN: u32 = 254;
+
+/// Attempts listed in the History view; the export includes all
+const HISTORY_ROWS: usize = 100;
 
 fn main() -> iced::Result {
     env_logger::init();
 
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    // Unprivileged session bus access on behalf of a root gp-gui (see user_helper.rs)
+    if let Some(code) = user_helper::helper_main(&args) {
+        std::process::exit(code);
+    }
+
+    // Hand over to an already running instance instead of starting a second GUI
+    match instance::acquire(&args) {
+        Ok(instance::Instance::Primary) => {}
+        Ok(instance::Instance::Secondary) => {
+            info!("gp-gui is already running, activated existing window");
+            return Ok(());
+        }
+        Err(e) => warn!("Single-instance check failed: {}", e),
+    }
+
     info!("Starting GlobalProtect VPN GUI");
 
     // Setup signal handlers for cleanup on SIGINT/SIGTERM
     setup_signal_handlers();
 
-    iced::application(GpGui::new, GpGui::update, GpGui::view)
-        .title(GpGui::title)
-        .window(window::Settings {
-            size: Size::new(500.0, 450.0),
-            min_size: Some(Size::new(400.0, 350.0)),
-            max_size: Some(Size::new(700.0, 600.0)),
-            resizable: true,
-            decorations: true,
-            transparent: false,
-            ..Default::default()
-        })
-        .theme(GpGui::theme)
-        .subscription(GpGui::subscription)
-        .run()
+    iced::application(
+        move || GpGui::new(cli::Args::parse(&args)),
+        GpGui::update,
+        GpGui::view,
+    )
+    .title(GpGui::title)
+    .window(window::Settings {
+        size: Size::new(500.0, 450.0),
+        min_size: Some(Size::new(400.0, 350.0)),
+        max_size: Some(Size::new(700.0, 600.0)),
+        resizable: true,
+        decorations: true,
+        transparent: false,
+        ..Default::default()
+    })
+    .theme(GpGui::theme)
+    .subscription(GpGui::subscription)
+    .run()
 }
 
 fn setup_signal_handlers() {
@@ -42,6 +118,7 @@ fn setup_signal_handlers() {
         if !CLEANUP_DONE.swap(true, Ordering::SeqCst) {
             info!("Signal received, cleaning up...");
             gpclient::cleanup_on_exit();
+            split_tunnel::revert();
         }
     };
 
@@ -56,60 +133,1125 @@ fn setup_signal_handlers() {
 enum Message {
     GatewayChanged(String),
     UsernameChanged(String),
-    PasswordChanged(String),
+    PasswordChanged(secret::Secret),
+    CertificatePassphraseChanged(secret::Secret),
+    /// Expiry description for a certificate path
+    CertificateChecked(String, String),
+    RememberPasswordToggled(bool),
+    KeyringUpdated(Result<(), String>),
+    PasswordForgotten(Result<(), String>),
+    ProfileSelected(String),
+    NewProfile,
+    EditProfile,
+    Editor(EditorMessage),
+    DismissConfigWarning,
     ConnectPressed,
+    /// Proxy and gateway certificates retrieved before connecting
+    GatewayInspected(
+        Option<proxy::Proxy>,
+        Result<gateway_certificate::GatewayCertificates, error::VpnError>,
+    ),
     DisconnectPressed,
     Connected(Result<String, String>),
     Disconnected(Result<String, String>),
+    TunnelDetected(Option<tunnel::TunnelInfo>),
+    SplitTunnelApplied(split_tunnel::Report),
+    SplitDnsApplied(Result<split_dns::PushedDns, String>),
+    KillSwitchEnabled(Result<(), String>),
+    DisableKillSwitch,
+    /// Disconnect and connect again for a fresh gateway session
+    Reauthenticate,
+    Tick,
+    Probed(Result<std::time::Duration, String>),
+    GatewayResolved(Vec<std::net::IpAddr>),
+    DnsDetected(Option<dns::DnsInfo>),
+    CheckDns,
+    DnsChecked(dns_check::Report),
+    Copy(String),
+    ShowRoutes,
+    Routes(RoutesMessage),
+    ShowHistory,
+    History(HistoryMessage),
+    ConnectionChecked(bool),
+    HookFinished(hooks::HookReport),
+    ToggleLog,
     EventOccurred(Event),
     FocusNext,
+    Activated(Vec<String>),
+}
+
+/// Messages produced by the Routes view
+#[derive(Debug, Clone)]
+enum RoutesMessage {
+    Loaded(Result<Vec<routes::Route>, String>),
+    Refresh,
+    QueryChanged(String),
+    Lookup,
+    LookedUp(Result<Vec<routes::HostRoute>, String>),
+    Close,
+}
+
+/// Messages produced by the History view
+#[derive(Debug, Clone)]
+enum HistoryMessage {
+    Loaded(Result<Vec<history::Record>, String>),
+    ProfileSelected(ProfileFilter),
+    OutcomeSelected(OutcomeFilter),
+    FromChanged(String),
+    ToChanged(String),
+    Export(ExportFormat),
+    /// Path written to, `None` if the save dialog was cancelled
+    Exported(Result<Option<String>, String>),
+    Close,
+}
+
+/// Profile choice of the History view
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ProfileFilter(Option<String>);
+
+impl std::fmt::Display for ProfileFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.0 {
+            Some(name) => f.write_str(name),
+            None => f.write_str("All profiles"),
+        }
+    }
+}
+
+/// Outcome choice of the History view
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct OutcomeFilter(Option<history::Outcome>);
+
+impl std::fmt::Display for OutcomeFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.0 {
+            Some(outcome) => outcome.fmt(f),
+            None => f.write_str("All outcomes"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ExportFormat {
+    Csv,
+    Json,
+}
+
+/// Messages produced by the profile editor
+#[derive(Debug, Clone)]
+enum EditorMessage {
+    NameChanged(String),
+    GatewayChanged(String),
+    UsernameChanged(String),
+    AuthMethodChanged(config::AuthMethod),
+    PasswordCommandChanged(String),
+    TimeoutChanged(String),
+    DefaultToggled(bool),
+    AdvancedToggled,
+    AuthgroupChanged(String),
+    AsGatewayToggled(bool),
+    FixOpensslToggled(bool),
+    CsdWrapperChanged(String),
+    OsChanged(config::ClientOs),
+    ClientVersionChanged(String),
+    UserAgentChanged(String),
+    HipToggled(bool),
+    DisableIpv6Toggled(bool),
+    SplitDnsToggled(bool),
+    KillSwitchToggled(bool),
+    KillSwitchAllowLanToggled(bool),
+    ReconnectTimeoutChanged(String),
+    SessionLifetimeChanged(String),
+    IdleTimeoutChanged(String),
+    SessionWarningChanged(String),
+    MtuChanged(String),
+    CertificateChanged(String),
+    CertificateKeyChanged(String),
+    BrowseCertificate,
+    BrowseCertificateKey,
+    CertificateChosen(Option<String>),
+    CertificateKeyChosen(Option<String>),
+    BrowseFailed(String),
+    InspectGateway,
+    GatewayInspected(Result<gateway_certificate::GatewayCertificates, error::VpnError>),
+    PinCertificate,
+    UnpinCertificate,
+    ExpiryWarningDaysChanged(String),
+    ProxyChanged(String),
+    ProxyPasswordChanged(secret::Secret),
+    ProbeHostChanged(String),
+    SplitIncludeChanged(String),
+    SplitExcludeChanged(String),
+    HookChanged(config::HookEvent, String),
+    ForgetPassword,
+    Save,
+    Cancel,
+    Delete,
 }
 
 struct GpGui {
     state: ConnectionState,
-    gateway: String,
-    username: String,
-    password: String,
+    config: config::UserConfig,
+    /// Index of the selected profile in `config.profiles`
+    selected: usize,
+    password: secret::Secret,
+    /// Passphrase for a protected client certificate key
+    certificate_passphrase: secret::Secret,
+    /// Last expiry check: certificate path and description
+    certificate_expiry: Option<(String, String)>,
+    /// Certificates of the gateway of the last connection attempt
+    gateway_certificates: Option<gateway_certificate::GatewayCertificates>,
+    /// Gateway certificate problem that did not prevent connecting
+    gateway_warning: Option<String>,
     error: Option<String>,
+    /// Problem with the config file found at startup, shown as a banner
+    config_warning: Option<String>,
+    editor: Option<ProfileEditor>,
+    /// Tunnel interface of the current connection, once detected
+    tunnel: Option<tunnel::TunnelInfo>,
+    /// DNS configuration of the tunnel, once detected
+    dns: Option<dns::DnsInfo>,
+    /// When the split DNS settings that were last applied were recorded
+    split_dns_recorded: Option<std::time::SystemTime>,
+    /// Public addresses of the gateway, resolved before connecting
+    gateway_addresses: Vec<std::net::IpAddr>,
+    /// Routes view, shown instead of the connection details while open
+    routes: Option<RoutesView>,
+    /// History view, shown instead of the connect form while open
+    connection_history: Option<HistoryView>,
+    /// Connection attempt in progress, recorded in the history when it ends
+    attempt: Option<history::Attempt>,
+    /// Whether the kill switch rules are installed
+    kill_switch: bool,
+    /// Session limits of the current connection
+    session: Option<session::Session>,
+    /// Set while disconnecting to re-authenticate, which keeps the kill
+    /// switch in place
+    reauthenticating: bool,
+    /// Findings of the last DNS check of this connection
+    dns_check: Option<dns_check::Report>,
+    dns_checking: bool,
+    /// Traffic through the tunnel, sampled every tick
+    traffic: statistics::Traffic,
+    /// Throughput and latency of the last `GRAPH_MINUTES`
+    history: statistics::History,
+    /// Result of the last latency probe, while connected
+    latency: Option<Result<std::time::Duration, String>>,
+    probing: bool,
+    /// Hook output and results, oldest first
+    log: VecDeque<String>,
+    /// Whether the log pane is expanded
+    show_log: bool,
     vpn_state: gpclient::VpnState,
     gateway_id: Id,
     username_id: Id,
     password_id: Id,
 }
 
+/// Routes via the tunnel and the "which route" lookup.
+#[derive(Default)]
+struct RoutesView {
+    /// Routes via the tunnel interface, `None` while loading
+    routes: Option<Result<Vec<routes::Route>, String>>,
+    /// Host to look up, as typed
+    query: String,
+    lookup: Option<Result<Vec<routes::HostRoute>, String>>,
+    looking_up: bool,
+}
+
+/// Past connection attempts, filtered.
+#[derive(Default)]
+struct HistoryView {
+    /// All records, newest first, `None` while loading
+    records: Option<Result<Vec<history::Record>, String>>,
+    profile: Option<String>,
+    outcome: Option<history::Outcome>,
+    /// Date range as typed, `YYYY-MM-DD`
+    from: String,
+    to: String,
+    /// Result of the last export
+    export: Option<Result<String, String>>,
+}
+
+impl HistoryView {
+    /// The filter for the current choices; invalid dates are ignored.
+    fn filter(&self) -> history::Filter {
+        let date = |value: &str| chrono::NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d").ok();
+        history::Filter {
+            profile: self.profile.clone(),
+            outcome: self.outcome,
+            from: date(&self.from),
+            to: date(&self.to),
+        }
+    }
+
+    /// Records matching the filter, newest first.
+    fn filtered(&self) -> Vec<&history::Record> {
+        let filter = self.filter();
+        match &self.records {
+            Some(Ok(records)) => records
+                .iter()
+                .filter(|record| filter.matches(record))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// A profile being created or edited.
+///
+/// Changes are applied to the configuration only when saved.
+struct ProfileEditor {
+    /// Index of the profile being edited, `None` for a new profile
+    index: Option<usize>,
+    profile: config::Profile,
+    /// Connection timeout as typed, validated on save
+    timeout: String,
+    /// Reconnect timeout and MTU as typed, validated on save
+    reconnect_timeout: String,
+    mtu: String,
+    expiry_warning_days: String,
+    /// Session limits as typed, in minutes, validated on save
+    session_lifetime: String,
+    idle_timeout: String,
+    session_warning: String,
+    /// Split-tunnel rules as typed, comma-separated, validated on save
+    split_include: String,
+    split_exclude: String,
+    /// Proxy password to store in the keyring on save, empty to keep the
+    /// stored one
+    proxy_password: secret::Secret,
+    make_default: bool,
+    /// Whether the Advanced section is expanded
+    advanced: bool,
+    error: Option<String>,
+    /// Outcome of the last keyring action
+    status: Option<String>,
+    /// Gateway certificates retrieved with "Inspect"
+    inspection: Option<Result<gateway_certificate::GatewayCertificates, error::VpnError>>,
+    inspecting: bool,
+}
+
+impl ProfileEditor {
+    fn new(index: Option<usize>, profile: config::Profile, make_default: bool) -> Self {
+        Self {
+            index,
+            timeout: profile.connect_timeout_secs.to_string(),
+            reconnect_timeout: optional_to_string(profile.reconnect_timeout_secs),
+            mtu: optional_to_string(profile.mtu),
+            expiry_warning_days: profile.expiry_warning_days.to_string(),
+            session_lifetime: optional_to_string(profile.session_lifetime_minutes),
+            idle_timeout: optional_to_string(profile.idle_timeout_minutes),
+            session_warning: profile.session_warning_minutes.to_string(),
+            split_include: profile.split_include.join(", "),
+            split_exclude: profile.split_exclude.join(", "),
+            proxy_password: secret::Secret::default(),
+            profile,
+            make_default,
+            advanced: false,
+            error: None,
+            status: None,
+            inspection: None,
+            inspecting: false,
+        }
+    }
+
+    /// Copy the numeric fields into the profile, or describe the first
+    /// invalid one.
+    fn apply_numbers(&mut self) -> Result<(), String> {
+        match self.timeout.trim().parse::<u64>() {
+            Ok(secs) if secs > 0 => self.profile.connect_timeout_secs = secs,
+            _ => return Err("Timeout must be a positive number of seconds".to_string()),
+        }
+        match parse_optional::<u32>(&self.reconnect_timeout) {
+            Ok(secs) if secs != Some(0) => self.profile.reconnect_timeout_secs = secs,
+            _ => return Err("Reconnect timeout must be a positive number of seconds".to_string()),
+        }
+        match parse_optional::<u16>(&self.mtu) {
+            Ok(mtu) if mtu.is_none_or(|mtu| mtu >= MIN_MTU) => self.profile.mtu = mtu,
+            _ => return Err(format!("MTU must be a number of at least {}", MIN_MTU)),
+        }
+        match self.expiry_warning_days.trim().parse::<u32>() {
+            Ok(days) => self.profile.expiry_warning_days = days,
+            _ => return Err("Certificate warning must be a number of days".to_string()),
+        }
+        match parse_optional::<u32>(&self.session_lifetime) {
+            Ok(minutes) if minutes != Some(0) => self.profile.session_lifetime_minutes = minutes,
+            _ => return Err("Session lifetime must be a positive number of minutes".to_string()),
+        }
+        match parse_optional::<u32>(&self.idle_timeout) {
+            Ok(minutes) if minutes != Some(0) => self.profile.idle_timeout_minutes = minutes,
+            _ => return Err("Idle timeout must be a positive number of minutes".to_string()),
+        }
+        match self.session_warning.trim().parse::<u32>() {
+            Ok(minutes) => self.profile.session_warning_minutes = minutes,
+            _ => return Err("Session warning must be a number of minutes".to_string()),
+        }
+        Ok(())
+    }
+
+    /// Copy the split-tunnel rules into the profile, or describe the first
+    /// invalid one.
+    fn apply_split_tunnel(&mut self) -> Result<(), String> {
+        let include = split_tunnel::parse_list(&self.split_include, true)
+            .map_err(|e| format!("Route through VPN: {:#}", e))?;
+        let exclude = split_tunnel::parse_list(&self.split_exclude, false)
+            .map_err(|e| format!("Keep off VPN: {:#}", e))?;
+        self.profile.split_include = include.iter().map(ToString::to_string).collect();
+        self.profile.split_exclude = exclude.iter().map(ToString::to_string).collect();
+        Ok(())
+    }
+
+    /// Describe a problem with the proxy settings, if any.
+    fn check_proxy(&self) -> Result<(), String> {
+        let Some(url) = &self.profile.proxy else {
+            return Ok(());
+        };
+        let proxy = proxy::Proxy::parse(url).map_err(|e| format!("{:#}", e))?;
+        if proxy.username.is_none() && !self.proxy_password.is_empty() {
+            return Err(
+                "Add the proxy user name to the URL, e.g. http://user@proxy:3128".to_string(),
+            );
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 enum ConnectionState {
     Disconnected,
     Connecting,
-    Connected { connected_at: String },
+    Connected {
+        connected_at: chrono::DateTime<chrono::Local>,
+    },
 }
 
 impl GpGui {
-    fn new() -> (Self, Task<Message>) {
-        let config = config::load_config();
+    fn new(args: cli::Args) -> (Self, Task<Message>) {
+        let (config, config_warning) = match config::load_config() {
+            Ok(config) => (config, None),
+            Err(e) => (None, Some(e.to_string())),
+        };
+        let config = system_config::apply(config.unwrap_or_else(config::UserConfig::empty));
+        let config = if config.profiles.is_empty() {
+            config::UserConfig::initial()
+        } else {
+            config
+        };
 
-        (
-            Self {
-                state: ConnectionState::Disconnected,
-                gateway: config
-                    .as_ref()
-                    .map(|c| c.vpn_server.clone())
-                    .unwrap_or_else(|| "access.tii.ae".to_string()),
-                username: config
-                    .as_ref()
-                    .map(|c| c.username.clone())
-                    .unwrap_or_default(),
-                password: String::new(),
-                error: None,
-                vpn_state: gpclient::create_vpn_state(),
-                gateway_id: Id::new("gateway"),
-                username_id: Id::new("username"),
-                password_id: Id::new("password"),
+        let mut app = Self {
+            state: ConnectionState::Disconnected,
+            selected: config.default_index(),
+            config,
+            password: secret::Secret::default(),
+            certificate_passphrase: secret::Secret::default(),
+            certificate_expiry: None,
+            gateway_certificates: None,
+            gateway_warning: None,
+            error: None,
+            config_warning,
+            editor: None,
+            tunnel: None,
+            dns: None,
+            split_dns_recorded: None,
+            gateway_addresses: Vec::new(),
+            routes: None,
+            connection_history: None,
+            attempt: None,
+            kill_switch: false,
+            session: None,
+            reauthenticating: false,
+            dns_check: None,
+            dns_checking: false,
+            traffic: statistics::Traffic::default(),
+            history: statistics::History::new((GRAPH_MINUTES * 60 / TICK_SECS) as usize),
+            latency: None,
+            probing: false,
+            log: VecDeque::new(),
+            show_log: false,
+            vpn_state: gpclient::create_vpn_state(),
+            gateway_id: Id::new("gateway"),
+            username_id: Id::new("username"),
+            password_id: Id::new("password"),
+        };
+        app.apply_args(args);
+
+        // Rules left behind by a gp-gui that did not exit cleanly
+        if killswitch::is_active() {
+            match killswitch::disable() {
+                Ok(()) => app.log("Removed kill switch rules left by a previous run"),
+                Err(e) => {
+                    app.error = Some(format!("{:#}", e));
+                    app.kill_switch = true;
+                }
+            }
+        }
+        let task = app.check_certificate(app.profile());
+
+        (app, task)
+    }
+
+    /// Apply command-line arguments from startup or from a forwarded launch.
+    fn apply_args(&mut self, args: cli::Args) {
+        if let Some(name) = args.profile {
+            if self.state != ConnectionState::Disconnected || self.editor.is_some() {
+                warn!("[UI] Ignoring profile '{}' while busy", name);
+            } else if let Some(index) = self.config.profiles.iter().position(|p| p.name == name) {
+                info!("[UI] Selected profile '{}'", name);
+                self.selected = index;
+            } else {
+                warn!("[UI] Unknown profile '{}'", name);
+            }
+        }
+    }
+
+    /// The currently selected profile
+    fn profile(&self) -> &config::Profile {
+        &self.config.profiles[self.selected]
+    }
+
+    fn profile_mut(&mut self) -> &mut config::Profile {
+        &mut self.config.profiles[self.selected]
+    }
+
+    /// Expiry description of the certificate of `profile`, if checked.
+    fn certificate_expiry(&self, profile: &config::Profile) -> Option<&str> {
+        let (path, description) = self.certificate_expiry.as_ref()?;
+        (profile.certificate.as_ref() == Some(path)).then_some(description.as_str())
+    }
+
+    /// Read the expiry of the certificate of `profile`, unless already known.
+    fn check_certificate(&self, profile: &config::Profile) -> Task<Message> {
+        let Some(path) = profile.certificate.clone() else {
+            return Task::none();
+        };
+        if self
+            .certificate_expiry
+            .as_ref()
+            .is_some_and(|(checked, _)| *checked == path)
+        {
+            return Task::none();
+        }
+
+        Task::perform(
+            {
+                let path = path.clone();
+                async move { certificate::expiry(&path).await }
+            },
+            move |result| {
+                let description = match result {
+                    Ok(not_after) => certificate::describe_expiry(not_after),
+                    Err(e) => format!("{:#}", e),
+                };
+                Message::CertificateChecked(path.clone(), description)
+            },
+        )
+    }
+
+    /// Add a timestamped line to the log pane.
+    fn log(&mut self, line: impl std::fmt::Display) {
+        let time = chrono::Local::now().format("%H:%M:%S");
+        self.log.push_back(format!("{} {}", time, line));
+        while self.log.len() > MAX_LOG_LINES {
+            self.log.pop_front();
+        }
+    }
+
+    /// Run the selected profile's hook for `event`, if it has one.
+    fn hook_task(&self, event: config::HookEvent) -> Task<Message> {
+        let profile = self.profile();
+        let Some(command) = profile.hooks.get(&event) else {
+            return Task::none();
+        };
+
+        let context = hooks::HookContext::new(event, profile, self.tunnel.clone());
+        Task::perform(hooks::run(command.clone(), context), Message::HookFinished)
+    }
+
+    /// Apply the selected profile's split-tunnel rules to `interface`, if
+    /// it has any.
+    fn split_tunnel_task(&self, interface: String) -> Task<Message> {
+        let profile = self.profile();
+        if profile.split_include.is_empty() && profile.split_exclude.is_empty() {
+            return Task::none();
+        }
+        Task::perform(
+            split_tunnel::apply(
+                profile.split_include.clone(),
+                profile.split_exclude.clone(),
+                interface,
+            ),
+            Message::SplitTunnelApplied,
+        )
+    }
+
+    /// Apply the split DNS settings again if the vpnc-script wrapper recorded
+    /// new ones, e.g. after openconnect reconnected.
+    fn reapply_split_dns_task(&mut self) -> Task<Message> {
+        let Some(interface) = self
+            .tunnel
+            .as_ref()
+            .filter(|_| self.profile().split_dns)
+            .map(|tunnel| tunnel.interface.clone())
+        else {
+            return Task::none();
+        };
+        let recorded = split_dns::recorded_at();
+        if recorded.is_none() || recorded == self.split_dns_recorded {
+            return Task::none();
+        }
+        self.split_dns_recorded = recorded;
+        self.log("Split DNS settings changed, applying them again");
+
+        let dns = {
+            let interface = interface.clone();
+            Task::perform(
+                async move { dns::of_tunnel(&interface).await },
+                Message::DnsDetected,
+            )
+        };
+        Task::perform(split_dns::apply(interface), |result| {
+            Message::SplitDnsApplied(result.map_err(|e| format!("{:#}", e)))
+        })
+        .chain(dns)
+    }
+
+    /// Revert the split DNS settings of the current tunnel, if the selected
+    /// profile uses split DNS.
+    fn revert_split_dns_task(&self) -> Task<Message> {
+        match &self.tunnel {
+            Some(tunnel) if self.profile().split_dns => {
+                Task::future(split_dns::revert(tunnel.interface.clone())).discard()
+            }
+            _ => Task::none(),
+        }
+    }
+
+    /// Install the kill switch for `interface`, if the selected profile
+    /// asks for it.
+    fn kill_switch_task(&self, interface: String) -> Task<Message> {
+        let profile = self.profile();
+        if !profile.kill_switch {
+            return Task::none();
+        }
+
+        let gateways = self.gateway_addresses.clone();
+        let allow_lan = profile.kill_switch_allow_lan;
+        Task::perform(
+            async move {
+                let pid = gpclient::pid();
+                let rules = routes::blocking(move || {
+                    killswitch::Rules::collect(interface, pid, &gateways, allow_lan)
+                })
+                .await?;
+                killswitch::enable(rules).await
             },
-            Task::none(),
+            |result| Message::KillSwitchEnabled(result.map_err(|e| format!("{:#}", e))),
         )
     }
 
+    /// Remove the kill switch rules if installed, reporting failures in the
+    /// UI.
+    ///
+    /// # Returns
+    ///
+    /// `false` if the rules are still in place.
+    fn disable_kill_switch(&mut self) -> bool {
+        if !self.kill_switch {
+            return true;
+        }
+        match killswitch::disable() {
+            Ok(()) => {
+                self.kill_switch = false;
+                self.log("Kill switch disabled");
+                true
+            }
+            Err(e) => {
+                warn!("[UI] {:#}", e);
+                self.error = Some(format!("{:#}", e));
+                false
+            }
+        }
+    }
+
+    /// Update the session limits and warn once the session is about to end.
+    fn session_warning_task(&mut self) -> Task<Message> {
+        let Some(session) = &mut self.session else {
+            return Task::none();
+        };
+        let now = chrono::Local::now();
+        session.update(session::reported());
+        if self.traffic.rx_rate > 0.0 || self.traffic.tx_rate > 0.0 {
+            session.activity(now);
+        }
+        let Some((end, limit)) = session.due_warning(now) else {
+            return Task::none();
+        };
+
+        let reason = match limit {
+            session::Limit::Lifetime => "its login lifetime ends",
+            session::Limit::Idle => "it is idle, unless there is traffic before then",
+        };
+        let body = format!(
+            "The session of {} ends at {}, as {}. Re-authenticate in gp-gui to stay connected.",
+            self.profile().name,
+            end.format("%H:%M"),
+            reason
+        );
+        warn!("[UI] {}", body);
+        self.log(&body);
+        Task::future(async move {
+            if let Err(e) = notification::notify("VPN session ending", &body).await {
+                warn!("[UI] Failed to show notification: {:#}", e);
+            }
+        })
+        .discard()
+    }
+
+    /// Whether the login lifetime of the current session has run out, and
+    /// re-authentication has not started yet.
+    fn session_expired(&self) -> bool {
+        !self.reauthenticating
+            && self
+                .session
+                .as_ref()
+                .is_some_and(|session| session.lifetime_ended(chrono::Local::now()))
+    }
+
+    /// Persist the configuration, reporting failures in the UI.
+    fn save_config(&mut self) {
+        if let Err(e) = config::save_config(&self.config) {
+            warn!("[UI] Failed to save config: {}", e);
+            self.error = Some(format!("Failed to save settings: {}", e));
+        }
+    }
+
+    fn update_editor(&mut self, message: EditorMessage) -> Task<Message> {
+        let Some(editor) = self.editor.as_mut() else {
+            return Task::none();
+        };
+
+        match message {
+            EditorMessage::NameChanged(name) => editor.profile.name = name,
+            EditorMessage::GatewayChanged(gateway) => {
+                editor.profile.gateway = gateway;
+                editor.inspection = None;
+            }
+            EditorMessage::UsernameChanged(username) => editor.profile.username = username,
+            EditorMessage::AuthMethodChanged(method) => editor.profile.auth_method = method,
+            EditorMessage::PasswordCommandChanged(command) => {
+                editor.profile.password_command = non_empty(command);
+            }
+            EditorMessage::TimeoutChanged(timeout) => editor.timeout = timeout,
+            EditorMessage::DefaultToggled(make_default) => editor.make_default = make_default,
+            EditorMessage::AdvancedToggled => editor.advanced = !editor.advanced,
+            EditorMessage::AuthgroupChanged(authgroup) => {
+                editor.profile.authgroup = non_empty(authgroup);
+            }
+            EditorMessage::AsGatewayToggled(as_gateway) => editor.profile.as_gateway = as_gateway,
+            EditorMessage::FixOpensslToggled(fix_openssl) => {
+                editor.profile.fix_openssl = fix_openssl;
+            }
+            EditorMessage::CsdWrapperChanged(csd_wrapper) => {
+                editor.profile.csd_wrapper = non_empty(csd_wrapper);
+            }
+            EditorMessage::OsChanged(os) => editor.profile.os = os,
+            EditorMessage::ClientVersionChanged(version) => {
+                editor.profile.client_version = non_empty(version);
+            }
+            EditorMessage::UserAgentChanged(user_agent) => {
+                editor.profile.user_agent = non_empty(user_agent);
+            }
+            EditorMessage::HipToggled(hip) => editor.profile.hip = hip,
+            EditorMessage::DisableIpv6Toggled(disable) => editor.profile.disable_ipv6 = disable,
+            EditorMessage::SplitDnsToggled(enabled) => editor.profile.split_dns = enabled,
+            EditorMessage::KillSwitchToggled(enabled) => editor.profile.kill_switch = enabled,
+            EditorMessage::KillSwitchAllowLanToggled(allow) => {
+                editor.profile.kill_switch_allow_lan = allow;
+            }
+            EditorMessage::ReconnectTimeoutChanged(timeout) => editor.reconnect_timeout = timeout,
+            EditorMessage::SessionLifetimeChanged(minutes) => editor.session_lifetime = minutes,
+            EditorMessage::IdleTimeoutChanged(minutes) => editor.idle_timeout = minutes,
+            EditorMessage::SessionWarningChanged(minutes) => editor.session_warning = minutes,
+            EditorMessage::MtuChanged(mtu) => editor.mtu = mtu,
+            EditorMessage::CertificateChanged(path) => {
+                editor.profile.certificate = non_empty(path);
+            }
+            EditorMessage::CertificateKeyChanged(path) => {
+                editor.profile.certificate_key = non_empty(path);
+            }
+            EditorMessage::BrowseCertificate => {
+                return browse(
+                    "Choose Client Certificate",
+                    EditorMessage::CertificateChosen,
+                );
+            }
+            EditorMessage::BrowseCertificateKey => {
+                return browse("Choose Private Key", EditorMessage::CertificateKeyChosen);
+            }
+            EditorMessage::CertificateChosen(path) => {
+                if path.is_some() {
+                    editor.profile.certificate = path;
+                    let profile = editor.profile.clone();
+                    return self.check_certificate(&profile);
+                }
+            }
+            EditorMessage::CertificateKeyChosen(path) => {
+                if path.is_some() {
+                    editor.profile.certificate_key = path;
+                }
+            }
+            EditorMessage::BrowseFailed(e) => {
+                warn!("[UI] File chooser failed: {}", e);
+                editor.error = Some(format!("Could not open file chooser: {}", e));
+            }
+            EditorMessage::InspectGateway => {
+                editor.inspecting = true;
+                editor.inspection = None;
+
+                // The proxy password is stored under the saved profile name
+                let mut profile = editor.profile.clone();
+                if let Some(index) = editor.index {
+                    profile.name = self.config.profiles[index].name.clone();
+                }
+                let typed_password = editor.proxy_password.clone();
+                let gateway = profile.gateway.trim().to_string();
+
+                return Task::perform(
+                    async move {
+                        let mut proxy = proxy::for_profile(&profile).await.map_err(|e| {
+                            error::VpnError::CertificateUnavailable {
+                                gateway: gateway.clone(),
+                                reason: format!("{:#}", e),
+                            }
+                        })?;
+                        if let Some(proxy) = proxy.as_mut()
+                            && !typed_password.is_empty()
+                        {
+                            proxy.password = typed_password;
+                        }
+                        gateway_certificate::inspect(&gateway, proxy.as_ref()).await
+                    },
+                    |result| Message::Editor(EditorMessage::GatewayInspected(result)),
+                );
+            }
+            EditorMessage::GatewayInspected(result) => {
+                editor.inspecting = false;
+                editor.inspection = Some(result);
+            }
+            EditorMessage::PinCertificate => {
+                if let Some(Ok(certificates)) = &editor.inspection
+                    && let Some(leaf) = certificates.leaf()
+                {
+                    editor.profile.pinned_certificate = Some(leaf.pin.clone());
+                }
+            }
+            EditorMessage::UnpinCertificate => editor.profile.pinned_certificate = None,
+            EditorMessage::ExpiryWarningDaysChanged(days) => editor.expiry_warning_days = days,
+            EditorMessage::ProxyChanged(url) => {
+                editor.profile.proxy = non_empty(url);
+                editor.inspection = None;
+            }
+            EditorMessage::ProxyPasswordChanged(password) => editor.proxy_password = password,
+            EditorMessage::ProbeHostChanged(host) => editor.profile.probe_host = non_empty(host),
+            EditorMessage::SplitIncludeChanged(rules) => editor.split_include = rules,
+            EditorMessage::SplitExcludeChanged(rules) => editor.split_exclude = rules,
+            EditorMessage::HookChanged(event, command) => {
+                if command.is_empty() {
+                    editor.profile.hooks.remove(&event);
+                } else {
+                    editor.profile.hooks.insert(event, command);
+                }
+            }
+            EditorMessage::Cancel => self.editor = None,
+            EditorMessage::ForgetPassword => {
+                let Some(index) = editor.index else {
+                    return Task::none();
+                };
+                editor.profile.remember_password = false;
+                editor.status = Some("Removing saved password...".to_string());
+
+                let name = self.config.profiles[index].name.clone();
+                return Task::perform(
+                    async move { keyring::forget_password(&name).await },
+                    |result| Message::PasswordForgotten(result.map_err(|e| e.to_string())),
+                );
+            }
+            EditorMessage::Save => {
+                let name = editor.profile.name.trim().to_string();
+                let duplicate = self
+                    .config
+                    .profiles
+                    .iter()
+                    .enumerate()
+                    .any(|(i, p)| p.name == name && Some(i) != editor.index);
+
+                let error = if name.is_empty() {
+                    Some("Profile name is required".to_string())
+                } else if duplicate {
+                    Some(format!("A profile named '{}' already exists", name))
+                } else if editor.profile.gateway.trim().is_empty() {
+                    Some("VPN server is required".to_string())
+                } else if let Err(e) = editor.check_proxy() {
+                    Some(e)
+                } else if let Err(e) = editor.apply_split_tunnel() {
+                    Some(e)
+                } else {
+                    editor.apply_numbers().err()
+                };
+                if error.is_some() {
+                    editor.error = error;
+                    return Task::none();
+                }
+
+                let Some(editor) = self.editor.take() else {
+                    return Task::none();
+                };
+                let mut profile = editor.profile;
+                profile.name = name;
+                profile.gateway = profile.gateway.trim().to_string();
+                profile.hooks.retain(|_, command| {
+                    *command = command.trim().to_string();
+                    !command.is_empty()
+                });
+                for field in [
+                    &mut profile.password_command,
+                    &mut profile.authgroup,
+                    &mut profile.csd_wrapper,
+                    &mut profile.client_version,
+                    &mut profile.user_agent,
+                    &mut profile.proxy,
+                    &mut profile.probe_host,
+                ] {
+                    *field = field
+                        .take()
+                        .and_then(|value| non_empty(value.trim().to_string()));
+                }
+
+                let mut tasks = Vec::new();
+                let new_name = profile.name.clone();
+                let proxy_password = (profile.proxy.is_some() && !editor.proxy_password.is_empty())
+                    .then_some(editor.proxy_password);
+                let index = match editor.index {
+                    Some(index) => {
+                        let old_name = self.config.profiles[index].name.clone();
+                        if self.config.default_profile.as_ref() == Some(&old_name) {
+                            self.config.default_profile = Some(profile.name.clone());
+                        }
+                        // Saved passwords are keyed by profile name
+                        if old_name != new_name {
+                            let mut moved = Vec::new();
+                            if profile.remember_password {
+                                moved.push(keyring::SecretKind::Password);
+                                moved.push(keyring::SecretKind::KeyPassphrase);
+                            }
+                            if profile.proxy.is_some() && proxy_password.is_none() {
+                                moved.push(keyring::SecretKind::Proxy);
+                            }
+                            for kind in moved {
+                                let (old_name, new_name) = (old_name.clone(), new_name.clone());
+                                tasks.push(keyring_task(async move {
+                                    keyring::rename(kind, &old_name, &new_name).await
+                                }));
+                            }
+                            if proxy_password.is_some() {
+                                // Replaced by the typed password under the new name
+                                tasks.push(keyring_task(async move {
+                                    keyring::forget(keyring::SecretKind::Proxy, &old_name).await
+                                }));
+                            }
+                        }
+                        self.config.profiles[index] = profile;
+                        index
+                    }
+                    None => {
+                        self.config.profiles.push(profile);
+                        self.config.profiles.len() - 1
+                    }
+                };
+                if editor.make_default {
+                    self.config.default_profile = Some(self.config.profiles[index].name.clone());
+                }
+
+                if let Some(password) = proxy_password {
+                    tasks.push(keyring_task(async move {
+                        keyring::store(keyring::SecretKind::Proxy, &new_name, &password).await
+                    }));
+                }
+
+                info!("[UI] Saved profile '{}'", self.config.profiles[index].name);
+                self.selected = index;
+                self.save_config();
+                tasks.push(self.check_certificate(self.profile()));
+                return Task::batch(tasks);
+            }
+            EditorMessage::Delete => {
+                let Some(index) = editor.index else {
+                    return Task::none();
+                };
+                self.editor = None;
+
+                let removed = self.config.profiles.remove(index);
+                info!("[UI] Deleted profile '{}'", removed.name);
+                if self.config.profiles.is_empty() {
+                    self.config = config::UserConfig::initial();
+                } else if self.config.default_profile.as_ref() == Some(&removed.name) {
+                    self.config.default_profile = Some(self.config.profiles[0].name.clone());
+                }
+                self.selected = self.config.default_index();
+                self.save_config();
+
+                let mut tasks = Vec::new();
+                if removed.remember_password {
+                    let name = removed.name.clone();
+                    tasks.push(keyring_task(async move {
+                        keyring::forget_password(&name).await
+                    }));
+                }
+                if removed.proxy.is_some() {
+                    tasks.push(keyring_task(async move {
+                        keyring::forget(keyring::SecretKind::Proxy, &removed.name).await
+                    }));
+                }
+                return Task::batch(tasks);
+            }
+        }
+
+        Task::none()
+    }
+
+    /// Read the routes via the tunnel for the Routes view.
+    fn load_routes(&self) -> Task<Message> {
+        let Some(tunnel) = &self.tunnel else {
+            return Task::done(Message::Routes(RoutesMessage::Loaded(Err(
+                "Tunnel interface not found".to_string(),
+            ))));
+        };
+
+        let interface = tunnel.interface.clone();
+        let load = routes::blocking(move || routes::via_interface(&interface));
+        Task::perform(load, |result| {
+            Message::Routes(RoutesMessage::Loaded(
+                result.map_err(|e| format!("{:#}", e)),
+            ))
+        })
+    }
+
+    fn update_routes(&mut self, message: RoutesMessage) -> Task<Message> {
+        let Some(view) = self.routes.as_mut() else {
+            return Task::none();
+        };
+
+        match message {
+            RoutesMessage::Loaded(routes) => view.routes = Some(routes),
+            RoutesMessage::Refresh => {
+                view.routes = None;
+                return self.load_routes();
+            }
+            RoutesMessage::QueryChanged(query) => view.query = query,
+            RoutesMessage::Lookup => {
+                if view.query.trim().is_empty() || view.looking_up {
+                    return Task::none();
+                }
+                view.looking_up = true;
+                view.lookup = None;
+
+                let interface = self
+                    .tunnel
+                    .as_ref()
+                    .map(|tunnel| tunnel.interface.clone())
+                    .unwrap_or_default();
+                return Task::perform(
+                    routes::lookup_host(view.query.clone(), interface),
+                    |result| {
+                        Message::Routes(RoutesMessage::LookedUp(
+                            result.map_err(|e| format!("{:#}", e)),
+                        ))
+                    },
+                );
+            }
+            RoutesMessage::LookedUp(result) => {
+                view.looking_up = false;
+                view.lookup = Some(result);
+            }
+            RoutesMessage::Close => self.routes = None,
+        }
+
+        Task::none()
+    }
+
+    fn update_history(&mut self, message: HistoryMessage) -> Task<Message> {
+        let Some(view) = self.connection_history.as_mut() else {
+            return Task::none();
+        };
+
+        match message {
+            HistoryMessage::Loaded(result) => {
+                view.records = Some(result.map(|mut records| {
+                    records.reverse();
+                    records
+                }));
+            }
+            HistoryMessage::ProfileSelected(ProfileFilter(profile)) => view.profile = profile,
+            HistoryMessage::OutcomeSelected(OutcomeFilter(outcome)) => view.outcome = outcome,
+            HistoryMessage::FromChanged(from) => view.from = from,
+            HistoryMessage::ToChanged(to) => view.to = to,
+            HistoryMessage::Export(format) => {
+                // Exported oldest first, like the history file
+                let mut records: Vec<history::Record> =
+                    view.filtered().into_iter().cloned().collect();
+                records.reverse();
+                view.export = None;
+
+                return Task::perform(
+                    async move {
+                        let records: Vec<&history::Record> = records.iter().collect();
+                        let (content, name) = match format {
+                            ExportFormat::Csv => (history::to_csv(&records), "gp-gui-history.csv"),
+                            ExportFormat::Json => {
+                                (history::to_json(&records)?, "gp-gui-history.json")
+                            }
+                        };
+                        let Some(path) =
+                            file_chooser::choose_save_file("Export Connection History", name)
+                                .await?
+                        else {
+                            return Ok(None);
+                        };
+                        history::export(&path, &content)?;
+                        anyhow::Ok(Some(path))
+                    },
+                    |result| {
+                        Message::History(HistoryMessage::Exported(
+                            result.map_err(|e| format!("{:#}", e)),
+                        ))
+                    },
+                );
+            }
+            HistoryMessage::Exported(result) => {
+                view.export = match result {
+                    Ok(Some(path)) => Some(Ok(path)),
+                    Ok(None) => None,
+                    Err(e) => Some(Err(e)),
+                };
+            }
+            HistoryMessage::Close => self.connection_history = None,
+        }
+
+        Task::none()
+    }
+
+    /// Close the connection attempt in progress and append it to the history.
+    fn record_attempt(&mut self, outcome: history::Outcome, error: Option<(String, Option<&str>)>) {
+        let Some(attempt) = self.attempt.take() else {
+            return;
+        };
+        let record = attempt.finish(outcome, self.traffic.counters, error);
+        if let Err(e) = history::append(&record) {
+            warn!("[UI] Failed to record the connection attempt: {:#}", e);
+        }
+    }
+
     fn title(&self) -> String {
         match self.state {
             ConnectionState::Disconnected => String::from("GlobalProtect VPN - Disconnected"),
@@ -131,7 +1273,9 @@ impl GpGui {
                                 return focus_next();
                             }
                         }
-                        keyboard::Key::Named(keyboard::key::Named::Enter) => {
+                        keyboard::Key::Named(keyboard::key::Named::Enter)
+                            if self.editor.is_none() =>
+                        {
                             // Handle Enter key based on current state
                             match &self.state {
                                 ConnectionState::Disconnected => {
@@ -151,82 +1295,551 @@ impl GpGui {
                 Task::none()
             }
             Message::GatewayChanged(gateway) => {
-                self.gateway = gateway;
+                self.profile_mut().gateway = gateway;
                 Task::none()
             }
             Message::UsernameChanged(username) => {
-                self.username = username;
+                self.profile_mut().username = username;
                 Task::none()
             }
             Message::PasswordChanged(password) => {
                 self.password = password;
                 Task::none()
             }
-            Message::FocusNext => {
-                // This is called when Enter is pressed in gateway or username field
-                // Focus moves: gateway → username → password (then ConnectPressed)
-                focus_next()
+            Message::CertificatePassphraseChanged(passphrase) => {
+                self.certificate_passphrase = passphrase;
+                Task::none()
+            }
+            Message::CertificateChecked(path, description) => {
+                self.certificate_expiry = Some((path, description));
+                Task::none()
+            }
+            Message::RememberPasswordToggled(remember) => {
+                self.profile_mut().remember_password = remember;
+                self.save_config();
+
+                if remember {
+                    return Task::none();
+                }
+                let name = self.profile().name.clone();
+                Task::perform(
+                    async move { keyring::forget_password(&name).await },
+                    |result| Message::KeyringUpdated(result.map_err(|e| e.to_string())),
+                )
+            }
+            Message::KeyringUpdated(result) => {
+                if let Err(e) = result {
+                    warn!("[UI] Keyring update failed: {}", e);
+                    let message = format!("Keyring: {}", e);
+                    match self.editor.as_mut() {
+                        Some(editor) => editor.error = Some(message),
+                        None => self.error = Some(message),
+                    }
+                }
+                Task::none()
+            }
+            Message::PasswordForgotten(result) => {
+                let status = match result {
+                    Ok(()) => "Saved password removed".to_string(),
+                    Err(e) => {
+                        warn!("[UI] Failed to forget password: {}", e);
+                        format!("Failed to remove saved password: {}", e)
+                    }
+                };
+                if let Some(editor) = self.editor.as_mut() {
+                    editor.status = Some(status);
+                }
+                Task::none()
+            }
+            Message::ProfileSelected(name) => {
+                if let Some(index) = self.config.profiles.iter().position(|p| p.name == name) {
+                    info!("[UI] Selected profile '{}'", name);
+                    self.selected = index;
+                    self.error = None;
+                }
+                self.check_certificate(self.profile())
+            }
+            Message::NewProfile => {
+                let profile = config::Profile::new(String::new(), String::new());
+                self.editor = Some(ProfileEditor::new(None, profile, false));
+                Task::none()
+            }
+            Message::EditProfile => {
+                let is_default = self.config.default_index() == self.selected;
+                self.editor = Some(ProfileEditor::new(
+                    Some(self.selected),
+                    self.profile().clone(),
+                    is_default,
+                ));
+                self.check_certificate(self.profile())
+            }
+            Message::Editor(message) => self.update_editor(message),
+            Message::ShowRoutes => {
+                self.routes = Some(RoutesView::default());
+                self.load_routes()
+            }
+            Message::Routes(message) => self.update_routes(message),
+            Message::ShowHistory => {
+                self.connection_history = Some(HistoryView::default());
+                Task::perform(async { history::load() }, |result| {
+                    Message::History(HistoryMessage::Loaded(
+                        result.map_err(|e| format!("{:#}", e)),
+                    ))
+                })
+            }
+            Message::History(message) => self.update_history(message),
+            Message::CheckDns => {
+                let Some(tunnel) = &self.tunnel else {
+                    return Task::none();
+                };
+                let profile = self.profile();
+                let connection = dns_check::Connection {
+                    interface: tunnel.interface.clone(),
+                    dns: self.dns.clone(),
+                    split_dns: profile.split_dns,
+                    probe_host: profile.probe_host.clone(),
+                };
+                self.dns_checking = true;
+                Task::perform(dns_check::run(connection), Message::DnsChecked)
+            }
+            Message::DnsChecked(report) => {
+                self.dns_checking = false;
+                // Disconnected while the check was running
+                if !matches!(self.state, ConnectionState::Connected { .. }) {
+                    return Task::none();
+                }
+                for finding in &report.findings {
+                    self.log(format!(
+                        "DNS check: {}: {}",
+                        finding.outcome, finding.message
+                    ));
+                }
+                self.dns_check = Some(report);
+                Task::none()
+            }
+            Message::DismissConfigWarning => {
+                self.config_warning = None;
+                Task::none()
+            }
+            Message::FocusNext => {
+                // This is called when Enter is pressed in gateway or username field
+                // Focus moves: gateway → username → password (then ConnectPressed)
+                focus_next()
+            }
+            Message::Activated(args) => {
+                info!("[UI] Activated by another launch with args: {:?}", args);
+                self.apply_args(cli::Args::parse(&args));
+
+                // Restore and raise the window so the user sees the running instance
+                window::latest()
+                    .and_then(|id| window::minimize(id, false).chain(window::gain_focus(id)))
+                    .chain(self.check_certificate(self.profile()))
             }
             Message::ConnectPressed => {
                 info!("[UI] Connect button pressed");
+                if self.profile().gateway.trim().is_empty() {
+                    self.error = Some("Enter the VPN server to connect to".to_string());
+                    return Task::none();
+                }
                 self.state = ConnectionState::Connecting;
                 self.error = None;
+                self.gateway_warning = None;
+                self.gateway_certificates = None;
+                self.gateway_addresses.clear();
+                // Rules from the last connection stay in place: they already
+                // allow its gateway and are replaced once the new tunnel is up,
+                // so a failed or hanging reconnect leaks nothing. Only a profile
+                // without a kill switch removes them.
+                if !self.profile().kill_switch && !self.disable_kill_switch() {
+                    self.state = ConnectionState::Disconnected;
+                    return Task::none();
+                }
+                self.connection_history = None;
+                self.attempt = Some(history::Attempt::start(self.profile()));
 
-                let config = gpclient::VpnConfig {
-                    gateway: self.gateway.clone(),
-                    username: self.username.clone(),
-                    password: self.password.clone(),
-                    ..Default::default()
-                };
+                // Remember the server and username typed for this profile
+                self.save_config();
+
+                // Check the gateway certificate before handing over to gpclient
+                let profile = self.profile().clone();
+                let inspect = Task::perform(
+                    async move {
+                        let proxy = proxy::for_profile(&profile).await?;
+                        let gateway = profile.gateway.trim();
+                        let inspected = gateway_certificate::inspect(gateway, proxy.as_ref()).await;
+                        anyhow::Ok((proxy, inspected))
+                    },
+                    |result| match result {
+                        Ok((proxy, inspected)) => Message::GatewayInspected(proxy, inspected),
+                        Err(e) => Message::Connected(Err(format!("{:#}", e))),
+                    },
+                );
+
+                // Resolved now, as the tunnel's DNS may give internal addresses
+                let (host, _) = gateway_certificate::host_and_port(&self.profile().gateway);
+                let resolve =
+                    Task::perform(
+                        async move { dns::lookup(&host).await },
+                        |result| match result {
+                            Ok(addresses) => Message::GatewayResolved(addresses),
+                            Err(e) => {
+                                warn!("[UI] {:#}", e);
+                                Message::GatewayResolved(Vec::new())
+                            }
+                        },
+                    );
 
+                Task::batch([
+                    resolve,
+                    self.hook_task(config::HookEvent::PreConnect).chain(inspect),
+                ])
+            }
+            Message::GatewayInspected(proxy, result) => {
+                if self.state != ConnectionState::Connecting {
+                    return Task::none();
+                }
+
+                match gateway_certificate::verify(self.profile(), &result) {
+                    Ok(warning) => {
+                        if let Some(warning) = &warning {
+                            warn!("[UI] {}", warning);
+                        }
+                        self.gateway_warning = warning;
+                        self.gateway_certificates = result.ok();
+                    }
+                    Err(e) => {
+                        info!("[UI] Gateway certificate refused: {}", e);
+                        self.state = ConnectionState::Disconnected;
+                        self.error = Some(e.to_string());
+                        self.record_attempt(
+                            history::Outcome::Failed,
+                            Some((e.to_string(), Some(e.kind()))),
+                        );
+                        return Task::none();
+                    }
+                }
+
+                let profile = self.profile().clone();
+                let password = self.password.clone();
+                let passphrase = self.certificate_passphrase.clone();
                 let state = self.vpn_state.clone();
 
                 Task::perform(
-                    async move { gpclient::connect_vpn(state, config).await },
-                    |result| Message::Connected(result.map_err(|e| e.to_string())),
+                    async move { connect_profile(state, profile, password, passphrase, proxy).await },
+                    |result| Message::Connected(result.map_err(|e| format!("{:#}", e))),
                 )
             }
             Message::DisconnectPressed => {
                 info!("[UI] Disconnect button pressed");
+                self.error = None;
+                // Last traffic counters for the history, while the tunnel is up
+                if let Some(tunnel) = &self.tunnel {
+                    let _ = self.traffic.sample(&tunnel.interface);
+                }
                 let state = self.vpn_state.clone();
 
-                Task::perform(
+                let disconnect = Task::perform(
                     async move { gpclient::disconnect_vpn(state).await },
                     |result| Message::Disconnected(result.map_err(|e| e.to_string())),
-                )
+                );
+
+                self.hook_task(config::HookEvent::PreDisconnect)
+                    .chain(disconnect)
             }
             Message::Connected(result) => {
                 match result {
                     Ok(msg) => {
                         info!("[UI] Connection successful: {}", msg);
-                        self.state = ConnectionState::Connected {
-                            connected_at: chrono::Local::now()
-                                .format("%Y-%m-%d %H:%M:%S")
-                                .to_string(),
-                        };
-                        self.password.clear();
-                        self.error = None;
+                        let connected_at = chrono::Local::now();
+                        self.state = ConnectionState::Connected { connected_at };
+                        if let Some(attempt) = &mut self.attempt {
+                            attempt.connected = Some(connected_at);
+                        }
+                        self.session = Some(session::Session::new(self.profile(), connected_at));
+                        self.password = secret::Secret::default();
+                        self.certificate_passphrase = secret::Secret::default();
+                        self.traffic = statistics::Traffic::default();
+                        self.history.clear();
+                        self.latency = None;
+
+                        // post-connect hooks need the tunnel address
+                        Task::perform(tunnel::detect(gpclient::pid()), Message::TunnelDetected)
                     }
                     Err(e) => {
                         info!("[UI] Connection failed: {}", e);
                         self.state = ConnectionState::Disconnected;
+                        self.record_attempt(history::Outcome::Failed, Some((e.clone(), None)));
                         self.error = Some(e);
+                        Task::none()
                     }
                 }
-                Task::none()
             }
             Message::Disconnected(result) => {
                 match result {
-                    Ok(msg) => {
-                        info!("[UI] Disconnection successful: {}", msg);
-                        self.error = None;
-                    }
+                    Ok(msg) => info!("[UI] Disconnection successful: {}", msg),
                     Err(e) => {
                         info!("[UI] Disconnection failed: {}", e);
                         self.error = Some(e);
                     }
                 }
                 self.state = ConnectionState::Disconnected;
+                self.record_attempt(history::Outcome::Disconnected, None);
+                split_tunnel::revert();
+                // Re-authentication connects again; keep blocking until then
+                let reauthenticating = std::mem::take(&mut self.reauthenticating);
+                if !reauthenticating {
+                    self.disable_kill_switch();
+                }
+
+                // The last tunnel details are still passed, e.g. for cleanup
+                let hook = self.hook_task(config::HookEvent::PostDisconnect);
+                let revert_dns = self.revert_split_dns_task();
+                self.tunnel = None;
+                self.dns = None;
+                self.split_dns_recorded = None;
+                self.dns_check = None;
+                self.routes = None;
+                self.session = None;
+                let cleanup = Task::batch([hook, revert_dns]);
+                // A failed disconnect is reported rather than reconnected
+                if reauthenticating && self.error.is_none() {
+                    return cleanup.chain(Task::done(Message::ConnectPressed));
+                }
+                cleanup
+            }
+            Message::TunnelDetected(tunnel) => {
+                if !matches!(self.state, ConnectionState::Connected { .. }) {
+                    return Task::none();
+                }
+                let profile = self.profile();
+                let Some(interface) = tunnel.as_ref().map(|t| t.interface.clone()) else {
+                    if profile.kill_switch {
+                        self.error =
+                            Some("Kill switch not enabled: tunnel interface not found".to_string());
+                    } else if !profile.split_include.is_empty() || !profile.split_exclude.is_empty()
+                    {
+                        self.error = Some(
+                            "Split-tunnel rules not applied: tunnel interface not found"
+                                .to_string(),
+                        );
+                    }
+                    return self.hook_task(config::HookEvent::PostConnect);
+                };
+
+                let dns = {
+                    let interface = interface.clone();
+                    Task::perform(
+                        async move { dns::of_tunnel(&interface).await },
+                        Message::DnsDetected,
+                    )
+                };
+                // The DNS details show resolved's settings once applied
+                let dns = if profile.split_dns {
+                    Task::perform(split_dns::apply(interface.clone()), |result| {
+                        Message::SplitDnsApplied(result.map_err(|e| format!("{:#}", e)))
+                    })
+                    .chain(dns)
+                } else {
+                    dns
+                };
+                let kill_switch = self.kill_switch_task(interface.clone());
+                let split = self.split_tunnel_task(interface);
+                self.tunnel = tunnel;
+                self.split_dns_recorded = split_dns::recorded_at();
+                // post-connect hooks see the routes with the rules applied
+                Task::batch([
+                    dns,
+                    kill_switch,
+                    split.chain(self.hook_task(config::HookEvent::PostConnect)),
+                ])
+            }
+            Message::SplitTunnelApplied(report) => {
+                // Disconnected while the rules were being applied
+                if !matches!(self.state, ConnectionState::Connected { .. }) {
+                    split_tunnel::revert();
+                    return Task::none();
+                }
+                for route in &report.applied {
+                    self.log(format!("Split tunnel: {}", route));
+                }
+                for e in &report.errors {
+                    warn!("[UI] Split-tunnel rule failed: {}", e);
+                    self.log(format!("Split tunnel: {}", e));
+                }
+                if let Some(e) = report.errors.first() {
+                    self.error = Some(format!("Split-tunnel rule failed: {}", e));
+                    self.show_log = true;
+                }
+                Task::none()
+            }
+            Message::SplitDnsApplied(result) => {
+                if !matches!(self.state, ConnectionState::Connected { .. }) {
+                    return Task::none();
+                }
+                match result {
+                    Ok(pushed) => {
+                        let domains = if pushed.split_domains.is_empty() {
+                            "no split domains".to_string()
+                        } else {
+                            pushed.split_domains.join(", ")
+                        };
+                        self.log(format!(
+                            "Split DNS configured in systemd-resolved: {}",
+                            domains
+                        ));
+                    }
+                    Err(e) => {
+                        warn!("[UI] Split DNS failed: {}", e);
+                        self.log(format!("Split DNS: {}", e));
+                        self.error = Some(format!("Split DNS not configured: {}", e));
+                    }
+                }
+                Task::none()
+            }
+            Message::KillSwitchEnabled(result) => {
+                match result {
+                    Ok(()) => {
+                        self.kill_switch = true;
+                        self.log("Kill switch enabled");
+                        // Disconnected while the rules were being installed
+                        if !matches!(self.state, ConnectionState::Connected { .. }) {
+                            self.disable_kill_switch();
+                        }
+                    }
+                    Err(e) => {
+                        warn!("[UI] Failed to enable the kill switch: {}", e);
+                        self.log(format!("Kill switch: {}", e));
+                        self.error = Some(format!("Kill switch not enabled: {}", e));
+                    }
+                }
+                Task::none()
+            }
+            Message::DisableKillSwitch => {
+                if self.disable_kill_switch() {
+                    self.error = None;
+                }
+                Task::none()
+            }
+            Message::Reauthenticate => {
+                info!("[UI] Re-authenticate pressed");
+                let profile = self.profile();
+                let password_available = profile.auth_method != config::AuthMethod::Password
+                    || profile.remember_password
+                    || profile.password_command.is_some();
+                self.reauthenticating = true;
+                if password_available {
+                    return self.update(Message::DisconnectPressed);
+                }
+
+                // The password was cleared after connecting; ask for it again.
+                // The error also stops the automatic reconnect.
+                let disconnect = self.update(Message::DisconnectPressed);
+                self.error = Some("Enter your password to re-authenticate".to_string());
+                disconnect
+            }
+            Message::GatewayResolved(addresses) => {
+                self.gateway_addresses = addresses;
+                Task::none()
+            }
+            Message::DnsDetected(dns) => {
+                if matches!(self.state, ConnectionState::Connected { .. }) {
+                    self.dns = dns;
+                }
+                Task::none()
+            }
+            Message::Copy(value) => iced::clipboard::write(value),
+            Message::Tick => {
+                // A vanished interface is noticed by the gpclient check below
+                if let Some(tunnel) = &self.tunnel {
+                    let _ = self.traffic.sample(&tunnel.interface);
+                }
+                self.history.push(statistics::Sample {
+                    rx_rate: self.traffic.rx_rate,
+                    tx_rate: self.traffic.tx_rate,
+                    latency: self.latency.clone().and_then(Result::ok),
+                });
+                let warning = self.session_warning_task();
+                // gpclient keeps retrying a session the gateway has ended
+                if self.session_expired() {
+                    let message = format!(
+                        "The VPN session of {} expired, re-authenticating",
+                        self.profile().name
+                    );
+                    warn!("[UI] {}", message);
+                    self.log(message);
+                    return warning.chain(self.update(Message::Reauthenticate));
+                }
+
+                let state = self.vpn_state.clone();
+                let mut tasks = vec![Task::perform(
+                    gpclient::is_running(state),
+                    Message::ConnectionChecked,
+                )];
+                if let Some(host) = self.profile().probe_host.clone()
+                    && !self.probing
+                {
+                    self.probing = true;
+                    tasks.push(Task::perform(statistics::probe(host), |result| {
+                        Message::Probed(result.map_err(|e| format!("{:#}", e)))
+                    }));
+                }
+                tasks.push(warning);
+                tasks.push(self.reapply_split_dns_task());
+                Task::batch(tasks)
+            }
+            Message::Probed(result) => {
+                self.probing = false;
+                if matches!(self.state, ConnectionState::Connected { .. }) {
+                    self.latency = Some(result);
+                }
+                Task::none()
+            }
+            Message::ConnectionChecked(running) => {
+                if running || !matches!(self.state, ConnectionState::Connected { .. }) {
+                    return Task::none();
+                }
+
+                warn!("[UI] VPN connection lost");
+                self.state = ConnectionState::Disconnected;
+                self.error = Some(if self.kill_switch {
+                    "The VPN connection was lost; the kill switch blocks traffic outside the VPN \
+                     until you reconnect"
+                        .to_string()
+                } else {
+                    "The VPN connection was lost".to_string()
+                });
+                self.log("VPN connection lost");
+                self.record_attempt(history::Outcome::Lost, None);
+                split_tunnel::revert();
+
+                let hook = self.hook_task(config::HookEvent::ConnectionLost);
+                let revert_dns = self.revert_split_dns_task();
+                self.tunnel = None;
+                self.dns = None;
+                self.split_dns_recorded = None;
+                self.dns_check = None;
+                self.routes = None;
+                self.session = None;
+                Task::batch([hook, revert_dns])
+            }
+            Message::HookFinished(report) => {
+                for line in &report.output {
+                    self.log(format!("[{}] {}", report.event, line));
+                }
+                match report.result {
+                    Ok(()) => self.log(format!("{} hook finished", report.event)),
+                    Err(e) => {
+                        warn!("[UI] {} hook failed: {}", report.event, e);
+                        let message = format!("{} hook failed: {}", report.event, e);
+                        self.log(&message);
+                        self.error = Some(message);
+                        self.show_log = true;
+                    }
+                }
+                Task::none()
+            }
+            Message::ToggleLog => {
+                self.show_log = !self.show_log;
                 Task::none()
             }
         }
@@ -234,9 +1847,42 @@ impl GpGui {
 
     fn view(&self) -> Element<'_, Message> {
         let content = match &self.state {
-            ConnectionState::Disconnected => self.view_disconnected(),
+            ConnectionState::Disconnected => match &self.connection_history {
+                Some(history) if self.editor.is_none() => self.view_history(history),
+                _ => self.view_disconnected(),
+            },
             ConnectionState::Connecting => self.view_connecting(),
-            ConnectionState::Connected { connected_at } => self.view_connected(connected_at),
+            ConnectionState::Connected { connected_at } => match &self.routes {
+                Some(routes) => self.view_routes(routes),
+                None => self.view_connected(connected_at),
+            },
+        };
+
+        let content: Element<'_, Message> = if self.editor.is_none() && !self.log.is_empty() {
+            column![content, self.view_log()].into()
+        } else {
+            content
+        };
+
+        let content: Element<'_, Message> = match &self.config_warning {
+            Some(warning) => column![
+                container(
+                    row![
+                        text(warning.as_str()).size(13).width(Length::Fill),
+                        button(text("Dismiss").size(13))
+                            .on_press(Message::DismissConfigWarning)
+                            .padding(6),
+                    ]
+                    .spacing(8)
+                    .align_y(Alignment::Center),
+                )
+                .padding(10)
+                .width(Length::Fill)
+                .style(container::rounded_box),
+                content,
+            ]
+            .into(),
+            None => content,
         };
 
         container(content)
@@ -252,50 +1898,327 @@ impl GpGui {
     }
 
     fn subscription(&self) -> Subscription<Message> {
-        iced::event::listen().map(Message::EventOccurred)
+        let connection_check = match self.state {
+            ConnectionState::Connected { .. } => {
+                iced::time::every(std::time::Duration::from_secs(TICK_SECS)).map(|_| Message::Tick)
+            }
+            _ => Subscription::none(),
+        };
+
+        Subscription::batch([
+            iced::event::listen().map(Message::EventOccurred),
+            Subscription::run(activations).map(Message::Activated),
+            connection_check,
+        ])
     }
 
     fn view_disconnected(&self) -> Element<'_, Message> {
+        if let Some(editor) = &self.editor {
+            let expiry = self.certificate_expiry(&editor.profile);
+            return scrollable(Self::view_editor(editor, expiry).map(Message::Editor)).into();
+        }
+
+        let profile = self.profile();
+
         let mut content = column![
             text("GlobalProtect VPN").size(28),
             Space::new().height(5),
             text("● Disconnected").size(14),
             Space::new().height(15),
+            text("Profile").size(13),
+            row![
+                pick_list(
+                    self.config.profile_names(),
+                    Some(profile.name.clone()),
+                    Message::ProfileSelected,
+                )
+                .padding(8)
+                .text_size(14)
+                .width(Length::Fill),
+                button(text("Edit").size(14))
+                    .on_press(Message::EditProfile)
+                    .padding(8),
+                button(text("New").size(14))
+                    .on_press(Message::NewProfile)
+                    .padding(8),
+                button(text("History").size(14))
+                    .on_press(Message::ShowHistory)
+                    .padding(8),
+            ]
+            .spacing(6),
+            Space::new().height(12),
             text("VPN Server").size(13),
-            text_input("e.g., access.tii.ae", &self.gateway)
+            text_input("e.g., vpn.example.com", &profile.gateway)
                 .id(self.gateway_id.clone())
-                .on_input(Message::GatewayChanged)
+                .on_input_maybe(
+                    (!profile.is_locked(config::LockedField::Gateway))
+                        .then_some(Message::GatewayChanged),
+                )
                 .on_submit(Message::FocusNext)
                 .padding(8)
                 .size(14),
             Space::new().height(12),
             text("Username").size(13),
-            text_input("Username", &self.username)
+            text_input("Username", &profile.username)
                 .id(self.username_id.clone())
                 .on_input(Message::UsernameChanged)
                 .on_submit(Message::FocusNext)
                 .padding(8)
                 .size(14),
-            Space::new().height(12),
-            text("Password").size(13),
-            text_input("Password", &self.password)
-                .id(self.password_id.clone())
-                .on_input(Message::PasswordChanged)
-                .on_submit(Message::ConnectPressed)
-                .padding(8)
-                .size(14)
-                .secure(true),
-            Space::new().height(15),
+        ]
+        .spacing(4)
+        .padding(25)
+        .max_width(450);
+
+        if profile.auth_method == config::AuthMethod::Password {
+            let placeholder = if profile.password_command.is_some() {
+                "Password (leave empty to run password command)"
+            } else if profile.remember_password {
+                "Password (leave empty to use saved password)"
+            } else {
+                "Password"
+            };
+
+            content = content
+                .push(Space::new().height(12))
+                .push(text("Password").size(13))
+                .push(
+                    text_input(placeholder, self.password.expose())
+                        .id(self.password_id.clone())
+                        .on_input(|password| Message::PasswordChanged(password.into()))
+                        .on_submit(Message::ConnectPressed)
+                        .padding(8)
+                        .size(14)
+                        .secure(true),
+                )
+                .push(
+                    checkbox(profile.remember_password)
+                        .label("Remember password in keyring")
+                        .on_toggle(Message::RememberPasswordToggled)
+                        .text_size(13),
+                );
+        }
+
+        if let Some(path) = &profile.certificate {
+            content = content.push(Space::new().height(12));
+            if let Some(expiry) = self.certificate_expiry(profile) {
+                content = content.push(text(expiry).size(13));
+            }
+            if certificate::CertificateKind::of(path) != certificate::CertificateKind::Pkcs11 {
+                content = content.push(
+                    text_input(
+                        "Certificate passphrase (leave empty if none)",
+                        self.certificate_passphrase.expose(),
+                    )
+                    .on_input(|passphrase| Message::CertificatePassphraseChanged(passphrase.into()))
+                    .on_submit(Message::ConnectPressed)
+                    .padding(8)
+                    .size(14)
+                    .secure(true),
+                );
+            }
+        }
+
+        content = content.push(Space::new().height(15)).push(
             button(text("Authenticate & Connect").size(16))
                 .on_press(Message::ConnectPressed)
                 .padding(10)
                 .width(Length::Fill),
+        );
+
+        if let Some(warning) = &self.gateway_warning {
+            content = content.push(Space::new().height(12));
+            content = content.push(text(format!("Warning: {}", warning)).size(13));
+        }
+
+        if let Some(error) = &self.error {
+            content = content.push(Space::new().height(12));
+            content = content.push(text(format!("Error: {}", error)).size(13));
+        }
+
+        if self.kill_switch {
+            content = content
+                .push(Space::new().height(12))
+                .push(text("Kill switch active: traffic outside the VPN is blocked").size(13))
+                .push(
+                    button(text("Disable Kill Switch").size(14))
+                        .on_press(Message::DisableKillSwitch)
+                        .padding(8),
+                );
+        }
+
+        scrollable(content).into()
+    }
+
+    fn view_editor<'a>(
+        editor: &'a ProfileEditor,
+        certificate_expiry: Option<&'a str>,
+    ) -> Element<'a, EditorMessage> {
+        let title = if editor.index.is_some() {
+            "Edit Profile"
+        } else {
+            "New Profile"
+        };
+
+        let mut buttons = row![
+            button(text("Save").size(16))
+                .on_press(EditorMessage::Save)
+                .padding(10)
+                .width(Length::Fill),
+            button(text("Cancel").size(16))
+                .on_press(EditorMessage::Cancel)
+                .padding(10)
+                .width(Length::Fill),
+        ]
+        .spacing(8);
+
+        let forget = editor.index.map(|_| {
+            button(text("Forget Saved Password").size(14))
+                .on_press(EditorMessage::ForgetPassword)
+                .padding(8)
+        });
+
+        // System profiles would reappear on the next start
+        if editor.index.is_some() && !editor.profile.managed {
+            buttons = buttons.push(
+                button(text("Delete").size(16))
+                    .on_press(EditorMessage::Delete)
+                    .padding(10)
+                    .width(Length::Fill),
+            );
+        }
+
+        let mut content = column![
+            text(title).size(28),
+            Space::new().height(15),
+            text("Profile Name").size(13),
+            text_input("e.g., Production", &editor.profile.name)
+                .on_input_maybe((!editor.profile.managed).then_some(EditorMessage::NameChanged))
+                .padding(8)
+                .size(14),
+            Space::new().height(12),
+            text("VPN Server").size(13),
+            text_input("e.g., vpn.example.com", &editor.profile.gateway)
+                .on_input_maybe(
+                    (!editor.profile.is_locked(config::LockedField::Gateway))
+                        .then_some(EditorMessage::GatewayChanged),
+                )
+                .padding(8)
+                .size(14),
+            Space::new().height(12),
+            text("Username").size(13),
+            text_input("Username", &editor.profile.username)
+                .on_input(EditorMessage::UsernameChanged)
+                .padding(8)
+                .size(14),
+            Space::new().height(12),
+            text("Authentication").size(13),
+            pick_list(
+                &config::AuthMethod::ALL[..],
+                Some(editor.profile.auth_method),
+                EditorMessage::AuthMethodChanged,
+            )
+            .padding(8)
+            .text_size(14)
+            .width(Length::Fill),
+            Space::new().height(12),
+            text("Password Command (optional)").size(13),
+            text_input(
+                "e.g., pass show work/vpn",
+                editor
+                    .profile
+                    .password_command
+                    .as_deref()
+                    .unwrap_or_default(),
+            )
+            .on_input(EditorMessage::PasswordCommandChanged)
+            .padding(8)
+            .size(14),
+            Space::new().height(12),
+            text("Client Certificate (optional)").size(13),
+            row![
+                text_input(
+                    "PEM, PKCS#12 or pkcs11: URI",
+                    editor.profile.certificate.as_deref().unwrap_or_default(),
+                )
+                .on_input(EditorMessage::CertificateChanged)
+                .padding(8)
+                .size(14)
+                .width(Length::Fill),
+                button(text("Browse").size(14))
+                    .on_press(EditorMessage::BrowseCertificate)
+                    .padding(8),
+            ]
+            .spacing(6),
+            text(certificate_expiry.unwrap_or_default()).size(13),
+            text("Certificate Key (optional)").size(13),
+            row![
+                text_input(
+                    "Only if not contained in the certificate",
+                    editor
+                        .profile
+                        .certificate_key
+                        .as_deref()
+                        .unwrap_or_default(),
+                )
+                .on_input(EditorMessage::CertificateKeyChanged)
+                .padding(8)
+                .size(14)
+                .width(Length::Fill),
+                button(text("Browse").size(14))
+                    .on_press(EditorMessage::BrowseCertificateKey)
+                    .padding(8),
+            ]
+            .spacing(6),
+            Space::new().height(12),
+            Self::view_gateway_certificate(editor),
+            Space::new().height(12),
+            text("Connection Timeout (seconds)").size(13),
+            text_input("60", &editor.timeout)
+                .on_input(EditorMessage::TimeoutChanged)
+                .padding(8)
+                .size(14),
+            Space::new().height(12),
+            checkbox(editor.make_default)
+                .label("Use as default profile")
+                .on_toggle(EditorMessage::DefaultToggled)
+                .text_size(14),
+            Space::new().height(12),
+            button(
+                text(if editor.advanced {
+                    "▾ Advanced"
+                } else {
+                    "▸ Advanced"
+                })
+                .size(14)
+            )
+            .on_press(EditorMessage::AdvancedToggled)
+            .padding(8)
+            .style(button::text),
         ]
         .spacing(4)
         .padding(25)
         .max_width(450);
 
-        if let Some(error) = &self.error {
+        if editor.advanced {
+            content = content.push(Self::view_advanced(editor));
+        }
+
+        if editor.profile.managed {
+            content = content.push(Space::new().height(12)).push(
+                text("Provided by your administrator; locked fields cannot be changed").size(13),
+            );
+        }
+        if let Some(forget) = forget {
+            content = content.push(Space::new().height(12)).push(forget);
+        }
+        if let Some(status) = &editor.status {
+            content = content.push(text(status.as_str()).size(13));
+        }
+
+        content = content.push(Space::new().height(15)).push(buttons);
+
+        if let Some(error) = &editor.error {
             content = content.push(Space::new().height(12));
             content = content.push(text(format!("Error: {}", error)).size(13));
         }
@@ -303,6 +2226,343 @@ impl GpGui {
         content.into()
     }
 
+    /// Connection options passed through to gpclient.
+    fn view_advanced(editor: &ProfileEditor) -> Element<'_, EditorMessage> {
+        let profile = &editor.profile;
+        let csd_locked = profile.is_locked(config::LockedField::CsdWrapper);
+        let fix_openssl_locked = profile.is_locked(config::LockedField::FixOpenssl);
+
+        column![
+            Self::optional_field(
+                "Auth Group",
+                "Gateway default",
+                &profile.authgroup,
+                Some(EditorMessage::AuthgroupChanged),
+            ),
+            Self::optional_field(
+                "CSD Wrapper",
+                "Located automatically",
+                &profile.csd_wrapper,
+                (!csd_locked).then_some(EditorMessage::CsdWrapperChanged),
+            ),
+            column![
+                text("Reported OS").size(13),
+                pick_list(
+                    &config::ClientOs::ALL[..],
+                    Some(profile.os),
+                    EditorMessage::OsChanged,
+                )
+                .padding(8)
+                .text_size(14)
+                .width(Length::Fill),
+            ]
+            .spacing(4),
+            Self::optional_field(
+                "Client Version",
+                "gpclient default",
+                &profile.client_version,
+                Some(EditorMessage::ClientVersionChanged),
+            ),
+            Self::optional_field(
+                "User Agent",
+                "gpclient default",
+                &profile.user_agent,
+                Some(EditorMessage::UserAgentChanged),
+            ),
+            column![
+                text("Reconnect Timeout (seconds)").size(13),
+                text_input("gpclient default", &editor.reconnect_timeout)
+                    .on_input(EditorMessage::ReconnectTimeoutChanged)
+                    .padding(8)
+                    .size(14),
+            ]
+            .spacing(4),
+            column![
+                text("MTU").size(13),
+                text_input("Automatic", &editor.mtu)
+                    .on_input(EditorMessage::MtuChanged)
+                    .padding(8)
+                    .size(14),
+            ]
+            .spacing(4),
+            Self::optional_field(
+                "Proxy",
+                "http://proxy:3128 or socks5://user@proxy:1080",
+                &profile.proxy,
+                Some(EditorMessage::ProxyChanged),
+            ),
+            column![
+                text("Proxy Password (saved in keyring)").size(13),
+                text_input(
+                    "Leave empty to keep the saved one",
+                    editor.proxy_password.expose()
+                )
+                .on_input(|password| EditorMessage::ProxyPasswordChanged(password.into()))
+                .padding(8)
+                .size(14)
+                .secure(true),
+            ]
+            .spacing(4),
+            Self::optional_field(
+                "Latency Probe",
+                "Internal host[:port], graphed while connected",
+                &profile.probe_host,
+                Some(EditorMessage::ProbeHostChanged),
+            ),
+            column![
+                text("Route Through VPN").size(13),
+                text_input("e.g. 10.20.0.0/16, wiki.example.com", &editor.split_include)
+                    .on_input(EditorMessage::SplitIncludeChanged)
+                    .padding(8)
+                    .size(14),
+            ]
+            .spacing(4),
+            column![
+                text("Keep Off VPN").size(13),
+                text_input("Local subnets, e.g. 192.168.50.0/24", &editor.split_exclude)
+                    .on_input(EditorMessage::SplitExcludeChanged)
+                    .padding(8)
+                    .size(14),
+            ]
+            .spacing(4),
+            column![
+                text("Session Lifetime (minutes)").size(13),
+                text_input("Reported by gpclient", &editor.session_lifetime)
+                    .on_input(EditorMessage::SessionLifetimeChanged)
+                    .padding(8)
+                    .size(14),
+            ]
+            .spacing(4),
+            column![
+                text("Idle Timeout (minutes)").size(13),
+                text_input("Reported by gpclient", &editor.idle_timeout)
+                    .on_input(EditorMessage::IdleTimeoutChanged)
+                    .padding(8)
+                    .size(14),
+            ]
+            .spacing(4),
+            column![
+                text("Warn Before Session End (minutes)").size(13),
+                text_input("10", &editor.session_warning)
+                    .on_input(EditorMessage::SessionWarningChanged)
+                    .padding(8)
+                    .size(14),
+            ]
+            .spacing(4),
+            column![
+                text("Warn Before Gateway Certificate Expiry (days)").size(13),
+                text_input("30", &editor.expiry_warning_days)
+                    .on_input(EditorMessage::ExpiryWarningDaysChanged)
+                    .padding(8)
+                    .size(14),
+            ]
+            .spacing(4),
+            checkbox(profile.as_gateway)
+                .label("Connect to the gateway directly (skip the portal)")
+                .on_toggle(EditorMessage::AsGatewayToggled)
+                .text_size(14),
+            checkbox(profile.fix_openssl)
+                .label("Enable legacy OpenSSL renegotiation")
+                .on_toggle_maybe((!fix_openssl_locked).then_some(EditorMessage::FixOpensslToggled))
+                .text_size(14),
+            checkbox(profile.hip)
+                .label("Submit HIP report")
+                .on_toggle(EditorMessage::HipToggled)
+                .text_size(14),
+            checkbox(profile.disable_ipv6)
+                .label("Disable IPv6 on the tunnel")
+                .on_toggle(EditorMessage::DisableIpv6Toggled)
+                .text_size(14),
+            checkbox(profile.split_dns)
+                .label("Split DNS: configure DNS in systemd-resolved")
+                .on_toggle(EditorMessage::SplitDnsToggled)
+                .text_size(14),
+            checkbox(profile.kill_switch)
+                .label("Kill switch: block traffic outside the VPN")
+                .on_toggle(EditorMessage::KillSwitchToggled)
+                .text_size(14),
+            checkbox(profile.kill_switch_allow_lan)
+                .label("Allow the local network with the kill switch")
+                .on_toggle_maybe(
+                    profile
+                        .kill_switch
+                        .then_some(EditorMessage::KillSwitchAllowLanToggled),
+                )
+                .text_size(14),
+            text(
+                "The kill switch starts blocking once the tunnel is up. Until then, \
+                 on the first connection, traffic leaves unprotected."
+            )
+            .size(12),
+            Self::view_hooks(profile),
+        ]
+        .spacing(12)
+        .padding([0, 25])
+        .max_width(450)
+        .into()
+    }
+
+    /// Hook commands, one per connection event.
+    fn view_hooks(profile: &config::Profile) -> Element<'_, EditorMessage> {
+        let fields = config::HookEvent::ALL.into_iter().map(|event| {
+            let command = profile.hooks.get(&event).map(String::as_str);
+            column![
+                text(format!("Hook: {}", event)).size(13),
+                text_input("Shell command, run as you", command.unwrap_or_default())
+                    .on_input(move |command| EditorMessage::HookChanged(event, command))
+                    .padding(8)
+                    .size(14),
+            ]
+            .spacing(4)
+            .into()
+        });
+
+        Column::with_children(fields).spacing(12).into()
+    }
+
+    /// Pin status of the gateway certificate, and the chain once inspected.
+    fn view_gateway_certificate(editor: &ProfileEditor) -> Element<'_, EditorMessage> {
+        let profile = &editor.profile;
+        let pin_locked = profile.is_locked(config::LockedField::PinnedCertificate);
+
+        let pinned = match &profile.pinned_certificate {
+            Some(pin) => format!("Pinned: {}", pin),
+            None => "Not pinned".to_string(),
+        };
+        let inspect = button(text("Inspect").size(14))
+            .on_press_maybe(
+                (!editor.inspecting && !profile.gateway.trim().is_empty())
+                    .then_some(EditorMessage::InspectGateway),
+            )
+            .padding(8);
+        let mut buttons = row![inspect].spacing(6);
+        if profile.pinned_certificate.is_some() {
+            buttons = buttons.push(
+                button(text("Unpin").size(14))
+                    .on_press_maybe((!pin_locked).then_some(EditorMessage::UnpinCertificate))
+                    .padding(8),
+            );
+        }
+
+        let mut content =
+            column![text("Gateway Certificate").size(13), text(pinned).size(13)].spacing(4);
+
+        match &editor.inspection {
+            None if editor.inspecting => {
+                content = content.push(text("Retrieving certificate...").size(13));
+            }
+            None => {}
+            Some(Err(e)) => content = content.push(text(e.to_string()).size(13)),
+            Some(Ok(certificates)) => {
+                let trust = match &certificates.verify_error {
+                    Some(reason) => format!("Not trusted by the system: {}", reason),
+                    None => "Trusted by the system".to_string(),
+                };
+                content = content.push(text(trust).size(13));
+
+                for (i, certificate) in certificates.chain.iter().enumerate() {
+                    let mut details = column![
+                        text(format!("{}. {}", i + 1, certificate.subject)).size(13),
+                        text(format!("Issuer: {}", certificate.issuer)).size(12),
+                    ]
+                    .spacing(2);
+                    if !certificate.alt_names.is_empty() {
+                        details = details.push(
+                            text(format!("Names: {}", certificate.alt_names.join(", "))).size(12),
+                        );
+                    }
+                    details = details
+                        .push(
+                            text(format!(
+                                "Expires: {}",
+                                certificate.not_after.format("%Y-%m-%d %H:%M UTC")
+                            ))
+                            .size(12),
+                        )
+                        .push(text(format!("SHA-256: {}", certificate.pin)).size(12));
+                    content = content.push(details);
+                }
+
+                if let Some(leaf) = certificates.leaf()
+                    && profile.pinned_certificate.as_ref() != Some(&leaf.pin)
+                {
+                    if profile.pinned_certificate.is_some() {
+                        content = content.push(
+                            text(
+                                "The gateway presents a different certificate than the pinned one",
+                            )
+                            .size(13),
+                        );
+                    }
+                    buttons = buttons.push(
+                        button(text("Pin").size(14))
+                            .on_press_maybe((!pin_locked).then_some(EditorMessage::PinCertificate))
+                            .padding(8),
+                    );
+                }
+            }
+        }
+
+        content.push(buttons).into()
+    }
+
+    /// A labelled text input for an optional setting; read-only without
+    /// `on_input`.
+    fn optional_field<'a>(
+        label: &'a str,
+        placeholder: &'a str,
+        value: &'a Option<String>,
+        on_input: Option<fn(String) -> EditorMessage>,
+    ) -> Element<'a, EditorMessage> {
+        column![
+            text(label).size(13),
+            text_input(placeholder, value.as_deref().unwrap_or_default())
+                .on_input_maybe(on_input)
+                .padding(8)
+                .size(14),
+        ]
+        .spacing(4)
+        .into()
+    }
+
+    /// Hook output, collapsed behind a toggle.
+    fn view_log(&self) -> Element<'_, Message> {
+        let label = if self.show_log {
+            "Hide Log".to_string()
+        } else {
+            format!("Show Log ({})", self.log.len())
+        };
+        let mut pane = column![
+            button(text(label).size(13))
+                .on_press(Message::ToggleLog)
+                .padding(6)
+        ]
+        .spacing(6)
+        .padding([0, 25])
+        .max_width(450);
+
+        if self.show_log {
+            let lines = Column::with_children(
+                self.log
+                    .iter()
+                    .map(|line| text(line.as_str()).size(12).into()),
+            );
+            pane = pane.push(
+                container(
+                    scrollable(lines)
+                        .anchor_bottom()
+                        .height(150)
+                        .width(Length::Fill),
+                )
+                .padding(8)
+                .width(Length::Fill)
+                .style(container::rounded_box),
+            );
+        }
+
+        pane.into()
+    }
+
     fn view_connecting(&self) -> Element<'_, Message> {
         column![
             text("GlobalProtect VPN").size(28),
@@ -317,34 +2577,595 @@ impl GpGui {
         .into()
     }
 
-    fn view_connected(&self, connected_at: &str) -> Element<'_, Message> {
-        let connected_at = connected_at.to_string();
+    /// Addresses and DNS settings of the session, with copy buttons.
+    fn view_addresses(&self) -> Element<'_, Message> {
+        let join = |items: &[String]| items.join(", ");
+        let mut details = column![].spacing(4);
+
+        if let Some(tunnel) = &self.tunnel
+            && !tunnel.addresses.is_empty()
+        {
+            let addresses: Vec<String> = tunnel.addresses.iter().map(|ip| ip.to_string()).collect();
+            details = details
+                .push(Space::new().height(8))
+                .push(copy_row("Tunnel IP:", join(&addresses)));
+        }
+        if let Some(dns) = &self.dns {
+            let servers: Vec<String> = dns.servers.iter().map(|ip| ip.to_string()).collect();
+            details = details
+                .push(Space::new().height(8))
+                .push(copy_row("DNS servers:", join(&servers)));
+            if !dns.search_domains.is_empty() {
+                details = details
+                    .push(Space::new().height(8))
+                    .push(copy_row("Search domains:", join(&dns.search_domains)));
+            }
+            if !dns.routing_domains.is_empty() {
+                details = details
+                    .push(Space::new().height(8))
+                    .push(copy_row("DNS-routed domains:", join(&dns.routing_domains)));
+            }
+            details = details
+                .push(Space::new().height(8))
+                .push(detail_row("DNS from:", dns.source.to_string()));
+        }
+        if !self.gateway_addresses.is_empty() {
+            let addresses: Vec<String> = self
+                .gateway_addresses
+                .iter()
+                .map(|ip| ip.to_string())
+                .collect();
+            details = details
+                .push(Space::new().height(8))
+                .push(copy_row("Gateway IP:", join(&addresses)));
+        }
+
+        details.into()
+    }
+
+    /// Kill switch state, if the profile uses one.
+    fn view_kill_switch(&self) -> Element<'_, Message> {
+        let profile = self.profile();
+        if !profile.kill_switch {
+            return column![].into();
+        }
+
+        let status = match (self.kill_switch, profile.kill_switch_allow_lan) {
+            (false, _) => "Not active",
+            (true, false) => "Active",
+            (true, true) => "Active, local network allowed",
+        };
         column![
-            text("GlobalProtect VPN").size(28),
+            Space::new().height(8),
+            detail_row("Kill switch:", status.to_string())
+        ]
+        .into()
+    }
+
+    /// Findings of the last DNS check, worst first.
+    fn view_dns_check(&self) -> Element<'_, Message> {
+        let mut details = column![].spacing(4);
+        if self.dns_checking {
+            return details
+                .push(Space::new().height(8))
+                .push(detail_row("DNS check:", "Running...".to_string()))
+                .into();
+        }
+        let Some(report) = &self.dns_check else {
+            return details.into();
+        };
+
+        let mut findings: Vec<&dns_check::Finding> = report.findings.iter().collect();
+        findings.sort_by_key(|f| std::cmp::Reverse(f.outcome));
+        details = details
+            .push(Space::new().height(8))
+            .push(detail_row("DNS check:", report.worst().to_string()));
+        for finding in findings {
+            details =
+                details.push(text(format!("{}: {}", finding.outcome, finding.message)).size(12));
+        }
+        details.into()
+    }
+
+    /// Countdown to the end of the gateway session, with re-authentication
+    /// once it is near.
+    fn view_session(&self) -> Element<'_, Message> {
+        let Some((end, limit)) = self.session.as_ref().and_then(|session| session.end()) else {
+            return column![].into();
+        };
+
+        let reason = match limit {
+            session::Limit::Lifetime => "login lifetime",
+            session::Limit::Idle => "idle timeout",
+        };
+        let remaining = (end - chrono::Local::now()).to_std().ok();
+        let value = match remaining {
+            Some(remaining) => format!(
+                "{} left, until {} ({})",
+                statistics::format_duration(remaining),
+                end.format("%H:%M"),
+                reason
+            ),
+            None => format!("Ended at {} ({})", end.format("%H:%M"), reason),
+        };
+
+        let mut details = column![Space::new().height(8), detail_row("Session:", value)].spacing(4);
+        let warning = self
+            .session
+            .as_ref()
+            .map(|session| session.warning)
+            .unwrap_or_default();
+        if remaining.is_none_or(|remaining| remaining <= warning) {
+            details = details.push(
+                button(text("Re-authenticate").size(14))
+                    .on_press(Message::Reauthenticate)
+                    .padding(8),
+            );
+        }
+        details.into()
+    }
+
+    /// Traffic counters and throughput of the tunnel.
+    fn view_statistics(&self) -> Element<'_, Message> {
+        let mut details = column![Space::new().height(8)].spacing(4);
+        let Some(tunnel) = &self.tunnel else {
+            return details
+                .push(detail_row(
+                    "Traffic:",
+                    "Unavailable (tunnel interface not found)".to_string(),
+                ))
+                .into();
+        };
+
+        let traffic = &self.traffic;
+        let counters = &traffic.counters;
+        let bytes = |total: u64, rate: f64| {
+            format!(
+                "{} ({}/s)",
+                statistics::format_bytes(total as f64),
+                statistics::format_bytes(rate)
+            )
+        };
+
+        details = details
+            .push(detail_row("Interface:", tunnel.interface.clone()))
+            .push(Space::new().height(8))
+            .push(detail_row(
+                "Received:",
+                bytes(counters.rx_bytes, traffic.rx_rate),
+            ))
+            .push(Space::new().height(8))
+            .push(detail_row(
+                "Sent:",
+                bytes(counters.tx_bytes, traffic.tx_rate),
+            ))
+            .push(Space::new().height(8))
+            .push(detail_row(
+                "Packets:",
+                format!(
+                    "{} received, {} sent",
+                    counters.rx_packets, counters.tx_packets
+                ),
+            ))
+            .push(Space::new().height(8))
+            .push(detail_row(
+                "Errors:",
+                format!(
+                    "{} received, {} sent, {} dropped",
+                    counters.rx_errors,
+                    counters.tx_errors,
+                    counters.rx_dropped + counters.tx_dropped
+                ),
+            ));
+        details.into()
+    }
+
+    /// Throughput and latency graphs of the last `GRAPH_MINUTES`.
+    fn view_graphs(&self) -> Element<'_, Message> {
+        let theme = self.theme();
+        let capacity = self.history.capacity();
+        let samples = || self.history.samples();
+        let mut panel = column![].spacing(4);
+
+        if self.tunnel.is_some() {
+            let throughput = graphs::Sparkline::new(capacity)
+                .line(
+                    graphs::Series::Received,
+                    samples().map(|sample| Some(sample.rx_rate as f32)),
+                )
+                .line(
+                    graphs::Series::Sent,
+                    samples().map(|sample| Some(sample.tx_rate as f32)),
+                );
+            let peak = statistics::format_bytes(f64::from(throughput.peak()));
+            panel = panel
+                .push(Space::new().height(8))
+                .push(
+                    row![
+                        text(format!("Throughput, last {} min:", GRAPH_MINUTES)).size(13),
+                        text("received")
+                            .size(13)
+                            .color(graphs::Series::Received.color(&theme)),
+                        text("sent")
+                            .size(13)
+                            .color(graphs::Series::Sent.color(&theme)),
+                        text(format!("(peak {}/s)", peak)).size(13),
+                    ]
+                    .spacing(6),
+                )
+                .push(canvas(throughput).width(Length::Fill).height(GRAPH_HEIGHT));
+        }
+
+        if let Some(host) = &self.profile().probe_host {
+            let latency = graphs::Sparkline::new(capacity).line(
+                graphs::Series::Latency,
+                samples()
+                    .map(|sample| sample.latency.map(|latency| latency.as_secs_f32() * 1000.0)),
+            );
+            let current = match &self.latency {
+                Some(Ok(latency)) => format!("{} ms", latency.as_millis()),
+                Some(Err(e)) => e.clone(),
+                None => "measuring...".to_string(),
+            };
+            panel = panel
+                .push(Space::new().height(8))
+                .push(
+                    text(format!(
+                        "Latency to {}: {} (peak {:.0} ms)",
+                        host,
+                        current,
+                        latency.peak()
+                    ))
+                    .size(13),
+                )
+                .push(canvas(latency).width(Length::Fill).height(GRAPH_HEIGHT));
+        }
+
+        panel.into()
+    }
+
+    /// Past connection attempts with filters and export.
+    fn view_history<'a>(&'a self, view: &'a HistoryView) -> Element<'a, Message> {
+        // Profiles since renamed or deleted are still in the history
+        let mut names = self.config.profile_names();
+        if let Some(Ok(records)) = &view.records {
+            for record in records {
+                if !names.contains(&record.profile) {
+                    names.push(record.profile.clone());
+                }
+            }
+        }
+        let mut profiles = vec![ProfileFilter(None)];
+        profiles.extend(names.into_iter().map(|name| ProfileFilter(Some(name))));
+        let mut outcomes = vec![OutcomeFilter(None)];
+        outcomes.extend(history::Outcome::ALL.map(|outcome| OutcomeFilter(Some(outcome))));
+
+        let invalid_date = |value: &str| {
+            !value.trim().is_empty()
+                && chrono::NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d").is_err()
+        };
+
+        let mut content = column![
+            text("Connection History").size(28),
             Space::new().height(5),
-            text("● Connected").size(18),
-            Space::new().height(20),
             row![
-                text("Gateway:").size(13),
-                Space::new().width(8),
-                text(self.gateway.clone()).size(13)
+                pick_list(
+                    profiles,
+                    Some(ProfileFilter(view.profile.clone())),
+                    |choice| Message::History(HistoryMessage::ProfileSelected(choice)),
+                )
+                .padding(8)
+                .text_size(14)
+                .width(Length::Fill),
+                pick_list(outcomes, Some(OutcomeFilter(view.outcome)), |choice| {
+                    Message::History(HistoryMessage::OutcomeSelected(choice))
+                })
+                .padding(8)
+                .text_size(14)
+                .width(Length::Fill),
             ]
-            .spacing(4),
-            Space::new().height(8),
+            .spacing(6),
             row![
-                text("Username:").size(13),
-                Space::new().width(8),
-                text(self.username.clone()).size(13)
+                text_input("From (YYYY-MM-DD)", &view.from)
+                    .on_input(|from| Message::History(HistoryMessage::FromChanged(from)))
+                    .padding(8)
+                    .size(14),
+                text_input("To (YYYY-MM-DD)", &view.to)
+                    .on_input(|to| Message::History(HistoryMessage::ToChanged(to)))
+                    .padding(8)
+                    .size(14),
             ]
-            .spacing(4),
-            Space::new().height(8),
+            .spacing(6),
+        ]
+        .spacing(4)
+        .padding(25)
+        .max_width(450);
+
+        if invalid_date(&view.from) || invalid_date(&view.to) {
+            content = content.push(text("Dates are entered as YYYY-MM-DD").size(13));
+        }
+
+        let records = view.filtered();
+        match &view.records {
+            None => content = content.push(text("Reading history...").size(13)),
+            Some(Err(e)) => content = content.push(text(format!("Error: {}", e)).size(13)),
+            Some(Ok(_)) => {
+                let connected = records.iter().filter(|r| r.connected.is_some()).count();
+                let duration: u64 = records.iter().map(|r| r.duration_secs).sum();
+                let bytes: u64 = records.iter().map(|r| r.rx_bytes + r.tx_bytes).sum();
+                content = content.push(Space::new().height(10)).push(
+                    text(format!(
+                        "{} attempts, {} connected, {} in total, {} transferred",
+                        records.len(),
+                        connected,
+                        statistics::format_duration(std::time::Duration::from_secs(duration)),
+                        statistics::format_bytes(bytes as f64)
+                    ))
+                    .size(14),
+                );
+
+                for record in records.iter().take(HISTORY_ROWS) {
+                    let mut line = format!(
+                        "{}  {}  {}",
+                        record.started.format("%Y-%m-%d %H:%M"),
+                        record.profile,
+                        record.outcome
+                    );
+                    if record.connected.is_some() {
+                        line.push_str(&format!(
+                            ", {}",
+                            statistics::format_duration(std::time::Duration::from_secs(
+                                record.duration_secs
+                            ))
+                        ));
+                    }
+                    content = content
+                        .push(Space::new().height(4))
+                        .push(text(line).size(13));
+                    if let Some(error) = &record.error {
+                        content = content.push(text(error.clone()).size(12));
+                    }
+                }
+                if records.len() > HISTORY_ROWS {
+                    content = content.push(
+                        text(format!(
+                            "{} older attempts not shown; export them instead",
+                            records.len() - HISTORY_ROWS
+                        ))
+                        .size(13),
+                    );
+                }
+            }
+        }
+
+        match &view.export {
+            Some(Ok(path)) => {
+                content = content
+                    .push(Space::new().height(8))
+                    .push(text(format!("Exported to {}", path)).size(13));
+            }
+            Some(Err(e)) => {
+                content = content
+                    .push(Space::new().height(8))
+                    .push(text(format!("Error: {}", e)).size(13));
+            }
+            None => {}
+        }
+
+        let can_export = !records.is_empty();
+        content = content.push(Space::new().height(15)).push(
             row![
-                text("Connected at:").size(13),
-                Space::new().width(8),
-                text(connected_at).size(13)
+                button(text("Export CSV").size(14))
+                    .on_press_maybe(
+                        can_export
+                            .then_some(Message::History(HistoryMessage::Export(ExportFormat::Csv))),
+                    )
+                    .padding(8),
+                button(text("Export JSON").size(14))
+                    .on_press_maybe(
+                        can_export.then_some(Message::History(HistoryMessage::Export(
+                            ExportFormat::Json
+                        ))),
+                    )
+                    .padding(8),
+                button(text("Back").size(14))
+                    .on_press(Message::History(HistoryMessage::Close))
+                    .padding(8),
             ]
-            .spacing(4),
+            .spacing(6),
+        );
+
+        scrollable(content).into()
+    }
+
+    /// Routes installed via the tunnel and the route lookup.
+    fn view_routes<'a>(&'a self, view: &'a RoutesView) -> Element<'a, Message> {
+        let interface = self
+            .tunnel
+            .as_ref()
+            .map(|tunnel| tunnel.interface.as_str())
+            .unwrap_or_default();
+
+        let mut content = column![text("Routes").size(28), Space::new().height(5)]
+            .spacing(4)
+            .padding(25)
+            .max_width(450);
+
+        match &view.routes {
+            None => content = content.push(text("Reading routes...").size(13)),
+            Some(Err(e)) => content = content.push(text(format!("Error: {}", e)).size(13)),
+            Some(Ok(routes)) => {
+                let mode = routes::TunnelMode::of(routes);
+                content = content
+                    .push(
+                        text(format!(
+                            "{}: {} routes via {}",
+                            mode,
+                            routes.len(),
+                            interface
+                        ))
+                        .size(14),
+                    )
+                    .push(Space::new().height(10))
+                    .push(route_row("Destination", "Gateway", "Metric"));
+
+                for route in routes {
+                    let destination = match route.table {
+                        ROUTE_TABLE_MAIN => route.to_string(),
+                        table => format!("{} (table {})", route, table),
+                    };
+                    let gateway = optional_to_string(route.gateway);
+                    let metric = optional_to_string(route.metric);
+                    content = content.push(route_row(&destination, &gateway, &metric));
+                }
+            }
+        }
+
+        content = content
+            .push(Space::new().height(15))
+            .push(text("Which route does a host use?").size(13))
+            .push(
+                row![
+                    text_input("Host name or address", &view.query)
+                        .on_input(|query| Message::Routes(RoutesMessage::QueryChanged(query)))
+                        .on_submit(Message::Routes(RoutesMessage::Lookup))
+                        .padding(8)
+                        .size(14),
+                    button(text("Check").size(14))
+                        .on_press_maybe(
+                            (!view.looking_up).then_some(Message::Routes(RoutesMessage::Lookup)),
+                        )
+                        .padding(8),
+                ]
+                .spacing(6),
+            );
+
+        match &view.lookup {
+            Some(Ok(host_routes)) => {
+                for host_route in host_routes {
+                    let route = &host_route.route;
+                    let path = match (&route.interface, route.gateway) {
+                        (Some(interface), Some(gateway)) => {
+                            format!("{} via {}", interface, gateway)
+                        }
+                        (Some(interface), None) => interface.clone(),
+                        (None, _) => "no interface".to_string(),
+                    };
+                    let verdict = if host_route.via_tunnel {
+                        "through the VPN"
+                    } else {
+                        "not through the VPN"
+                    };
+                    content = content.push(
+                        text(format!("{}: {} ({})", host_route.address, verdict, path)).size(13),
+                    );
+                }
+            }
+            Some(Err(e)) => content = content.push(text(format!("Error: {}", e)).size(13)),
+            None if view.looking_up => content = content.push(text("Checking...").size(13)),
+            None => {}
+        }
+
+        content = content.push(Space::new().height(15)).push(
+            row![
+                button(text("Refresh").size(14))
+                    .on_press(Message::Routes(RoutesMessage::Refresh))
+                    .padding(8),
+                button(text("Back").size(14))
+                    .on_press(Message::Routes(RoutesMessage::Close))
+                    .padding(8),
+            ]
+            .spacing(6),
+        );
+
+        scrollable(content).into()
+    }
+
+    fn view_connected(
+        &self,
+        connected_at: &chrono::DateTime<chrono::Local>,
+    ) -> Element<'_, Message> {
+        let profile = self.profile();
+        let duration = (chrono::Local::now() - *connected_at)
+            .to_std()
+            .unwrap_or_default();
+
+        let mut certificate = column![].spacing(4);
+        if let Some(leaf) = self
+            .gateway_certificates
+            .as_ref()
+            .and_then(|certificates| certificates.leaf())
+        {
+            let pinned = if profile.pinned_certificate.is_some() {
+                " (pinned)"
+            } else {
+                ""
+            };
+            certificate = certificate.push(Space::new().height(8)).push(
+                row![
+                    text("Certificate:").size(13),
+                    Space::new().width(8),
+                    text(format!(
+                        "{}, expires {}{}",
+                        leaf.subject,
+                        leaf.not_after.format("%Y-%m-%d"),
+                        pinned
+                    ))
+                    .size(13)
+                ]
+                .spacing(4),
+            );
+        }
+        if let Some(warning) = &self.gateway_warning {
+            certificate = certificate
+                .push(Space::new().height(8))
+                .push(text(format!("Warning: {}", warning)).size(13));
+        }
+        if let Some(error) = &self.error {
+            certificate = certificate
+                .push(Space::new().height(8))
+                .push(text(format!("Error: {}", error)).size(13));
+        }
+
+        let content = column![
+            text("GlobalProtect VPN").size(28),
+            Space::new().height(5),
+            text("● Connected").size(18),
+            Space::new().height(20),
+            detail_row("Profile:", profile.name.clone()),
+            Space::new().height(8),
+            detail_row("Gateway:", profile.gateway.clone()),
+            Space::new().height(8),
+            detail_row("Username:", profile.username.clone()),
+            Space::new().height(8),
+            detail_row(
+                "Connected at:",
+                connected_at.format("%Y-%m-%d %H:%M:%S").to_string()
+            ),
+            Space::new().height(8),
+            detail_row("Duration:", statistics::format_duration(duration)),
+            self.view_session(),
+            self.view_kill_switch(),
+            self.view_addresses(),
+            self.view_statistics(),
+            self.view_graphs(),
+            certificate,
+            self.view_dns_check(),
             Space::new().height(20),
+            row![
+                button(text("Routes").size(14))
+                    .on_press(Message::ShowRoutes)
+                    .padding(8)
+                    .width(Length::Fill),
+                button(text("Check DNS").size(14))
+                    .on_press_maybe(
+                        (self.tunnel.is_some() && !self.dns_checking).then_some(Message::CheckDns)
+                    )
+                    .padding(8)
+                    .width(Length::Fill),
+            ]
+            .spacing(6),
+            Space::new().height(6),
             button(text("Disconnect").size(16))
                 .on_press(Message::DisconnectPressed)
                 .padding(10)
@@ -352,14 +3173,206 @@ impl GpGui {
         ]
         .spacing(4)
         .padding(25)
-        .max_width(450)
-        .into()
+        .max_width(450);
+
+        scrollable(content).into()
     }
 }
 
+/// A "Label: value" line of the Connected view.
+fn detail_row<'a>(label: &'a str, value: String) -> Element<'a, Message> {
+    row![
+        text(label).size(13),
+        Space::new().width(8),
+        text(value).size(13)
+    ]
+    .spacing(4)
+    .into()
+}
+
+/// A line of the route table in the Routes view.
+fn route_row<'a>(destination: &str, gateway: &str, metric: &str) -> Element<'a, Message> {
+    row![
+        text(destination.to_string())
+            .size(13)
+            .width(Length::FillPortion(3)),
+        text(gateway.to_string())
+            .size(13)
+            .width(Length::FillPortion(2)),
+        text(metric.to_string())
+            .size(13)
+            .width(Length::FillPortion(1)),
+    ]
+    .spacing(6)
+    .into()
+}
+
+/// A "Label: value" line of the Connected view with a button copying the
+/// value to the clipboard.
+fn copy_row<'a>(label: &'a str, value: String) -> Element<'a, Message> {
+    row![
+        text(label).size(13),
+        Space::new().width(8),
+        text(value.clone()).size(13).width(Length::Fill),
+        button(text("Copy").size(12))
+            .on_press(Message::Copy(value))
+            .padding([2, 8]),
+    ]
+    .spacing(4)
+    .align_y(Alignment::Center)
+    .into()
+}
+
+/// Connect using `profile`.
+///
+/// A typed password always wins. Otherwise the profile's password command is
+/// run, or with "Remember password" enabled the password is taken from the
+/// keyring. A typed password is stored in the keyring once the connection
+/// succeeds if "Remember password" is enabled. `proxy` is the profile's proxy
+/// with its password, if any.
+async fn connect_profile(
+    state: gpclient::VpnState,
+    profile: config::Profile,
+    password: secret::Secret,
+    passphrase: secret::Secret,
+    proxy: Option<proxy::Proxy>,
+) -> anyhow::Result<String> {
+    certificate::check_access(&profile)?;
+
+    let remember = profile.remember_password && profile.auth_method == config::AuthMethod::Password;
+    let typed = !password.is_empty();
+
+    let password = if typed || profile.auth_method != config::AuthMethod::Password {
+        password
+    } else if let Some(command) = &profile.password_command {
+        password_command::run(&profile.name, command).await?
+    } else if remember {
+        keyring::lookup_password(&profile.name)
+            .await
+            .context("Failed to read saved password")?
+            .context("No saved password for this profile, enter it once to remember it")?
+    } else {
+        password
+    };
+
+    let mut config = gpclient::VpnConfig::from_profile(&profile, password.clone());
+    config.proxy = proxy.map(|proxy| proxy.url());
+    if profile.split_dns {
+        config.script = Some(
+            split_dns::prepare()
+                .await
+                .context("Split DNS is not available")?,
+        );
+    }
+
+    // Last, so no decrypted key is left behind by the steps above
+    let passphrase_typed = !passphrase.is_empty();
+    if let Some(key) = certificate::protected_key(&profile).await? {
+        let passphrase = if passphrase_typed {
+            passphrase.clone()
+        } else if profile.remember_password {
+            keyring::lookup(keyring::SecretKind::KeyPassphrase, &profile.name)
+                .await
+                .context("Failed to read saved key passphrase")?
+                .with_context(|| format!("Enter the passphrase of the key in {}", key))?
+        } else {
+            anyhow::bail!("Enter the passphrase of the key in {}", key);
+        };
+        let unlocked = certificate::unlock(&profile, &passphrase).await?;
+        config.certificate = Some(unlocked.certificate);
+        config.certificate_key = unlocked.key;
+    }
+    let result = gpclient::connect_vpn(state, config).await;
+    if result.is_err() {
+        certificate::remove_unlocked();
+    }
+
+    if result.is_ok()
+        && remember
+        && typed
+        && let Err(e) = keyring::store_password(&profile.name, &password).await
+    {
+        warn!("Failed to save password in keyring: {}", e);
+    }
+    if result.is_ok()
+        && profile.remember_password
+        && passphrase_typed
+        && let Err(e) = keyring::store(
+            keyring::SecretKind::KeyPassphrase,
+            &profile.name,
+            &passphrase,
+        )
+        .await
+    {
+        warn!("Failed to save key passphrase in keyring: {}", e);
+    }
+
+    result
+}
+
+/// Run a keyring update in the background, reporting failures.
+fn keyring_task(
+    update: impl Future<Output = anyhow::Result<()>> + Send + 'static,
+) -> Task<Message> {
+    Task::perform(update, |result| {
+        Message::KeyringUpdated(result.map_err(|e| e.to_string()))
+    })
+}
+
+/// Open the file chooser and report the chosen file to the profile editor.
+fn browse(title: &'static str, on_chosen: fn(Option<String>) -> EditorMessage) -> Task<Message> {
+    Task::perform(
+        async move { file_chooser::choose_file(title).await },
+        move |result| {
+            Message::Editor(match result {
+                Ok(path) => on_chosen(path),
+                Err(e) => EditorMessage::BrowseFailed(format!("{:#}", e)),
+            })
+        },
+    )
+}
+
+/// `None` for an empty string, so optional settings are omitted rather than
+/// passed to gpclient empty.
+fn non_empty(value: String) -> Option<String> {
+    (!value.is_empty()).then_some(value)
+}
+
+fn optional_to_string<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+/// Parse an optional number typed in the editor; blank means unset.
+fn parse_optional<T: std::str::FromStr>(value: &str) -> Result<Option<T>, T::Err> {
+    match value.trim() {
+        "" => Ok(None),
+        value => value.parse().map(Some),
+    }
+}
+
+/// Stream of arguments forwarded by later launches of gp-gui.
+///
+/// Ends immediately if this process does not own the single-instance socket.
+fn activations() -> impl Stream<Item = Vec<String>> {
+    iced::stream::channel(8, async |mut output| {
+        let Some(listener) = instance::take_listener() else {
+            return;
+        };
+
+        loop {
+            let args = instance::next_activation(&listener).await;
+            if output.send(args).await.is_err() {
+                break;
+            }
+        }
+    })
+}
+
 impl Drop for GpGui {
     fn drop(&mut self) {
         info!("GpGui dropping, cleaning up...");
         gpclient::cleanup_on_exit();
+        split_tunnel::revert();
+        self.record_attempt(history::Outcome::Exited, None);
     }
 }
@@ -0,0 +1,67 @@
+//! Desktop Notifications
+//!
+//! Notifications are sent to the desktop's notification server
+//! (`org.freedesktop.Notifications`) on the user's session bus. When gp-gui
+//! runs as root they are sent by a helper process running as the invoking
+//! user (see the `user_helper` module), like the file chooser's dialogs.
+
+use crate::{privileges, user_helper};
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use zbus::zvariant::Value;
+use zbus::{Connection, Proxy};
+
+const SERVICE: &str = "org.freedesktop.Notifications";
+const PATH: &str = "/org/freedesktop/Notifications";
+const INTERFACE: &str = "org.freedesktop.Notifications";
+
+const APP_NAME: &str = "gp-gui";
+
+/// Show a notification.
+///
+/// # Errors
+///
+/// Returns an error if no notification server is running.
+pub async fn notify(summary: &str, body: &str) -> Result<()> {
+    if privileges::is_acting_for_user() {
+        user_helper::run(&["notify", summary, body], None).await?;
+        return Ok(());
+    }
+
+    send(summary, body).await
+}
+
+async fn send(summary: &str, body: &str) -> Result<()> {
+    let connection = Connection::session()
+        .await
+        .context("Failed to connect to the session bus")?;
+    let server = Proxy::new(&connection, SERVICE, PATH, INTERFACE).await?;
+
+    // Critical urgency keeps the notification until it is dismissed
+    let hints: HashMap<&str, Value> = HashMap::from([("urgency", Value::from(2u8))]);
+    let actions: Vec<&str> = Vec::new();
+    let _: u32 = server
+        .call(
+            "Notify",
+            &(
+                APP_NAME,
+                0u32,
+                "network-vpn",
+                summary,
+                body,
+                actions,
+                hints,
+                -1i32,
+            ),
+        )
+        .await
+        .context("No notification server available")?;
+    Ok(())
+}
+
+/// Helper side of `gp-gui --user-helper notify <summary> <body>`.
+pub async fn helper(args: &[String]) -> Result<()> {
+    let summary = args.first().map(String::as_str).unwrap_or(APP_NAME);
+    let body = args.get(1).map(String::as_str).unwrap_or_default();
+    send(summary, body).await
+}
@@ -0,0 +1,127 @@
+//! Passwords from External Commands
+//!
+//! A profile can name a shell command that prints the VPN password, so it can
+//! come from a password manager (`pass show work/vpn`, `op read ...`,
+//! `bw get password ...`) instead of being typed. The command runs right
+//! before connecting and the first line of its output is used as the password.
+//!
+//! # Handling the Result
+//!
+//! The output is never logged, cached or written anywhere; it is handed to
+//! gpclient and dropped. Only the exit status and the command's stderr are
+//! reported on failure.
+//!
+//! # Running as Root
+//!
+//! The command belongs to the user, not to root: when gp-gui runs on behalf of
+//! a user it is started with that user's uid/gid, home directory and runtime
+//! directory, so agents such as gpg-agent and the session bus can be reached.
+
+use crate::privileges;
+use crate::secret::Secret;
+use anyhow::{Context, Result};
+use log::info;
+use std::process::Stdio;
+use tokio::process::Command;
+use tokio::time::{Duration, timeout};
+
+/// Maximum time to wait for the command, including any unlock prompt
+const TIMEOUT_SECS: u64 = 60;
+
+/// Run `command` with `sh -c` and return the first line of its output.
+///
+/// # Arguments
+///
+/// * `profile` - Name of the profile, for logging only
+/// * `command` - The shell command line
+///
+/// # Errors
+///
+/// Fails if the command cannot be started, exits unsuccessfully, does not
+/// finish within [`TIMEOUT_SECS`] or prints an empty password.
+pub async fn run(profile: &str, command: &str) -> Result<Secret> {
+    // The command line itself may embed item names worth keeping out of logs
+    info!("Running password command for profile '{}'", profile);
+
+    let mut cmd = Command::new("/bin/sh");
+    cmd.arg("-c")
+        .arg(command)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+
+    privileges::run_as_invoking_user(&mut cmd)?;
+
+    let child = cmd.spawn().context("Failed to start password command")?;
+    let output = timeout(Duration::from_secs(TIMEOUT_SECS), child.wait_with_output())
+        .await
+        .with_context(|| format!("Password command timed out after {}s", TIMEOUT_SECS))?
+        .context("Failed to run password command")?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let mut message = format!("Password command failed ({})", output.status);
+        if !stderr.trim().is_empty() {
+            message = format!("{}: {}", message, stderr.trim());
+        }
+        return Err(anyhow::anyhow!(message));
+    }
+
+    // Cut after the first line in place; the rest is zeroed with the buffer
+    let mut stdout = output.stdout;
+    if let Some(end) = stdout.iter().position(|&b| b == b'\n') {
+        stdout.truncate(end);
+    }
+    if stdout.last() == Some(&b'\r') {
+        stdout.pop();
+    }
+
+    let password = Secret::from_utf8(stdout)
+        .ok_or_else(|| anyhow::anyhow!("Password command printed invalid UTF-8"))?;
+    if password.is_empty() {
+        return Err(anyhow::anyhow!("Password command printed no password"));
+    }
+
+    Ok(password)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn uses_first_line_of_output() {
+        let password = run("test", "printf 'hunter2\\nsecond line\\n'").await;
+        assert_eq!(password.unwrap().expose(), "hunter2");
+    }
+
+    #[tokio::test]
+    async fn keeps_surrounding_spaces() {
+        assert_eq!(
+            run("test", "printf ' pass '").await.unwrap().expose(),
+            " pass "
+        );
+    }
+
+    #[tokio::test]
+    async fn reports_failure_with_stderr() {
+        let error = run("test", "echo 'item not found' >&2; exit 3")
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("exit status: 3"), "{}", error);
+        assert!(error.ends_with(": item not found"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn rejects_empty_password() {
+        assert!(run("test", "true").await.is_err());
+        assert!(run("test", "echo").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_utf8() {
+        assert!(run("test", "printf '\\377'").await.is_err());
+    }
+}
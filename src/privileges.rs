@@ -0,0 +1,257 @@
+//! Acting on Behalf of the Invoking User
+//!
+//! gp-gui runs as root, either through the setuid wrapper or `sudo`, because
+//! gpclient needs to manage the tunnel. Files in the user's home directory
+//! must nevertheless be owned by the user, so all such I/O runs in a process
+//! with the invoking user's uid/gid.
+//!
+//! # Identifying the Invoking User
+//!
+//! - `GP_GUI_INVOKING_UID`, set by the setuid wrapper after it has cleared the
+//!   environment (so it cannot be supplied by the caller)
+//! - `SUDO_UID`, set by `sudo`
+//! - Otherwise the real uid of the process
+//!
+//! The environment is only consulted when the process really runs as root;
+//! an unprivileged process could not switch identities anyway.
+//!
+//! # Helper Processes
+//!
+//! Credentials belong to the whole process, so switching them in gp-gui
+//! itself would briefly strip root from unrelated work on other threads (nft,
+//! routes, gpclient). Forking is no way out either: after a fork of a
+//! multi-threaded process the child may only make async-signal-safe calls,
+//! which rules out allocating, logging or any of the file handling gp-gui
+//! needs. Work as the invoking user therefore always happens in a freshly
+//! executed process, either an external command set up with
+//! [`run_as_invoking_user`] or gp-gui's own `--user-helper` mode (see the
+//! `user_helper` module).
+//!
+//! # Programs Run as Root
+//!
+//! Scripts that gpclient and openconnect run as root, such as the CSD wrapper
+//! or a vpnc-script, may be named in a profile the user can edit. They are
+//! only accepted if [`check_root_owned`] confirms that no one but root could
+//! have changed them.
+
+use anyhow::{Context, Result};
+use log::warn;
+use nix::unistd::{Uid, User, getuid};
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// Environment variable set by the setuid wrapper
+const WRAPPER_UID_VAR: &str = "GP_GUI_INVOKING_UID";
+
+/// Environment variable set by sudo
+const SUDO_UID_VAR: &str = "SUDO_UID";
+
+/// The user that started gp-gui.
+///
+/// Resolved once from the environment and the passwd database. Returns `None`
+/// if the user has no passwd entry.
+pub fn invoking_user() -> Option<&'static User> {
+    static USER: OnceLock<Option<User>> = OnceLock::new();
+
+    USER.get_or_init(|| {
+        let uid = invoking_uid();
+        match User::from_uid(uid) {
+            Ok(Some(user)) => Some(user),
+            Ok(None) => {
+                warn!("No passwd entry for invoking uid {}", uid);
+                None
+            }
+            Err(e) => {
+                warn!("Failed to look up invoking uid {}: {}", uid, e);
+                None
+            }
+        }
+    })
+    .as_ref()
+}
+
+/// Uid of the user that started gp-gui.
+///
+/// Unlike [`invoking_user`] this does not need a passwd entry.
+pub fn invoking_uid() -> Uid {
+    resolve_uid(getuid(), |var| std::env::var(var).ok())
+}
+
+/// Pick the invoking uid given the real uid and a way to read the environment.
+fn resolve_uid(real: Uid, env: impl Fn(&str) -> Option<String>) -> Uid {
+    if !real.is_root() {
+        return real;
+    }
+
+    [WRAPPER_UID_VAR, SUDO_UID_VAR]
+        .iter()
+        .find_map(|var| env(var)?.parse().ok())
+        .map(Uid::from_raw)
+        .unwrap_or(real)
+}
+
+/// Whether gp-gui runs as root on behalf of another user.
+///
+/// Based on the real uid of the process.
+pub fn is_acting_for_user() -> bool {
+    getuid().is_root() && invoking_user().is_some_and(|user| !user.uid.is_root())
+}
+
+/// Make `command` run as the invoking user, with that user's home directory
+/// and runtime directory.
+///
+/// Does nothing unless gp-gui runs as root on behalf of another user.
+pub fn run_as_invoking_user(command: &mut tokio::process::Command) -> Result<()> {
+    run_std_as_invoking_user(command.as_std_mut())
+}
+
+/// Like [`run_as_invoking_user`], for a blocking command.
+pub fn run_std_as_invoking_user(command: &mut std::process::Command) -> Result<()> {
+    use std::os::unix::process::CommandExt;
+
+    if !is_acting_for_user() {
+        return Ok(());
+    }
+
+    let user = invoking_user().context("Invoking user is unknown")?;
+    command
+        .uid(user.uid.as_raw())
+        .gid(user.gid.as_raw())
+        .current_dir(&user.dir)
+        .env("HOME", &user.dir)
+        .env("USER", &user.name)
+        .env("LOGNAME", &user.name)
+        .env("XDG_RUNTIME_DIR", format!("/run/user/{}", user.uid));
+    Ok(())
+}
+
+/// Make sure only root can modify the program at `path`.
+///
+/// The file and every directory above it (after resolving symlinks) must be
+/// owned by root and not writable by group or others. Directories with the
+/// sticky bit, such as `/tmp` or `/nix/store`, may be writable by others:
+/// entries owned by root cannot be replaced there.
+///
+/// # Errors
+///
+/// Returns an error naming the offending path if the check fails.
+pub fn check_root_owned(path: &Path) -> Result<()> {
+    if !path.is_absolute() {
+        anyhow::bail!("{} is not an absolute path", path.display());
+    }
+    let resolved = path
+        .canonicalize()
+        .with_context(|| format!("Failed to resolve {}", path.display()))?;
+
+    for (index, component) in resolved.ancestors().enumerate() {
+        let metadata = component
+            .metadata()
+            .with_context(|| format!("Failed to inspect {}", component.display()))?;
+        let mode = metadata.permissions().mode();
+        let sticky_dir = index > 0 && mode & 0o1000 != 0;
+
+        if metadata.uid() != 0 {
+            anyhow::bail!(
+                "{} is owned by uid {}, not root",
+                component.display(),
+                metadata.uid()
+            );
+        }
+        if mode & 0o022 != 0 && !sticky_dir {
+            anyhow::bail!(
+                "{} is writable by users other than root",
+                component.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env<'a>(vars: &'a [(&str, &str)]) -> impl Fn(&str) -> Option<String> + 'a {
+        |name| {
+            vars.iter()
+                .find(|(var, _)| *var == name)
+                .map(|(_, value)| value.to_string())
+        }
+    }
+
+    #[test]
+    fn unprivileged_processes_ignore_the_environment() {
+        let vars = [(WRAPPER_UID_VAR, "0"), (SUDO_UID_VAR, "0")];
+        assert_eq!(resolve_uid(Uid::from_raw(1000), env(&vars)), 1000.into());
+    }
+
+    #[test]
+    fn wrapper_uid_wins_over_sudo() {
+        let vars = [(SUDO_UID_VAR, "1001"), (WRAPPER_UID_VAR, "1000")];
+        assert_eq!(resolve_uid(Uid::from_raw(0), env(&vars)), 1000.into());
+        assert_eq!(resolve_uid(Uid::from_raw(0), env(&vars[..1])), 1001.into());
+    }
+
+    #[test]
+    fn invalid_or_missing_uids_fall_back_to_root() {
+        let vars = [(WRAPPER_UID_VAR, "alice")];
+        assert!(resolve_uid(Uid::from_raw(0), env(&vars)).is_root());
+        assert!(resolve_uid(Uid::from_raw(0), env(&[])).is_root());
+    }
+
+    /// A fresh directory for `name` below the system temp directory.
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("gp-gui-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir(&dir).unwrap();
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o755)).unwrap();
+        dir
+    }
+
+    #[test]
+    fn root_owned_programs_are_trusted() {
+        if !getuid().is_root() {
+            return;
+        }
+        let dir = temp_dir("root-owned");
+        let script = dir.join("hipreport.sh");
+        std::fs::write(&script, "#!/bin/sh\n").unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let result = check_root_owned(&script);
+        std::fs::remove_dir_all(&dir).unwrap();
+        result.unwrap();
+    }
+
+    #[test]
+    fn writable_or_foreign_programs_are_refused() {
+        if !getuid().is_root() {
+            return;
+        }
+        let dir = temp_dir("not-root-owned");
+        let script = dir.join("hipreport.sh");
+        std::fs::write(&script, "#!/bin/sh\n").unwrap();
+
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o775)).unwrap();
+        let group_writable = check_root_owned(&script);
+
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o777)).unwrap();
+        let writable_dir = check_root_owned(&script);
+
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o755)).unwrap();
+        std::os::unix::fs::chown(&script, Some(1000), None).unwrap();
+        let foreign = check_root_owned(&script);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(group_writable.unwrap_err().to_string().contains("writable"));
+        assert!(writable_dir.unwrap_err().to_string().contains("writable"));
+        assert!(foreign.unwrap_err().to_string().contains("uid 1000"));
+    }
+
+    #[test]
+    fn relative_program_paths_are_refused() {
+        assert!(check_root_owned(Path::new("hipreport.sh")).is_err());
+    }
+}
@@ -0,0 +1,282 @@
+//! systemd-resolved
+//!
+//! Per-link DNS configuration of systemd-resolved, read and set over its
+//! D-Bus API on the system bus. vpnc-script hands the DNS servers and domains
+//! pushed by the gateway to resolved when it is running, so the tunnel link
+//! carries them; with split DNS gp-gui sets them itself (see split_dns.rs).
+//!
+//! Domains are either search domains, appended to single-label names, or
+//! routing-only domains (`~corp.example.com`), which only send queries for
+//! names below them to the link's servers.
+
+use anyhow::{Context, Result};
+use nix::net::if_::if_nametoindex;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use zbus::zvariant::OwnedObjectPath;
+use zbus::{Connection, Proxy};
+
+const SERVICE: &str = "org.freedesktop.resolve1";
+const MANAGER_PATH: &str = "/org/freedesktop/resolve1";
+const MANAGER_INTERFACE: &str = "org.freedesktop.resolve1.Manager";
+const LINK_INTERFACE: &str = "org.freedesktop.resolve1.Link";
+
+/// Address families as used by the resolved API (Linux values)
+const AF_INET: i32 = 2;
+const AF_INET6: i32 = 10;
+
+/// Answer of `ResolveHostname`: (link index, family, address) entries, the
+/// canonical name and flags
+type ResolvedHostname = (Vec<(i32, i32, Vec<u8>)>, String, u64);
+
+/// DNS configuration of one link.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LinkDns {
+    pub servers: Vec<IpAddr>,
+    pub search_domains: Vec<String>,
+    /// Domains only used to route queries, without the leading `~`
+    pub routing_domains: Vec<String>,
+}
+
+/// Read the DNS configuration resolved holds for `interface`.
+///
+/// # Errors
+///
+/// Returns an error if the interface does not exist or resolved is not
+/// running.
+pub async fn link_dns(interface: &str) -> Result<LinkDns> {
+    let link = link(interface).await?;
+
+    let servers: Vec<(i32, Vec<u8>)> = link
+        .get_property("DNS")
+        .await
+        .context("Failed to read DNS servers from systemd-resolved")?;
+    let domains: Vec<(String, bool)> = link
+        .get_property("Domains")
+        .await
+        .context("Failed to read DNS domains from systemd-resolved")?;
+
+    let mut dns = LinkDns {
+        servers: servers
+            .into_iter()
+            .filter_map(|(family, address)| to_ip(family, &address))
+            .collect(),
+        ..LinkDns::default()
+    };
+    for (domain, routing_only) in domains {
+        if routing_only {
+            dns.routing_domains.push(domain);
+        } else {
+            dns.search_domains.push(domain);
+        }
+    }
+    Ok(dns)
+}
+
+/// Replace the DNS configuration of `interface`, like `resolvectl dns` and
+/// `resolvectl domain` together.
+///
+/// # Arguments
+///
+/// * `interface` - Link to configure
+/// * `dns` - Servers and domains; routing-only domains are only used to
+///   route queries for names below them to the link's servers
+/// * `default_route` - Whether queries for all other names may use the link
+///
+/// # Errors
+///
+/// Returns an error if the interface does not exist, resolved is not
+/// running or refuses the change.
+pub async fn set_link_dns(interface: &str, dns: &LinkDns, default_route: bool) -> Result<()> {
+    let index = index(interface)?;
+    let manager = manager().await?;
+
+    let servers = servers(dns);
+    let domains = domains(dns);
+
+    let _: () = manager
+        .call("SetLinkDNS", &(index, servers))
+        .await
+        .context("systemd-resolved refused the DNS servers")?;
+    let _: () = manager
+        .call("SetLinkDomains", &(index, domains))
+        .await
+        .context("systemd-resolved refused the DNS domains")?;
+    let _: () = manager
+        .call("SetLinkDefaultRoute", &(index, default_route))
+        .await
+        .context("systemd-resolved refused the DNS default route")?;
+    Ok(())
+}
+
+/// Servers of `dns` as passed to SetLinkDNS.
+fn servers(dns: &LinkDns) -> Vec<(i32, Vec<u8>)> {
+    dns.servers
+        .iter()
+        .map(|server| match server {
+            IpAddr::V4(ip) => (AF_INET, ip.octets().to_vec()),
+            IpAddr::V6(ip) => (AF_INET6, ip.octets().to_vec()),
+        })
+        .collect()
+}
+
+/// Domains of `dns` as passed to SetLinkDomains, flagged if routing-only.
+fn domains(dns: &LinkDns) -> Vec<(&str, bool)> {
+    dns.search_domains
+        .iter()
+        .map(|domain| (domain.as_str(), false))
+        .chain(
+            dns.routing_domains
+                .iter()
+                .map(|domain| (domain.as_str(), true)),
+        )
+        .collect()
+}
+
+/// Drop the DNS configuration set for `interface`, like `resolvectl revert`.
+///
+/// Nothing needs to be done for an interface that no longer exists, since
+/// resolved forgets removed links.
+///
+/// # Errors
+///
+/// Returns an error if resolved is not running or refuses the change.
+pub async fn revert_link(interface: &str) -> Result<()> {
+    let Ok(index) = index(interface) else {
+        return Ok(());
+    };
+    let _: () = manager()
+        .await?
+        .call("RevertLink", &(index,))
+        .await
+        .context("systemd-resolved refused to revert the link")?;
+    Ok(())
+}
+
+/// Resolve `name` through resolved, like `resolvectl query`.
+///
+/// # Returns
+///
+/// The addresses with the index of the link whose DNS servers answered,
+/// 0 for answers not from a link (e.g. `/etc/hosts`).
+///
+/// # Errors
+///
+/// Returns an error if resolved is not running or the name does not
+/// resolve.
+pub async fn resolve_hostname(name: &str) -> Result<Vec<(i32, IpAddr)>> {
+    // Any link, any address family, no flags
+    let (addresses, _canonical, _flags): ResolvedHostname = manager()
+        .await?
+        .call("ResolveHostname", &(0i32, name, 0i32, 0u64))
+        .await
+        .with_context(|| format!("Failed to resolve {}", name))?;
+
+    Ok(addresses
+        .into_iter()
+        .filter_map(|(index, family, address)| Some((index, to_ip(family, &address)?)))
+        .collect())
+}
+
+/// Whether resolved is running and answering on the system bus.
+pub async fn is_running() -> bool {
+    match manager().await {
+        Ok(manager) => manager
+            .get_property::<bool>("DNSSECSupported")
+            .await
+            .is_ok(),
+        Err(_) => false,
+    }
+}
+
+/// Proxy for the resolved link object of `interface`.
+async fn link(interface: &str) -> Result<Proxy<'static>> {
+    let index = index(interface)?;
+    let manager = manager().await?;
+    let path: OwnedObjectPath = manager
+        .call("GetLink", &(index,))
+        .await
+        .context("systemd-resolved is not available")?;
+
+    Ok(Proxy::new_owned(manager.connection().clone(), SERVICE, path, LINK_INTERFACE).await?)
+}
+
+/// Proxy for the resolved manager object.
+async fn manager() -> Result<Proxy<'static>> {
+    let connection = Connection::system()
+        .await
+        .context("Failed to connect to the system bus")?;
+    Ok(Proxy::new_owned(connection, SERVICE, MANAGER_PATH, MANAGER_INTERFACE).await?)
+}
+
+/// Interface index as used by the resolved API.
+fn index(interface: &str) -> Result<i32> {
+    let index =
+        if_nametoindex(interface).with_context(|| format!("No network interface {}", interface))?;
+    i32::try_from(index).context("Interface index out of range")
+}
+
+fn to_ip(family: i32, address: &[u8]) -> Option<IpAddr> {
+    match family {
+        AF_INET => <[u8; 4]>::try_from(address)
+            .ok()
+            .map(|octets| IpAddr::V4(Ipv4Addr::from(octets))),
+        AF_INET6 => <[u8; 16]>::try_from(address)
+            .ok()
+            .map(|octets| IpAddr::V6(Ipv6Addr::from(octets))),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_addresses_by_family() {
+        assert_eq!(
+            to_ip(AF_INET, &[10, 0, 0, 53]),
+            Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 53)))
+        );
+        let mut v6 = [0u8; 16];
+        v6[0] = 0xfd;
+        v6[15] = 0x53;
+        assert_eq!(to_ip(AF_INET6, &v6), Some("fd00::53".parse().unwrap()));
+    }
+
+    #[test]
+    fn arguments_mark_routing_domains() {
+        let dns = LinkDns {
+            servers: vec!["10.0.0.53".parse().unwrap(), "fd00::53".parse().unwrap()],
+            search_domains: vec!["corp.example.com".to_string()],
+            routing_domains: vec!["lab.example.com".to_string()],
+        };
+        let servers = servers(&dns);
+
+        assert_eq!(servers[0], (AF_INET, vec![10, 0, 0, 53]));
+        assert_eq!(servers[1].0, AF_INET6);
+        assert_eq!(
+            servers
+                .iter()
+                .map(|(family, address)| to_ip(*family, address).unwrap())
+                .collect::<Vec<_>>(),
+            dns.servers
+        );
+        assert_eq!(
+            domains(&dns),
+            [("corp.example.com", false), ("lab.example.com", true)]
+        );
+    }
+
+    #[test]
+    fn interfaces_are_looked_up_by_index() {
+        assert_eq!(index("lo").unwrap(), 1);
+        assert!(index("gp-gui-missing0").is_err());
+    }
+
+    #[test]
+    fn rejects_mismatched_addresses() {
+        assert_eq!(to_ip(AF_INET, &[10, 0, 0]), None);
+        assert_eq!(to_ip(AF_INET6, &[10, 0, 0, 53]), None);
+        assert_eq!(to_ip(7, &[10, 0, 0, 53]), None);
+    }
+}
@@ -0,0 +1,750 @@
+//! Routes
+//!
+//! Routing table queries over rtnetlink, the kernel interface `ip route`
+//! uses, spoken directly on a netlink socket:
+//!
+//! - the routes installed via the tunnel interface, which tell whether the
+//!   gateway pushed a full tunnel (a default route) or a split tunnel (only
+//!   internal subnets)
+//! - the route the kernel picks for a given address, i.e. whether traffic to
+//!   a host goes through the VPN
+//!
+//! Both only read the routing table and need no privileges. Adding and
+//! deleting routes, for the split-tunnel rules of a profile, needs root like
+//! gpclient itself.
+//!
+//! The netlink calls block. Async code runs them through [`blocking`], so a
+//! slow kernel answer cannot stall the tasks sharing the runtime's threads.
+
+use crate::dns;
+use anyhow::{Context, Result};
+use nix::net::if_::{if_indextoname, if_nametoindex};
+use nix::sys::socket::{
+    AddressFamily, MsgFlags, NetlinkAddr, SockFlag, SockProtocol, SockType, bind, recv, sendto,
+    socket,
+};
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::os::fd::{AsRawFd, OwnedFd};
+
+// Netlink message types and flags (linux/netlink.h, linux/rtnetlink.h)
+const NLMSG_ERROR: u16 = 2;
+const NLMSG_DONE: u16 = 3;
+const RTM_NEWROUTE: u16 = 24;
+const RTM_DELROUTE: u16 = 25;
+const RTM_GETROUTE: u16 = 26;
+const NLM_F_REQUEST: u16 = 0x1;
+const NLM_F_ACK: u16 = 0x4;
+const NLM_F_DUMP: u16 = 0x300;
+const NLM_F_EXCL: u16 = 0x200;
+const NLM_F_CREATE: u16 = 0x400;
+
+// Route attributes
+const RTA_DST: u16 = 1;
+const RTA_OIF: u16 = 4;
+const RTA_GATEWAY: u16 = 5;
+const RTA_PRIORITY: u16 = 6;
+const RTA_PREFSRC: u16 = 7;
+const RTA_TABLE: u16 = 15;
+
+const RTN_UNICAST: u8 = 1;
+const RTPROT_STATIC: u8 = 4;
+const RT_SCOPE_UNIVERSE: u8 = 0;
+const RT_SCOPE_LINK: u8 = 253;
+const RT_SCOPE_NOWHERE: u8 = 255;
+const RT_TABLE_LOCAL: u32 = 255;
+/// The table `ip route` uses without `table`
+pub const RT_TABLE_MAIN: u32 = 254;
+const AF_INET: u8 = 2;
+const AF_INET6: u8 = 10;
+
+const NLMSG_HEADER_LEN: usize = 16;
+const RTMSG_LEN: usize = 12;
+const RECEIVE_BUFFER_LEN: usize = 32 * 1024;
+
+/// A route of the routing table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Route {
+    pub destination: IpAddr,
+    pub prefix_len: u8,
+    pub gateway: Option<IpAddr>,
+    /// Outgoing interface, if the route names one
+    pub interface: Option<String>,
+    pub metric: Option<u32>,
+    pub table: u32,
+    /// Preferred source address, only set for looked-up routes
+    pub source: Option<IpAddr>,
+}
+
+impl Route {
+    /// Whether the route matches every address of its family
+    pub fn is_default(&self) -> bool {
+        self.prefix_len == 0
+    }
+
+    /// Whether `address` lies within the route's destination
+    pub fn contains(&self, address: IpAddr) -> bool {
+        address.is_ipv4() == self.destination.is_ipv4()
+            && network(address, self.prefix_len) == network(self.destination, self.prefix_len)
+    }
+}
+
+/// `address` with the bits after the first `prefix_len` cleared, e.g.
+/// 10.1.2.3 and 16 give 10.1.0.0.
+pub fn network(address: IpAddr, prefix_len: u8) -> IpAddr {
+    match address {
+        IpAddr::V4(ip) => {
+            let mask = u32::MAX.checked_shl(32 - u32::from(prefix_len.min(32)));
+            IpAddr::V4((u32::from(ip) & mask.unwrap_or(0)).into())
+        }
+        IpAddr::V6(ip) => {
+            let mask = u128::MAX.checked_shl(128 - u32::from(prefix_len.min(128)));
+            IpAddr::V6((u128::from(ip) & mask.unwrap_or(0)).into())
+        }
+    }
+}
+
+/// Destination in CIDR notation, e.g. "10.0.0.0/8" or "default".
+impl fmt::Display for Route {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_default() {
+            write!(f, "default")
+        } else {
+            write!(f, "{}/{}", self.destination, self.prefix_len)
+        }
+    }
+}
+
+/// How traffic is split between the tunnel and the local network.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TunnelMode {
+    /// All traffic goes through the tunnel
+    Full,
+    /// Only the routed subnets go through the tunnel
+    Split,
+}
+
+impl TunnelMode {
+    /// Classify the routes installed via the tunnel.
+    ///
+    /// vpnc-script installs a full tunnel either as a default route or as the
+    /// two halves 0.0.0.0/1 and 128.0.0.0/1, which win over the existing
+    /// default route without replacing it.
+    pub fn of(tunnel_routes: &[Route]) -> Self {
+        if tunnel_routes.iter().any(|route| route.prefix_len <= 1) {
+            TunnelMode::Full
+        } else {
+            TunnelMode::Split
+        }
+    }
+}
+
+impl fmt::Display for TunnelMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TunnelMode::Full => f.write_str("Full tunnel"),
+            TunnelMode::Split => f.write_str("Split tunnel"),
+        }
+    }
+}
+
+/// The route to one address of a looked-up host.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostRoute {
+    pub address: IpAddr,
+    pub route: Route,
+    /// Whether the route uses the tunnel interface
+    pub via_tunnel: bool,
+}
+
+/// Run blocking netlink work, such as [`list`] or [`route_to`], on a thread
+/// of its own.
+///
+/// A panic in `work` is passed on to the caller.
+pub async fn blocking<T: Send + 'static>(work: impl FnOnce() -> T + Send + 'static) -> T {
+    match tokio::task::spawn_blocking(work).await {
+        Ok(value) => value,
+        Err(e) => std::panic::resume_unwind(e.into_panic()),
+    }
+}
+
+/// List the unicast routes of all tables except the local one.
+///
+/// # Errors
+///
+/// Returns an error if the netlink request fails.
+pub fn list() -> Result<Vec<Route>> {
+    let mut routes = Vec::new();
+    for family in [AF_INET, AF_INET6] {
+        let messages = request(RTM_GETROUTE, NLM_F_DUMP, header(family, 0), &[])?;
+        routes.extend(
+            messages
+                .iter()
+                .filter_map(|message| parse_route(message))
+                .filter(|(kind, route)| *kind == RTN_UNICAST && route.table != RT_TABLE_LOCAL)
+                .map(|(_, route)| route),
+        );
+    }
+    Ok(routes)
+}
+
+/// Routes whose outgoing interface is `interface`, most specific first.
+///
+/// # Errors
+///
+/// Returns an error if the netlink request fails.
+pub fn via_interface(interface: &str) -> Result<Vec<Route>> {
+    let mut routes: Vec<Route> = list()?
+        .into_iter()
+        .filter(|route| route.interface.as_deref() == Some(interface))
+        .collect();
+    routes.sort_by_key(|route| (route.destination.is_ipv6(), u8::MAX - route.prefix_len));
+    Ok(routes)
+}
+
+/// The route the kernel uses for `address`, like `ip route get`.
+///
+/// # Errors
+///
+/// Returns an error if the address is unreachable or the request fails.
+pub fn route_to(address: IpAddr) -> Result<Route> {
+    let (family, prefix_len, octets) = match address {
+        IpAddr::V4(ip) => (AF_INET, 32, ip.octets().to_vec()),
+        IpAddr::V6(ip) => (AF_INET6, 128, ip.octets().to_vec()),
+    };
+    let messages = request(
+        RTM_GETROUTE,
+        0,
+        header(family, prefix_len),
+        &[(RTA_DST, &octets)],
+    )
+    .with_context(|| format!("No route to {}", address))?;
+
+    let (_, route) = messages
+        .iter()
+        .find_map(|message| parse_route(message))
+        .with_context(|| format!("No route to {}", address))?;
+    Ok(route)
+}
+
+/// Resolve `host` and report the route to each of its addresses.
+///
+/// # Arguments
+///
+/// * `host` - Host name or address
+/// * `tunnel` - Name of the tunnel interface
+///
+/// # Errors
+///
+/// Returns an error if the host cannot be resolved or a route lookup fails.
+pub async fn lookup_host(host: String, tunnel: String) -> Result<Vec<HostRoute>> {
+    let addresses = dns::lookup(host.trim()).await?;
+
+    blocking(move || {
+        addresses
+            .into_iter()
+            .map(|address| {
+                let route = route_to(address)?;
+                Ok(HostRoute {
+                    address,
+                    via_tunnel: route.interface.as_deref() == Some(tunnel.as_str()),
+                    route,
+                })
+            })
+            .collect()
+    })
+    .await
+}
+
+/// Install `route`, like `ip route add`.
+///
+/// Routes without a gateway are installed as directly reachable through
+/// their interface.
+///
+/// # Errors
+///
+/// Returns an error if the route already exists, its interface does not
+/// exist or the process lacks the privileges.
+pub fn add(route: &Route) -> Result<()> {
+    let scope = if route.gateway.is_some() {
+        RT_SCOPE_UNIVERSE
+    } else {
+        RT_SCOPE_LINK
+    };
+    change(
+        RTM_NEWROUTE,
+        NLM_F_ACK | NLM_F_CREATE | NLM_F_EXCL,
+        route,
+        RTPROT_STATIC,
+        scope,
+    )
+    .with_context(|| format!("Failed to add route {}", route))
+}
+
+/// Remove `route`, like `ip route del`.
+///
+/// # Errors
+///
+/// Returns an error if no such route exists or the process lacks the
+/// privileges.
+pub fn delete(route: &Route) -> Result<()> {
+    // Protocol 0 and scope "nowhere" match any route with these attributes
+    change(RTM_DELROUTE, NLM_F_ACK, route, 0, RT_SCOPE_NOWHERE)
+        .with_context(|| format!("Failed to delete route {}", route))
+}
+
+/// Send a route change and wait for the kernel's acknowledgement.
+fn change(message_type: u16, flags: u16, route: &Route, protocol: u8, scope: u8) -> Result<()> {
+    let (family, destination) = match route.destination {
+        IpAddr::V4(ip) => (AF_INET, ip.octets().to_vec()),
+        IpAddr::V6(ip) => (AF_INET6, ip.octets().to_vec()),
+    };
+
+    let mut rtmsg = header(family, route.prefix_len);
+    // Table IDs above 255 only fit in the attribute
+    rtmsg[4] = u8::try_from(route.table).unwrap_or(0);
+    rtmsg[5] = protocol;
+    rtmsg[6] = scope;
+    if message_type == RTM_NEWROUTE {
+        rtmsg[7] = RTN_UNICAST;
+    }
+
+    let table = route.table.to_ne_bytes();
+    let mut attributes: Vec<(u16, Vec<u8>)> = vec![(RTA_TABLE, table.to_vec())];
+    if route.prefix_len > 0 {
+        attributes.push((RTA_DST, destination));
+    }
+    if let Some(gateway) = route.gateway {
+        let octets = match gateway {
+            IpAddr::V4(ip) => ip.octets().to_vec(),
+            IpAddr::V6(ip) => ip.octets().to_vec(),
+        };
+        attributes.push((RTA_GATEWAY, octets));
+    }
+    if let Some(interface) = &route.interface {
+        let index = if_nametoindex(interface.as_str())
+            .with_context(|| format!("No network interface {}", interface))?;
+        attributes.push((RTA_OIF, index.to_ne_bytes().to_vec()));
+    }
+    if let Some(metric) = route.metric {
+        attributes.push((RTA_PRIORITY, metric.to_ne_bytes().to_vec()));
+    }
+
+    let attributes: Vec<(u16, &[u8])> = attributes
+        .iter()
+        .map(|(attribute, data)| (*attribute, data.as_slice()))
+        .collect();
+    request(message_type, flags, rtmsg, &attributes)?;
+    Ok(())
+}
+
+/// Route message header for `family` with the destination prefix length set.
+fn header(family: u8, dst_len: u8) -> [u8; RTMSG_LEN] {
+    let mut header = [0u8; RTMSG_LEN];
+    header[0] = family;
+    header[1] = dst_len;
+    header
+}
+
+/// Send a route request and collect the route messages of the reply.
+///
+/// Requests other than dumps return after the first reply, which is the
+/// route for RTM_GETROUTE and the acknowledgement for changes.
+fn request(
+    message_type: u16,
+    flags: u16,
+    header: [u8; RTMSG_LEN],
+    attributes: &[(u16, &[u8])],
+) -> Result<Vec<Vec<u8>>> {
+    let socket = open()?;
+    let message = encode_request(message_type, flags, header, attributes);
+
+    sendto(
+        socket.as_raw_fd(),
+        &message,
+        &NetlinkAddr::new(0, 0),
+        MsgFlags::empty(),
+    )
+    .context("Failed to send netlink request")?;
+
+    let dump = flags & NLM_F_DUMP != 0;
+    let mut routes = Vec::new();
+    let mut buffer = vec![0u8; RECEIVE_BUFFER_LEN];
+    loop {
+        let received = recv(socket.as_raw_fd(), &mut buffer, MsgFlags::empty())
+            .context("Failed to receive netlink reply")?;
+
+        if parse_reply(&buffer[..received], &mut routes)? || !dump {
+            return Ok(routes);
+        }
+    }
+}
+
+/// Build a route message with the given header and attributes.
+fn encode_request(
+    message_type: u16,
+    flags: u16,
+    header: [u8; RTMSG_LEN],
+    attributes: &[(u16, &[u8])],
+) -> Vec<u8> {
+    let mut body = header.to_vec();
+    for (attribute, data) in attributes {
+        let len = 4 + data.len();
+        body.extend_from_slice(&(len as u16).to_ne_bytes());
+        body.extend_from_slice(&attribute.to_ne_bytes());
+        body.extend_from_slice(data);
+        body.resize(body.len() + align(len) - len, 0);
+    }
+
+    let mut message = Vec::with_capacity(NLMSG_HEADER_LEN + body.len());
+    message.extend_from_slice(&((NLMSG_HEADER_LEN + body.len()) as u32).to_ne_bytes());
+    message.extend_from_slice(&message_type.to_ne_bytes());
+    message.extend_from_slice(&(NLM_F_REQUEST | flags).to_ne_bytes());
+    message.extend_from_slice(&1u32.to_ne_bytes()); // sequence number
+    message.extend_from_slice(&0u32.to_ne_bytes()); // port ID, 0 = kernel
+    message.extend_from_slice(&body);
+    message
+}
+
+/// Collect the RTM_NEWROUTE payloads of one received datagram.
+///
+/// Returns `true` once the reply is complete (NLMSG_DONE or an
+/// acknowledgement).
+fn parse_reply(data: &[u8], routes: &mut Vec<Vec<u8>>) -> Result<bool> {
+    let mut rest = data;
+    while rest.len() >= NLMSG_HEADER_LEN {
+        let len = u32::from_ne_bytes(rest[0..4].try_into()?) as usize;
+        let kind = u16::from_ne_bytes(rest[4..6].try_into()?);
+        if len < NLMSG_HEADER_LEN || len > rest.len() {
+            anyhow::bail!("Malformed netlink reply");
+        }
+        let payload = &rest[NLMSG_HEADER_LEN..len];
+
+        match kind {
+            NLMSG_DONE => return Ok(true),
+            NLMSG_ERROR => {
+                // A negative errno, or 0 for an acknowledgement
+                let errno = i32::from_ne_bytes(
+                    payload
+                        .get(0..4)
+                        .context("Malformed netlink error")?
+                        .try_into()?,
+                );
+                if errno != 0 {
+                    return Err(std::io::Error::from_raw_os_error(-errno).into());
+                }
+                return Ok(true);
+            }
+            RTM_NEWROUTE => routes.push(payload.to_vec()),
+            _ => {}
+        }
+        rest = &rest[align(len).min(rest.len())..];
+    }
+    Ok(false)
+}
+
+fn open() -> Result<OwnedFd> {
+    let socket = socket(
+        AddressFamily::Netlink,
+        SockType::Raw,
+        SockFlag::SOCK_CLOEXEC,
+        SockProtocol::NetlinkRoute,
+    )
+    .context("Failed to open netlink socket")?;
+    bind(socket.as_raw_fd(), &NetlinkAddr::new(0, 0)).context("Failed to bind netlink socket")?;
+    Ok(socket)
+}
+
+/// Parse an RTM_NEWROUTE payload into its route type and route.
+fn parse_route(payload: &[u8]) -> Option<(u8, Route)> {
+    let header = payload.get(..RTMSG_LEN)?;
+    let (family, dst_len, table, kind) = (header[0], header[1], header[4], header[7]);
+
+    let mut route = Route {
+        destination: unspecified(family)?,
+        prefix_len: dst_len,
+        gateway: None,
+        interface: None,
+        metric: None,
+        table: u32::from(table),
+        source: None,
+    };
+
+    let mut rest = &payload[RTMSG_LEN..];
+    while rest.len() >= 4 {
+        let len = usize::from(u16::from_ne_bytes([rest[0], rest[1]]));
+        let attribute = u16::from_ne_bytes([rest[2], rest[3]]);
+        if len < 4 || len > rest.len() {
+            break;
+        }
+        let data = &rest[4..len];
+
+        match attribute {
+            RTA_DST => route.destination = to_ip(family, data)?,
+            RTA_GATEWAY => route.gateway = to_ip(family, data),
+            RTA_PREFSRC => route.source = to_ip(family, data),
+            RTA_OIF => {
+                let index = u32::from_ne_bytes(data.try_into().ok()?);
+                route.interface = if_indextoname(index)
+                    .ok()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .filter(|name| !name.is_empty());
+            }
+            RTA_PRIORITY => route.metric = Some(u32::from_ne_bytes(data.try_into().ok()?)),
+            // Table IDs above 255 only fit in the attribute
+            RTA_TABLE => route.table = u32::from_ne_bytes(data.try_into().ok()?),
+            _ => {}
+        }
+        rest = &rest[align(len).min(rest.len())..];
+    }
+
+    Some((kind, route))
+}
+
+fn unspecified(family: u8) -> Option<IpAddr> {
+    match family {
+        AF_INET => Some(IpAddr::V4(Ipv4Addr::UNSPECIFIED)),
+        AF_INET6 => Some(IpAddr::V6(Ipv6Addr::UNSPECIFIED)),
+        _ => None,
+    }
+}
+
+fn to_ip(family: u8, data: &[u8]) -> Option<IpAddr> {
+    match family {
+        AF_INET => <[u8; 4]>::try_from(data).ok().map(|o| IpAddr::V4(o.into())),
+        AF_INET6 => <[u8; 16]>::try_from(data)
+            .ok()
+            .map(|o| IpAddr::V6(o.into())),
+        _ => None,
+    }
+}
+
+/// Netlink messages and attributes are padded to 4 bytes
+fn align(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `10.0.0.0/8 via 192.168.1.1 dev lo proto static metric 100`, as
+    /// received from the kernel (x86_64)
+    const IPV4_ROUTE: [u8; 52] = [
+        2, 8, 0, 0, 254, 4, 0, 1, 0, 0, 0, 0, // rtmsg
+        8, 0, 15, 0, 254, 0, 0, 0, // RTA_TABLE main
+        8, 0, 6, 0, 100, 0, 0, 0, // RTA_PRIORITY 100
+        8, 0, 1, 0, 10, 0, 0, 0, // RTA_DST
+        8, 0, 5, 0, 192, 168, 1, 1, // RTA_GATEWAY
+        8, 0, 4, 0, 1, 0, 0, 0, // RTA_OIF lo
+    ];
+
+    /// `default via fe80::1 dev lo proto ra metric 1024 pref medium`
+    const IPV6_DEFAULT_ROUTE: [u8; 64] = [
+        10, 0, 0, 0, 254, 9, 0, 1, 0, 0, 0, 0, // rtmsg
+        8, 0, 15, 0, 254, 0, 0, 0, // RTA_TABLE main
+        8, 0, 6, 0, 0, 4, 0, 0, // RTA_PRIORITY 1024
+        20, 0, 5, 0, 0xfe, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, // RTA_GATEWAY
+        8, 0, 4, 0, 1, 0, 0, 0, // RTA_OIF lo
+        5, 0, 20, 0, 0, 0, 0, 0, // RTA_PREF, padded
+    ];
+
+    /// `local 127.0.0.1 dev lo table local proto kernel src 127.0.0.1`
+    const IPV4_LOCAL_ROUTE: [u8; 44] = [
+        2, 32, 0, 0, 255, 2, 254, 2, 0, 0, 0, 0, // rtmsg
+        8, 0, 15, 0, 255, 0, 0, 0, // RTA_TABLE local
+        8, 0, 1, 0, 127, 0, 0, 1, // RTA_DST
+        8, 0, 7, 0, 127, 0, 0, 1, // RTA_PREFSRC
+        8, 0, 4, 0, 1, 0, 0, 0, // RTA_OIF lo
+    ];
+
+    fn route(destination: &str, prefix_len: u8) -> Route {
+        Route {
+            destination: destination.parse().unwrap(),
+            prefix_len,
+            gateway: None,
+            interface: Some("tun0".to_string()),
+            metric: None,
+            table: 254,
+            source: None,
+        }
+    }
+
+    /// Wrap `payload` in a netlink header of type `kind`.
+    fn message(kind: u16, payload: &[u8]) -> Vec<u8> {
+        let mut message = Vec::new();
+        message.extend_from_slice(&((NLMSG_HEADER_LEN + payload.len()) as u32).to_ne_bytes());
+        message.extend_from_slice(&kind.to_ne_bytes());
+        message.extend_from_slice(&[0; 10]);
+        message.extend_from_slice(payload);
+        message.resize(align(message.len()), 0);
+        message
+    }
+
+    #[cfg(target_endian = "little")]
+    #[test]
+    fn decodes_ipv4_route() {
+        let (kind, route) = parse_route(&IPV4_ROUTE).unwrap();
+
+        assert_eq!(kind, RTN_UNICAST);
+        assert_eq!(
+            route,
+            Route {
+                destination: "10.0.0.0".parse().unwrap(),
+                prefix_len: 8,
+                gateway: Some("192.168.1.1".parse().unwrap()),
+                interface: Some("lo".to_string()),
+                metric: Some(100),
+                table: 254,
+                source: None,
+            }
+        );
+        assert_eq!(route.to_string(), "10.0.0.0/8");
+    }
+
+    #[cfg(target_endian = "little")]
+    #[test]
+    fn decodes_ipv6_default_route() {
+        let (kind, route) = parse_route(&IPV6_DEFAULT_ROUTE).unwrap();
+
+        assert_eq!(kind, RTN_UNICAST);
+        assert_eq!(route.destination, "::".parse::<IpAddr>().unwrap());
+        assert!(route.is_default());
+        assert_eq!(route.gateway, Some("fe80::1".parse().unwrap()));
+        assert_eq!(route.metric, Some(1024));
+        assert_eq!(route.to_string(), "default");
+    }
+
+    #[cfg(target_endian = "little")]
+    #[test]
+    fn decodes_local_route_with_source() {
+        let (kind, route) = parse_route(&IPV4_LOCAL_ROUTE).unwrap();
+
+        assert_ne!(kind, RTN_UNICAST);
+        assert_eq!(route.table, RT_TABLE_LOCAL);
+        assert_eq!(route.source, Some("127.0.0.1".parse().unwrap()));
+        assert_eq!(route.to_string(), "127.0.0.1/32");
+    }
+
+    #[test]
+    fn rejects_truncated_routes() {
+        assert_eq!(parse_route(&IPV4_ROUTE[..RTMSG_LEN - 1]), None);
+
+        let mut unknown_family = IPV4_ROUTE;
+        unknown_family[0] = 7;
+        assert_eq!(parse_route(&unknown_family), None);
+
+        // A truncated attribute ends parsing but keeps what was read
+        let (_, route) = parse_route(&IPV4_ROUTE[..IPV4_ROUTE.len() - 2]).unwrap();
+        assert_eq!(route.interface, None);
+    }
+
+    #[test]
+    fn unknown_interfaces_are_unnamed() {
+        let mut payload = IPV4_ROUTE;
+        let oif = payload.len() - 4;
+        payload[oif..].copy_from_slice(&u32::MAX.to_ne_bytes());
+        assert_eq!(parse_route(&payload).unwrap().1.interface, None);
+    }
+
+    #[test]
+    fn encodes_route_lookup() {
+        let message = encode_request(
+            RTM_GETROUTE,
+            0,
+            header(AF_INET, 32),
+            &[(RTA_DST, &[10, 0, 0, 1])],
+        );
+
+        assert_eq!(message.len(), NLMSG_HEADER_LEN + RTMSG_LEN + 8);
+        assert_eq!(message[0..4], (message.len() as u32).to_ne_bytes());
+        assert_eq!(message[4..6], RTM_GETROUTE.to_ne_bytes());
+        assert_eq!(message[6..8], NLM_F_REQUEST.to_ne_bytes());
+        assert_eq!(message[16..18], [AF_INET, 32]);
+        assert_eq!(message[28..30], 8u16.to_ne_bytes());
+        assert_eq!(message[30..32], RTA_DST.to_ne_bytes());
+        assert_eq!(message[32..], [10, 0, 0, 1]);
+    }
+
+    #[test]
+    fn encodes_padded_dump_request() {
+        let message = encode_request(
+            RTM_GETROUTE,
+            NLM_F_DUMP,
+            header(AF_INET6, 0),
+            &[(RTA_TABLE, &[1, 2])],
+        );
+
+        assert_eq!(message.len() % 4, 0);
+        assert_eq!(message[6..8], (NLM_F_REQUEST | NLM_F_DUMP).to_ne_bytes());
+        assert_eq!(message[28..30], 6u16.to_ne_bytes());
+        assert_eq!(message[32..], [1, 2, 0, 0]);
+    }
+
+    #[test]
+    fn collects_routes_until_done() {
+        let mut data = message(RTM_NEWROUTE, &IPV4_ROUTE);
+        data.extend(message(99, &[0; 4]));
+        data.extend(message(RTM_NEWROUTE, &IPV6_DEFAULT_ROUTE));
+        let mut routes = Vec::new();
+
+        assert!(!parse_reply(&data, &mut routes).unwrap());
+        assert_eq!(routes, [IPV4_ROUTE.to_vec(), IPV6_DEFAULT_ROUTE.to_vec()]);
+
+        assert!(parse_reply(&message(NLMSG_DONE, &[0; 4]), &mut routes).unwrap());
+        assert_eq!(routes.len(), 2);
+    }
+
+    #[test]
+    fn reports_netlink_errors() {
+        let mut routes = Vec::new();
+        let unreachable = message(NLMSG_ERROR, &(-101i32).to_ne_bytes());
+        let error = parse_reply(&unreachable, &mut routes).unwrap_err();
+        assert_eq!(
+            error
+                .downcast_ref::<std::io::Error>()
+                .unwrap()
+                .raw_os_error(),
+            Some(101)
+        );
+
+        let ack = message(NLMSG_ERROR, &0i32.to_ne_bytes());
+        assert!(parse_reply(&ack, &mut routes).unwrap());
+
+        let mut truncated = message(RTM_NEWROUTE, &IPV4_ROUTE);
+        truncated.truncate(40);
+        assert!(parse_reply(&truncated, &mut routes).is_err());
+    }
+
+    #[test]
+    fn classifies_tunnel_mode() {
+        let split = [route("10.0.0.0", 8), route("fd00::", 64)];
+        assert_eq!(TunnelMode::of(&split), TunnelMode::Split);
+        assert_eq!(TunnelMode::of(&[]), TunnelMode::Split);
+
+        let halves = [route("0.0.0.0", 1), route("128.0.0.0", 1)];
+        assert_eq!(TunnelMode::of(&halves), TunnelMode::Full);
+        assert_eq!(TunnelMode::of(&[route("::", 0)]), TunnelMode::Full);
+    }
+
+    #[test]
+    fn looks_up_loopback_route() {
+        let route = route_to("127.0.0.1".parse().unwrap()).unwrap();
+        assert_eq!(route.interface.as_deref(), Some("lo"));
+        assert_eq!(route.prefix_len, 32);
+    }
+
+    #[tokio::test]
+    async fn blocking_work_returns_its_result() {
+        let route = blocking(|| route_to("127.0.0.1".parse().unwrap()))
+            .await
+            .unwrap();
+        assert_eq!(route.interface.as_deref(), Some("lo"));
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "netlink failed")]
+    async fn blocking_work_passes_on_panics() {
+        blocking(|| panic!("netlink failed")).await
+    }
+}
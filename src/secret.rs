@@ -0,0 +1,162 @@
+//! Secret Strings
+//!
+//! Passwords are held in [`Secret`] rather than a plain `String` from the
+//! moment they enter gp-gui (password field, keyring, password command) until
+//! they are written to gpclient's stdin.
+//!
+//! # Guarantees
+//!
+//! - The buffer is overwritten with zeros when the value is dropped
+//! - The buffer is locked into memory with `mlock` where the memlock limit
+//!   allows, so it is not written to swap
+//! - `Debug` prints `Secret(***)`, never the contents
+//! - It does not implement `Serialize`, so a secret cannot end up in a config
+//!   or log file: deriving `Serialize` on a struct holding one fails to compile
+//!
+//! A `Secret` is immutable: it is never grown in place, which would leave an
+//! unzeroed copy behind in the old allocation. Copies made outside gp-gui
+//! (e.g. by the text input widget) are beyond its reach.
+
+use std::ffi::c_void;
+use std::fmt;
+use std::ptr::NonNull;
+use std::sync::atomic::{Ordering, compiler_fence};
+
+/// A string that is zeroed on drop and never printed or serialised.
+#[derive(Default)]
+pub struct Secret(String);
+
+impl Secret {
+    /// Take ownership of `bytes`, which must be valid UTF-8.
+    ///
+    /// The bytes are wiped if they are not valid UTF-8.
+    pub fn from_utf8(bytes: Vec<u8>) -> Option<Self> {
+        match String::from_utf8(bytes) {
+            Ok(value) => Some(Self::from(value)),
+            Err(e) => {
+                wipe(&mut e.into_bytes());
+                None
+            }
+        }
+    }
+
+    /// Borrow the secret value.
+    ///
+    /// Keep the borrow short and do not copy the value into a plain `String`.
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The whole allocation, including spare capacity
+    fn buffer(&self) -> Option<(NonNull<c_void>, usize)> {
+        let capacity = self.0.capacity();
+        if capacity == 0 {
+            return None;
+        }
+        let ptr = NonNull::new(self.0.as_ptr() as *mut c_void)?;
+        Some((ptr, capacity))
+    }
+}
+
+impl From<String> for Secret {
+    fn from(value: String) -> Self {
+        let secret = Self(value);
+        if let Some((ptr, len)) = secret.buffer() {
+            // Best effort: fails silently when RLIMIT_MEMLOCK is exhausted
+            // SAFETY: the range is the live allocation owned by `secret`
+            let _ = unsafe { nix::sys::mman::mlock(ptr, len) };
+        }
+        secret
+    }
+}
+
+impl Clone for Secret {
+    fn clone(&self) -> Self {
+        Self::from(self.0.clone())
+    }
+}
+
+impl Drop for Secret {
+    fn drop(&mut self) {
+        let Some((ptr, len)) = self.buffer() else {
+            return;
+        };
+
+        // SAFETY: zeros are valid UTF-8, and the string is emptied right after
+        let bytes = unsafe { self.0.as_mut_vec() };
+        wipe(bytes);
+        // Spare capacity may hold leftovers of earlier, longer contents
+        for byte in bytes.spare_capacity_mut() {
+            // SAFETY: `byte` points into the allocation owned by this string
+            unsafe { std::ptr::write_volatile(byte.as_mut_ptr(), 0) };
+        }
+        bytes.clear();
+
+        // Locks do not nest, so this may also unlock a page shared with
+        // another secret; acceptable for a best-effort measure
+        // SAFETY: same range as locked in `from`
+        let _ = unsafe { nix::sys::mman::munlock(ptr, len) };
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Secret(***)")
+    }
+}
+
+/// Overwrite `bytes` with zeros in a way the compiler cannot optimise away.
+pub fn wipe(bytes: &mut [u8]) {
+    for byte in bytes.iter_mut() {
+        // SAFETY: `byte` is a valid, exclusive reference
+        unsafe { std::ptr::write_volatile(byte, 0) };
+    }
+    compiler_fence(Ordering::SeqCst);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn debug_is_redacted() {
+        let secret = Secret::from("hunter2".to_string());
+        assert_eq!(format!("{:?}", secret), "Secret(***)");
+        assert_eq!(format!("{:?}", Some(secret)), "Some(Secret(***))");
+    }
+
+    #[test]
+    fn from_utf8_accepts_only_valid_text() {
+        assert_eq!(
+            Secret::from_utf8(b"hunter2".to_vec()).unwrap().expose(),
+            "hunter2"
+        );
+        assert!(Secret::from_utf8(vec![0xff, 0xfe]).is_none());
+    }
+
+    #[test]
+    fn clones_are_independent() {
+        let secret = Secret::from("hunter2".to_string());
+        let copy = secret.clone();
+        drop(secret);
+        assert_eq!(copy.expose(), "hunter2");
+    }
+
+    #[test]
+    fn empty_secrets() {
+        assert!(Secret::default().is_empty());
+        assert!(Secret::from(String::new()).is_empty());
+        assert!(!Secret::from("x".to_string()).is_empty());
+    }
+
+    #[test]
+    fn wipe_zeroes_bytes() {
+        let mut bytes = *b"hunter2";
+        wipe(&mut bytes);
+        assert_eq!(bytes, [0; 7]);
+    }
+}
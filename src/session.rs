@@ -0,0 +1,334 @@
+//! Session Lifetime
+//!
+//! GlobalProtect gateways end a session after a fixed login lifetime and
+//! after a period without traffic. gpclient keeps running when that happens,
+//! retrying a session the gateway no longer accepts, so gp-gui tracks both
+//! limits itself to count down, warn ahead of time and re-authenticate once
+//! the login lifetime has run out.
+//!
+//! The limits come from the profile when configured there, otherwise from
+//! gpclient's output: openconnect logs the lifetime and idle timeout from the
+//! gateway's configuration while connecting. gpclient's output is therefore
+//! read through pipes and passed on to gp-gui's own stdout and stderr.
+//!
+//! Inactivity is measured on the tunnel interface: its counters only see the
+//! traffic the gateway counts, not keepalives, which travel outside it.
+
+use crate::config::Profile;
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+use log::info;
+use std::io::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+
+/// Limits found in gpclient's output for the current connection
+static REPORTED: Mutex<Reported> = Mutex::new(Reported {
+    expires_at: None,
+    idle_timeout: None,
+});
+
+/// Session limits reported by gpclient.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Reported {
+    /// When the login lifetime ends
+    pub expires_at: Option<DateTime<Local>>,
+    pub idle_timeout: Option<Duration>,
+}
+
+impl Reported {
+    /// Pick up a session limit from one line of gpclient's output.
+    ///
+    /// Recognised are openconnect's messages
+    ///
+    /// - `Session will expire after <n> minutes.`
+    /// - `Session authentication will expire at <ctime date>`
+    /// - `Idle timeout is <n> minutes.`
+    pub fn parse_line(&mut self, line: &str, now: DateTime<Local>) {
+        let line = line.trim();
+        if let Some(minutes) = after(line, "Session will expire after ").and_then(minutes) {
+            self.expires_at = Some(now + minutes);
+        } else if let Some(date) = after(line, "Session authentication will expire at ") {
+            // ctime format, e.g. "Thu Oct  8 17:30:00 2026"; the weekday is redundant
+            let date = date
+                .split_whitespace()
+                .skip(1)
+                .collect::<Vec<_>>()
+                .join(" ");
+            if let Some(expires_at) = NaiveDateTime::parse_from_str(&date, "%b %d %H:%M:%S %Y")
+                .ok()
+                .and_then(|date| Local.from_local_datetime(&date).earliest())
+            {
+                self.expires_at = Some(expires_at);
+            }
+        } else if let Some(minutes) = after(line, "Idle timeout is ").and_then(minutes) {
+            self.idle_timeout = Some(minutes);
+        }
+    }
+}
+
+/// Forget the limits of the previous connection.
+pub fn reset() {
+    *REPORTED.lock().unwrap_or_else(|e| e.into_inner()) = Reported::default();
+}
+
+/// Limits reported for the current connection so far.
+pub fn reported() -> Reported {
+    *REPORTED.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Pass gpclient's output on to gp-gui's stdout or stderr, picking up the
+/// session limits on the way.
+///
+/// # Arguments
+///
+/// * `output` - gpclient's stdout or stderr pipe
+/// * `stderr` - Whether to pass it on to stderr instead of stdout
+pub async fn watch_output(output: impl AsyncRead + Unpin, stderr: bool) {
+    let mut reader = BufReader::new(output);
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        match reader.read_until(b'\n', &mut line).await {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+
+        let _ = if stderr {
+            std::io::stderr().write_all(&line)
+        } else {
+            std::io::stdout().write_all(&line)
+        };
+
+        let text = String::from_utf8_lossy(&line);
+        let mut reported = REPORTED.lock().unwrap_or_else(|e| e.into_inner());
+        let before = *reported;
+        reported.parse_line(&text, Local::now());
+        if *reported != before {
+            info!("Session limits reported by gpclient: {:?}", *reported);
+        }
+    }
+}
+
+/// Which limit ends the session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    Lifetime,
+    Idle,
+}
+
+/// Session limits of the current connection.
+#[derive(Debug, Clone)]
+pub struct Session {
+    /// When the login lifetime ends, if known
+    pub expires_at: Option<DateTime<Local>>,
+    pub idle_timeout: Option<Duration>,
+    /// Last time traffic went through the tunnel
+    pub last_activity: DateTime<Local>,
+    /// How long before the end to warn
+    pub warning: Duration,
+    /// Whether the limits are configured in the profile rather than reported
+    lifetime_configured: bool,
+    idle_configured: bool,
+    /// End of the session that was warned about
+    warned_for: Option<DateTime<Local>>,
+}
+
+impl Session {
+    /// Start tracking a session that connected at `connected_at`.
+    pub fn new(profile: &Profile, connected_at: DateTime<Local>) -> Self {
+        let minutes = |minutes: u32| Duration::from_secs(u64::from(minutes) * 60);
+        let mut session = Self {
+            expires_at: profile
+                .session_lifetime_minutes
+                .map(|lifetime| connected_at + minutes(lifetime)),
+            idle_timeout: profile.idle_timeout_minutes.map(minutes),
+            last_activity: connected_at,
+            warning: minutes(profile.session_warning_minutes),
+            lifetime_configured: profile.session_lifetime_minutes.is_some(),
+            idle_configured: profile.idle_timeout_minutes.is_some(),
+            warned_for: None,
+        };
+        session.update(reported());
+        session
+    }
+
+    /// Adopt the limits gpclient reported, unless configured in the profile.
+    pub fn update(&mut self, reported: Reported) {
+        if !self.lifetime_configured && reported.expires_at.is_some() {
+            self.expires_at = reported.expires_at;
+        }
+        if !self.idle_configured && reported.idle_timeout.is_some() {
+            self.idle_timeout = reported.idle_timeout;
+        }
+    }
+
+    /// Note traffic through the tunnel at `now`.
+    pub fn activity(&mut self, now: DateTime<Local>) {
+        self.last_activity = now;
+    }
+
+    /// When and why the session ends first, if any limit is known.
+    pub fn end(&self) -> Option<(DateTime<Local>, Limit)> {
+        let lifetime = self.expires_at.map(|at| (at, Limit::Lifetime));
+        let idle = self
+            .idle_timeout
+            .map(|timeout| (self.last_activity + timeout, Limit::Idle));
+        match (lifetime, idle) {
+            (Some(lifetime), Some(idle)) => Some(if idle.0 < lifetime.0 { idle } else { lifetime }),
+            (lifetime, idle) => lifetime.or(idle),
+        }
+    }
+
+    /// Whether the login lifetime has run out at `now`.
+    pub fn lifetime_ended(&self, now: DateTime<Local>) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+
+    /// Whether to warn now that the session is about to end.
+    ///
+    /// Returns the end of the session once per end; an idle timeout pushed
+    /// back by traffic counts as a new end.
+    pub fn due_warning(&mut self, now: DateTime<Local>) -> Option<(DateTime<Local>, Limit)> {
+        let (end, limit) = self.end()?;
+        // Negative once the session has ended
+        let remaining = (end - now).to_std().ok().filter(|r| !r.is_zero())?;
+        if remaining > self.warning || self.warned_for == Some(end) {
+            return None;
+        }
+        self.warned_for = Some(end);
+        Some((end, limit))
+    }
+}
+
+/// The rest of `line` after `prefix`, which may follow a log prefix such as
+/// a timestamp.
+fn after<'a>(line: &'a str, prefix: &str) -> Option<&'a str> {
+    line.find(prefix).map(|start| &line[start + prefix.len()..])
+}
+
+/// Parse `<n> minutes` at the start of `text`.
+fn minutes(text: &str) -> Option<Duration> {
+    let minutes: u64 = text.split_whitespace().next()?.parse().ok()?;
+    Some(Duration::from_secs(minutes * 60))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(hour: u32, minute: u32) -> DateTime<Local> {
+        Local
+            .with_ymd_and_hms(2026, 10, 8, hour, minute, 0)
+            .unwrap()
+    }
+
+    fn profile(lifetime: Option<u32>, idle: Option<u32>) -> Profile {
+        let mut profile = Profile::new("Work".to_string(), "vpn.example.com".to_string());
+        profile.session_lifetime_minutes = lifetime;
+        profile.idle_timeout_minutes = idle;
+        profile.session_warning_minutes = 10;
+        profile
+    }
+
+    #[test]
+    fn parses_openconnect_messages() {
+        let now = at(9, 0);
+        let mut reported = Reported::default();
+
+        reported.parse_line("Session will expire after 480 minutes.", now);
+        assert_eq!(reported.expires_at, Some(at(17, 0)));
+
+        reported.parse_line(
+            "[2026-10-08 09:00:01] Session authentication will expire at Thu Oct  8 17:30:00 2026\n",
+            now,
+        );
+        assert_eq!(reported.expires_at, Some(at(17, 30)));
+
+        reported.parse_line("Idle timeout is 60 minutes.", now);
+        assert_eq!(reported.idle_timeout, Some(Duration::from_secs(3600)));
+    }
+
+    #[test]
+    fn ignores_unrelated_and_malformed_lines() {
+        let mut reported = Reported::default();
+        reported.parse_line("Connected as 10.0.0.2", at(9, 0));
+        reported.parse_line("Session will expire after many minutes.", at(9, 0));
+        reported.parse_line("Session authentication will expire at soon", at(9, 0));
+        assert_eq!(reported, Reported::default());
+    }
+
+    #[test]
+    fn configured_limits_win_over_reported_ones() {
+        let mut session = Session::new(&profile(Some(60), None), at(9, 0));
+        session.update(Reported {
+            expires_at: Some(at(17, 0)),
+            idle_timeout: Some(Duration::from_secs(1800)),
+        });
+
+        assert_eq!(session.expires_at, Some(at(10, 0)));
+        assert_eq!(session.idle_timeout, Some(Duration::from_secs(1800)));
+    }
+
+    #[test]
+    fn earliest_limit_ends_the_session() {
+        let mut session = Session::new(&profile(Some(480), Some(30)), at(9, 0));
+        assert_eq!(session.end(), Some((at(9, 30), Limit::Idle)));
+
+        session.activity(at(16, 50));
+        assert_eq!(session.end(), Some((at(17, 0), Limit::Lifetime)));
+
+        let session = Session::new(&profile(None, None), at(9, 0));
+        assert_eq!(session.end(), None);
+    }
+
+    #[test]
+    fn warns_once_per_end() {
+        let mut session = Session::new(&profile(Some(60), None), at(9, 0));
+
+        assert_eq!(session.due_warning(at(9, 45)), None);
+        assert_eq!(
+            session.due_warning(at(9, 51)),
+            Some((at(10, 0), Limit::Lifetime))
+        );
+        assert_eq!(session.due_warning(at(9, 55)), None);
+        // Not after the end
+        let mut session = Session::new(&profile(Some(60), None), at(9, 0));
+        assert_eq!(session.due_warning(at(10, 0)), None);
+    }
+
+    #[test]
+    fn limits_may_follow_a_log_prefix() {
+        let line = "[09:00:00] Idle timeout is 30 minutes.";
+        assert_eq!(after(line, "Idle timeout is "), Some("30 minutes."));
+        assert_eq!(after(line, "Session will expire after "), None);
+        assert_eq!(minutes("30 minutes."), Some(Duration::from_secs(1800)));
+        assert_eq!(minutes("-5 minutes."), None);
+        assert_eq!(minutes(""), None);
+    }
+
+    #[test]
+    fn idle_end_moves_with_activity() {
+        let mut session = Session::new(&profile(None, Some(30)), at(9, 0));
+        assert_eq!(session.end(), Some((at(9, 30), Limit::Idle)));
+
+        assert!(session.due_warning(at(9, 25)).is_some());
+        session.activity(at(9, 26));
+        assert_eq!(session.end(), Some((at(9, 56), Limit::Idle)));
+        // A new end is warned about again
+        assert_eq!(
+            session.due_warning(at(9, 50)),
+            Some((at(9, 56), Limit::Idle))
+        );
+    }
+
+    #[test]
+    fn lifetime_ends_at_expiry() {
+        let session = Session::new(&profile(Some(60), Some(5)), at(9, 0));
+
+        // The idle timeout is only an estimate and does not end the lifetime
+        assert!(!session.lifetime_ended(at(9, 30)));
+        assert!(session.lifetime_ended(at(10, 0)));
+        assert!(!Session::new(&profile(None, None), at(9, 0)).lifetime_ended(at(23, 0)));
+    }
+}
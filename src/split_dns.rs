@@ -0,0 +1,367 @@
+//! Split DNS
+//!
+//! vpnc-script applies the DNS servers and domains pushed by the gateway
+//! itself, and without systemd-resolved integration it does so by rewriting
+//! `/etc/resolv.conf`, which replaces any local resolver (e.g. one serving
+//! containers). For profiles with split DNS, gpclient runs a small wrapper
+//! around vpnc-script instead, which records the DNS settings for gp-gui and
+//! hides them from vpnc-script. gp-gui then sets them on the tunnel link in
+//! systemd-resolved:
+//!
+//! - the gateway's default domains become search domains
+//! - its split-DNS domains become routing-only domains, so only names below
+//!   them are sent to the VPN's servers
+//! - with a full tunnel the link is also the default route for other names
+//!
+//! The wrapper records the settings again when openconnect reconnects, and
+//! gp-gui re-applies them when the recording changes. resolved forgets the
+//! settings when the tunnel interface goes away; gp-gui reverts them on
+//! disconnect as well.
+
+use crate::privileges;
+use crate::resolved::{self, LinkDns};
+use crate::routes::{self, TunnelMode};
+use anyhow::{Context, Result};
+use log::{info, warn};
+use std::fs;
+use std::net::IpAddr;
+use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Directory for the wrapper script and the settings it records
+const RUNTIME_DIR: &str = "/run/gp-gui";
+const SCRIPT_NAME: &str = "vpnc-script";
+const SETTINGS_NAME: &str = "vpnc-dns.env";
+
+/// Where vpnc-script is commonly installed, if openconnect does not say
+const VPNC_SCRIPT_PATHS: [&str; 4] = [
+    "/etc/vpnc/vpnc-script",
+    "/usr/share/vpnc-scripts/vpnc-script",
+    "/usr/libexec/vpnc-scripts/vpnc-script",
+    "/usr/local/sbin/vpnc-script",
+];
+
+/// vpnc-script variables carrying the DNS settings
+const DNS_VARIABLES: [&str; 4] = [
+    "INTERNAL_IP4_DNS",
+    "INTERNAL_IP6_DNS",
+    "CISCO_DEF_DOMAIN",
+    "CISCO_SPLIT_DNS",
+];
+
+/// DNS settings pushed by the gateway.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PushedDns {
+    pub servers: Vec<IpAddr>,
+    /// Domains appended to single-label names
+    pub default_domains: Vec<String>,
+    /// Internal zones whose names are resolved by the VPN's servers
+    pub split_domains: Vec<String>,
+}
+
+impl PushedDns {
+    /// Parse the `NAME=value` lines recorded by the wrapper script.
+    pub fn parse(content: &str) -> Self {
+        let mut dns = Self::default();
+        for line in content.lines() {
+            let Some((name, value)) = line.split_once('=') else {
+                continue;
+            };
+            // Lists are space-separated, except CISCO_SPLIT_DNS which uses
+            // commas; accept both everywhere
+            let values = value
+                .split([' ', ','])
+                .filter(|v| !v.is_empty())
+                .map(str::to_string);
+            match name {
+                "INTERNAL_IP4_DNS" | "INTERNAL_IP6_DNS" => {
+                    dns.servers
+                        .extend(values.filter_map(|v| v.parse::<IpAddr>().ok()));
+                }
+                "CISCO_DEF_DOMAIN" => dns.default_domains.extend(values),
+                "CISCO_SPLIT_DNS" => dns.split_domains.extend(values),
+                _ => {}
+            }
+        }
+        dns
+    }
+
+    /// Link configuration for resolved.
+    fn link_dns(&self) -> LinkDns {
+        LinkDns {
+            servers: self.servers.clone(),
+            search_domains: self.default_domains.clone(),
+            // Search domains route their queries to the link as well
+            routing_domains: self
+                .split_domains
+                .iter()
+                .filter(|domain| !self.default_domains.contains(domain))
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+/// Install the vpnc-script wrapper for a connection with split DNS.
+///
+/// # Returns
+///
+/// The path of the wrapper, to pass to gpclient as its vpnc-script.
+///
+/// # Errors
+///
+/// Returns an error if systemd-resolved is not running, vpnc-script cannot
+/// be found or the wrapper cannot be written.
+pub async fn prepare() -> Result<String> {
+    if !resolved::is_running().await {
+        anyhow::bail!("systemd-resolved is not running; turn off split DNS for this profile");
+    }
+    let vpnc_script = find_vpnc_script().context("Could not find vpnc-script")?;
+    // The wrapper runs it as root
+    privileges::check_root_owned(Path::new(&vpnc_script))
+        .with_context(|| format!("Refusing to run {}", vpnc_script))?;
+
+    fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(RUNTIME_DIR)
+        .with_context(|| format!("Failed to create {}", RUNTIME_DIR))?;
+
+    // Settings of an earlier connection must not be mistaken for new ones
+    let settings = settings_path();
+    if let Err(e) = fs::remove_file(&settings)
+        && e.kind() != std::io::ErrorKind::NotFound
+    {
+        return Err(e).with_context(|| format!("Failed to remove {}", settings.display()));
+    }
+
+    let script = Path::new(RUNTIME_DIR).join(SCRIPT_NAME);
+    fs::write(&script, wrapper_script(&vpnc_script, &settings))
+        .with_context(|| format!("Failed to write {}", script.display()))?;
+    fs::set_permissions(&script, fs::Permissions::from_mode(0o700))
+        .with_context(|| format!("Failed to make {} executable", script.display()))?;
+
+    info!("Split DNS: wrapping {}", vpnc_script);
+    Ok(script.to_string_lossy().into_owned())
+}
+
+/// Set the recorded DNS settings on the tunnel link in systemd-resolved.
+///
+/// # Errors
+///
+/// Returns an error if the wrapper recorded no settings or resolved refuses
+/// them.
+pub async fn apply(interface: String) -> Result<PushedDns> {
+    let pushed = recorded().with_context(|| {
+        format!(
+            "gpclient did not record DNS settings in {}",
+            settings_path().display()
+        )
+    })?;
+    if pushed.servers.is_empty() {
+        anyhow::bail!("The gateway did not push any DNS servers");
+    }
+
+    let tunnel = interface.clone();
+    let full_tunnel = routes::blocking(move || routes::via_interface(&tunnel))
+        .await
+        .is_ok_and(|routes| TunnelMode::of(&routes) == TunnelMode::Full);
+    let link = pushed.link_dns();
+    resolved::set_link_dns(&interface, &link, full_tunnel).await?;
+
+    info!(
+        "Split DNS on {}: servers {:?}, search {:?}, routing {:?}, default route {}",
+        interface, link.servers, link.search_domains, link.routing_domains, full_tunnel
+    );
+    Ok(pushed)
+}
+
+/// DNS settings recorded by the wrapper for the current connection, if it
+/// uses split DNS.
+pub fn recorded() -> Option<PushedDns> {
+    let content = fs::read_to_string(settings_path()).ok()?;
+    Some(PushedDns::parse(&content))
+}
+
+/// When the wrapper last recorded DNS settings, if it has for this
+/// connection.
+pub fn recorded_at() -> Option<SystemTime> {
+    fs::metadata(settings_path()).ok()?.modified().ok()
+}
+
+/// Revert the DNS settings of the tunnel link and forget the recorded ones.
+pub async fn revert(interface: String) {
+    if let Err(e) = resolved::revert_link(&interface).await {
+        warn!("Failed to revert split DNS on {}: {:#}", interface, e);
+    }
+    let _ = fs::remove_file(settings_path());
+}
+
+fn settings_path() -> PathBuf {
+    Path::new(RUNTIME_DIR).join(SETTINGS_NAME)
+}
+
+/// Shell script recording the DNS settings in `settings` and running
+/// `vpnc_script` without them.
+fn wrapper_script(vpnc_script: &str, settings: &Path) -> String {
+    let settings = quote(&settings.to_string_lossy());
+
+    let mut lines = vec![
+        "#!/bin/sh".to_string(),
+        "# Written by gp-gui: records the DNS settings pushed by the gateway for".to_string(),
+        "# systemd-resolved and hides them so vpnc-script leaves resolv.conf alone".to_string(),
+        "case \"$reason\" in".to_string(),
+        "connect|reconnect)".to_string(),
+        "    {".to_string(),
+    ];
+    lines.extend(
+        DNS_VARIABLES
+            .iter()
+            .map(|name| format!("        echo \"{name}=${name}\"")),
+    );
+    lines.extend([
+        format!("    }} > {settings}.tmp && mv {settings}.tmp {settings}"),
+        "    ;;".to_string(),
+        "esac".to_string(),
+        format!("unset {}", DNS_VARIABLES.join(" ")),
+        format!("exec {} \"$@\"", quote(vpnc_script)),
+    ]);
+    lines.join("\n") + "\n"
+}
+
+/// Quote `value` for a POSIX shell.
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// The vpnc-script openconnect uses by default.
+///
+/// openconnect names it in its `--help` output, below the `--script`
+/// option; otherwise the usual install locations are tried.
+fn find_vpnc_script() -> Option<String> {
+    let help = std::process::Command::new("openconnect")
+        .arg("--help")
+        .output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).into_owned())
+        .unwrap_or_default();
+
+    let mut lines = help.lines();
+    let from_help = lines
+        .by_ref()
+        .find(|line| line.contains("--script"))
+        .and_then(|_| lines.next())
+        .and_then(|line| line.split('"').nth(1))
+        .map(str::to_string);
+
+    from_help
+        .into_iter()
+        .chain(VPNC_SCRIPT_PATHS.iter().map(|path| path.to_string()))
+        .find(|path| Path::new(path).is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_recorded_settings() {
+        let content = "INTERNAL_IP4_DNS=10.0.0.53 10.0.1.53\n\
+                       INTERNAL_IP6_DNS=fd00::53\n\
+                       CISCO_DEF_DOMAIN=corp.example.com\n\
+                       CISCO_SPLIT_DNS=corp.example.com,lab.example.com\n";
+        let dns = PushedDns::parse(content);
+
+        assert_eq!(
+            dns.servers,
+            ["10.0.0.53", "10.0.1.53", "fd00::53"]
+                .map(|ip| ip.parse::<IpAddr>().unwrap())
+                .to_vec()
+        );
+        assert_eq!(dns.default_domains, ["corp.example.com"]);
+        assert_eq!(dns.split_domains, ["corp.example.com", "lab.example.com"]);
+    }
+
+    #[test]
+    fn ignores_empty_and_invalid_values() {
+        let dns =
+            PushedDns::parse("INTERNAL_IP4_DNS=\nINTERNAL_IP6_DNS=not-an-ip\ngarbage\nOTHER=x\n");
+        assert_eq!(dns, PushedDns::default());
+    }
+
+    #[test]
+    fn split_domains_become_routing_domains() {
+        let pushed = PushedDns {
+            servers: vec!["10.0.0.53".parse().unwrap()],
+            default_domains: vec!["corp.example.com".to_string()],
+            split_domains: vec![
+                "corp.example.com".to_string(),
+                "lab.example.com".to_string(),
+            ],
+        };
+
+        assert_eq!(
+            pushed.link_dns(),
+            LinkDns {
+                servers: pushed.servers.clone(),
+                search_domains: vec!["corp.example.com".to_string()],
+                routing_domains: vec!["lab.example.com".to_string()],
+            }
+        );
+    }
+
+    #[test]
+    fn quotes_for_the_shell() {
+        assert_eq!(quote("/run/gp-gui"), "'/run/gp-gui'");
+        assert_eq!(quote("it's"), r"'it'\''s'");
+    }
+
+    /// Run the wrapper with `reason`, returning the recorded settings and
+    /// what the wrapped script saw of them.
+    fn run_wrapper(reason: &str, dir: &Path) -> (Option<PushedDns>, String) {
+        let settings = dir.join(SETTINGS_NAME);
+        let script = dir.join(SCRIPT_NAME);
+        // Run through sh, as a script written just now may not be executable
+        // yet while other tests fork
+        let vpnc_script = dir.join("fake-vpnc-script");
+        fs::write(
+            &vpnc_script,
+            "echo \"${INTERNAL_IP4_DNS-unset} ${CISCO_SPLIT_DNS-unset}\"\n",
+        )
+        .unwrap();
+        fs::write(&script, wrapper_script("/bin/sh", &settings)).unwrap();
+        let output = std::process::Command::new("sh")
+            .arg(&script)
+            .arg(&vpnc_script)
+            .env("reason", reason)
+            .env("INTERNAL_IP4_DNS", "10.0.0.53")
+            .env("CISCO_SPLIT_DNS", "corp.example.com")
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let seen = String::from_utf8(output.stdout).unwrap();
+        let Ok(content) = fs::read_to_string(&settings) else {
+            return (None, seen);
+        };
+        fs::remove_file(&settings).unwrap();
+        (Some(PushedDns::parse(&content)), seen)
+    }
+
+    #[test]
+    fn wrapper_records_and_hides_settings_on_connect_and_reconnect() {
+        let dir = std::env::temp_dir().join(format!("gp-gui-split-dns-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        for reason in ["connect", "reconnect"] {
+            let (dns, seen) = run_wrapper(reason, &dir);
+            let dns = dns.expect(reason);
+            assert_eq!(dns.servers, ["10.0.0.53".parse::<IpAddr>().unwrap()]);
+            assert_eq!(dns.split_domains, ["corp.example.com"]);
+            assert_eq!(seen, "unset unset\n");
+        }
+
+        assert_eq!(run_wrapper("disconnect", &dir).0, None);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,401 @@
+//! Split-Tunnel Rules
+//!
+//! Per-profile corrections to the routes the gateway pushes: extra subnets
+//! and hosts sent through the tunnel, and local subnets kept off it, such as
+//! lab networks that a corporate full tunnel would otherwise swallow.
+//!
+//! The rules are applied as routes in the main table once the tunnel
+//! interface is up. Include routes point at the tunnel interface and vanish
+//! with it. Exclude routes copy the gateway and interface of the route that
+//! covered the subnet outside the tunnel, so they would outlive it; every
+//! route added is therefore remembered and removed again on disconnect and
+//! on exit.
+
+use crate::dns;
+use crate::routes::{self, Route};
+use anyhow::{Context, Result};
+use log::{info, warn};
+use nix::errno::Errno;
+use nix::net::if_::if_nametoindex;
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::Mutex;
+
+/// Routes added for the current connection, removed by [`revert`]
+static APPLIED: Mutex<Vec<Route>> = Mutex::new(Vec::new());
+
+/// Destination of a split-tunnel rule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    /// A subnet in CIDR notation; a plain address is a single host
+    Subnet { network: IpAddr, prefix_len: u8 },
+    /// A host name, resolved when the rule is applied
+    Host(String),
+}
+
+impl Target {
+    /// Parse a subnet (`10.20.0.0/16`, `fd00::/8`), address or host name.
+    ///
+    /// Host bits of a subnet are cleared, so `10.20.1.1/16` becomes
+    /// `10.20.0.0/16`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the value is neither a valid subnet nor a valid
+    /// host name.
+    pub fn parse(value: &str) -> Result<Self> {
+        let value = value.trim();
+
+        if let Some((address, prefix_len)) = value.split_once('/') {
+            let address: IpAddr = address
+                .parse()
+                .with_context(|| format!("Invalid subnet address in {}", value))?;
+            let prefix_len = prefix_len
+                .parse::<u8>()
+                .ok()
+                .filter(|len| *len <= host_prefix_len(address))
+                .with_context(|| format!("Invalid prefix length in {}", value))?;
+            return Ok(Target::Subnet {
+                network: routes::network(address, prefix_len),
+                prefix_len,
+            });
+        }
+
+        if let Ok(address) = value.parse::<IpAddr>() {
+            return Ok(Target::Subnet {
+                network: address,
+                prefix_len: host_prefix_len(address),
+            });
+        }
+
+        let valid_host = !value.is_empty()
+            && value.split('.').all(|label| {
+                !label.is_empty()
+                    && !label.starts_with('-')
+                    && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+            });
+        if !valid_host {
+            anyhow::bail!("{} is neither a subnet nor a host name", value);
+        }
+        Ok(Target::Host(value.to_ascii_lowercase()))
+    }
+}
+
+/// Subnets in CIDR notation, hosts by name.
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Target::Subnet {
+                network,
+                prefix_len,
+            } => write!(f, "{}/{}", network, prefix_len),
+            Target::Host(host) => f.write_str(host),
+        }
+    }
+}
+
+/// Parse a comma-separated list of rules as entered in the profile editor.
+///
+/// # Arguments
+///
+/// * `text` - Rules separated by commas or whitespace
+/// * `hosts_allowed` - Whether host names are accepted besides subnets
+///
+/// # Errors
+///
+/// Returns an error describing the first invalid rule.
+pub fn parse_list(text: &str, hosts_allowed: bool) -> Result<Vec<Target>> {
+    let mut targets = Vec::new();
+    for value in text.split([',', ' ', '\t', '\n']).filter(|v| !v.is_empty()) {
+        let target = Target::parse(value)?;
+        if !hosts_allowed && matches!(target, Target::Host(_)) {
+            anyhow::bail!("{} is not a subnet", value);
+        }
+        if !targets.contains(&target) {
+            targets.push(target);
+        }
+    }
+    Ok(targets)
+}
+
+/// Outcome of applying the rules of a profile.
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    /// Descriptions of the routes added
+    pub applied: Vec<String>,
+    /// Rules that could not be applied
+    pub errors: Vec<String>,
+}
+
+/// Add the routes for the include and exclude rules of a profile.
+///
+/// A rule that fails is reported and skipped; the others are still applied.
+///
+/// # Arguments
+///
+/// * `include` - Subnets and host names to route through the tunnel
+/// * `exclude` - Subnets to keep on the local network
+/// * `interface` - Name of the tunnel interface
+pub async fn apply(include: Vec<String>, exclude: Vec<String>, interface: String) -> Report {
+    let mut report = Report::default();
+
+    for value in &include {
+        let targets = match Target::parse(value) {
+            Ok(Target::Host(host)) => match dns::lookup(&host).await {
+                Ok(addresses) => addresses
+                    .into_iter()
+                    .map(|address| (address, host_prefix_len(address), Some(host.clone())))
+                    .collect(),
+                Err(e) => {
+                    report.errors.push(format!("{:#}", e));
+                    continue;
+                }
+            },
+            Ok(Target::Subnet {
+                network,
+                prefix_len,
+            }) => vec![(network, prefix_len, None)],
+            Err(e) => {
+                report.errors.push(format!("{:#}", e));
+                continue;
+            }
+        };
+
+        for (destination, prefix_len, host) in targets {
+            let route = Route {
+                destination,
+                prefix_len,
+                gateway: None,
+                interface: Some(interface.clone()),
+                metric: None,
+                table: routes::RT_TABLE_MAIN,
+                source: None,
+            };
+            let description = match host {
+                Some(host) => format!("{} ({}) via {}", route, host, interface),
+                None => format!("{} via {}", route, interface),
+            };
+            add(route, description, &mut report).await;
+        }
+    }
+
+    if exclude.is_empty() {
+        return report;
+    }
+    // The routes outside the tunnel, before any exclude route is added
+    let local: Vec<Route> = match routes::blocking(routes::list).await {
+        Ok(all) => all
+            .into_iter()
+            .filter(|route| {
+                route.table == routes::RT_TABLE_MAIN
+                    && route
+                        .interface
+                        .as_ref()
+                        .is_some_and(|name| *name != interface)
+            })
+            .collect(),
+        Err(e) => {
+            report.errors.push(format!("{:#}", e));
+            return report;
+        }
+    };
+
+    for value in &exclude {
+        let (network, prefix_len) = match Target::parse(value) {
+            Ok(Target::Subnet {
+                network,
+                prefix_len,
+            }) => (network, prefix_len),
+            Ok(Target::Host(host)) => {
+                report.errors.push(format!("{} is not a subnet", host));
+                continue;
+            }
+            Err(e) => {
+                report.errors.push(format!("{:#}", e));
+                continue;
+            }
+        };
+
+        // The most specific local route covering the whole subnet
+        let Some(covering) = local
+            .iter()
+            .filter(|route| route.prefix_len <= prefix_len && route.contains(network))
+            .max_by_key(|route| route.prefix_len)
+        else {
+            report.errors.push(format!(
+                "No route outside the VPN for {}/{}",
+                network, prefix_len
+            ));
+            continue;
+        };
+        let device = covering.interface.as_deref().unwrap_or_default();
+        let via = match covering.gateway {
+            Some(gateway) => format!("{} dev {}", gateway, device),
+            None => device.to_string(),
+        };
+
+        if covering.prefix_len == prefix_len {
+            info!("{}/{} is already routed via {}", network, prefix_len, via);
+            report
+                .applied
+                .push(format!("{}/{} already via {}", network, prefix_len, via));
+            continue;
+        }
+
+        let route = Route {
+            destination: network,
+            prefix_len,
+            gateway: covering.gateway,
+            interface: covering.interface.clone(),
+            metric: None,
+            table: routes::RT_TABLE_MAIN,
+            source: None,
+        };
+        let description = format!("{} via {}", route, via);
+        add(route, description, &mut report).await;
+    }
+
+    report
+}
+
+/// Remove the routes added by [`apply`].
+///
+/// Routes that are already gone, such as include routes removed together
+/// with the tunnel interface, are skipped silently.
+pub fn revert() {
+    let applied = std::mem::take(&mut *APPLIED.lock().unwrap_or_else(|e| e.into_inner()));
+    for route in applied {
+        // The kernel removes the routes of an interface together with it
+        if let Some(interface) = &route.interface
+            && if_nametoindex(interface.as_str()).is_err()
+        {
+            continue;
+        }
+        match routes::delete(&route) {
+            Ok(()) => info!("Removed split-tunnel route {}", route),
+            Err(e) if is_missing(&e) => {}
+            Err(e) => warn!("{:#}", e),
+        }
+    }
+}
+
+async fn add(route: Route, description: String, report: &mut Report) {
+    let added = route.clone();
+    match routes::blocking(move || routes::add(&added)).await {
+        Ok(()) => {
+            info!("Added split-tunnel route {}", description);
+            APPLIED
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .push(route);
+            report.applied.push(description);
+        }
+        Err(e) => report.errors.push(format!("{:#}", e)),
+    }
+}
+
+/// Whether a route change failed because the route does not exist
+fn is_missing(error: &anyhow::Error) -> bool {
+    error
+        .root_cause()
+        .downcast_ref::<std::io::Error>()
+        .and_then(std::io::Error::raw_os_error)
+        == Some(Errno::ESRCH as i32)
+}
+
+fn host_prefix_len(address: IpAddr) -> u8 {
+    if address.is_ipv4() { 32 } else { 128 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn subnet(network: &str, prefix_len: u8) -> Target {
+        Target::Subnet {
+            network: network.parse().unwrap(),
+            prefix_len,
+        }
+    }
+
+    #[test]
+    fn parses_subnets_and_clears_host_bits() {
+        assert_eq!(
+            Target::parse("10.20.0.0/16").unwrap(),
+            subnet("10.20.0.0", 16)
+        );
+        assert_eq!(
+            Target::parse(" 10.20.1.1/16 ").unwrap(),
+            subnet("10.20.0.0", 16)
+        );
+        assert_eq!(Target::parse("fd00::1/8").unwrap(), subnet("fd00::", 8));
+        assert_eq!(Target::parse("0.0.0.0/0").unwrap(), subnet("0.0.0.0", 0));
+    }
+
+    #[test]
+    fn plain_addresses_are_hosts() {
+        assert_eq!(Target::parse("192.0.2.7").unwrap(), subnet("192.0.2.7", 32));
+        assert_eq!(
+            Target::parse("2001:db8::7").unwrap(),
+            subnet("2001:db8::7", 128)
+        );
+    }
+
+    #[test]
+    fn parses_host_names() {
+        assert_eq!(
+            Target::parse("Git.Example.COM").unwrap(),
+            Target::Host("git.example.com".to_string())
+        );
+        assert_eq!(
+            Target::parse("intranet").unwrap(),
+            Target::Host("intranet".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_rules() {
+        for value in [
+            "",
+            "10.0.0.0/33",
+            "fd00::/129",
+            "10.0.0.0/x",
+            "10.0.0/8",
+            "-bad.example.com",
+            "a..b",
+            "under_score.example.com",
+        ] {
+            assert!(Target::parse(value).is_err(), "{:?} was accepted", value);
+        }
+    }
+
+    #[test]
+    fn displays_in_cidr_notation() {
+        assert_eq!(subnet("10.20.0.0", 16).to_string(), "10.20.0.0/16");
+        assert_eq!(
+            Target::Host("git.example.com".to_string()).to_string(),
+            "git.example.com"
+        );
+    }
+
+    #[test]
+    fn parses_lists_without_duplicates() {
+        let targets =
+            parse_list("10.0.0.0/8, 10.1.0.0/8\n192.0.2.1\tgit.example.com", true).unwrap();
+        assert_eq!(
+            targets,
+            [
+                subnet("10.0.0.0", 8),
+                subnet("192.0.2.1", 32),
+                Target::Host("git.example.com".to_string()),
+            ]
+        );
+        assert_eq!(parse_list("  ,\n", true).unwrap(), []);
+    }
+
+    #[test]
+    fn list_rejects_hosts_unless_allowed() {
+        assert!(parse_list("10.0.0.0/8, git.example.com", false).is_err());
+        assert!(parse_list("10.0.0.0/8, bad/rule", true).is_err());
+    }
+}
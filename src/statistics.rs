@@ -0,0 +1,388 @@
+//! Tunnel Statistics
+//!
+//! Traffic counters of the tunnel interface, read from
+//! `/sys/class/net/<interface>/statistics` once per UI tick. The kernel keeps
+//! these counters per interface, so they start at zero with each connection
+//! and need no privileges to read.
+//!
+//! Latency is measured as the time a TCP connection to the profile's probe
+//! host takes to establish, which works without raw sockets and through
+//! firewalls that drop ICMP. Together with the throughput history this tells
+//! apart a slow tunnel (latency up for every service) from a slow service.
+
+use crate::gateway_certificate;
+use anyhow::{Context, Result};
+use std::collections::VecDeque;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+
+/// Time allowed for a probe connection; slower probes count as lost
+const PROBE_TIMEOUT_SECS: u64 = 2;
+
+/// Counters of the tunnel interface since it was created.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Counters {
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    pub rx_packets: u64,
+    pub tx_packets: u64,
+    pub rx_errors: u64,
+    pub tx_errors: u64,
+    pub rx_dropped: u64,
+    pub tx_dropped: u64,
+}
+
+impl Counters {
+    /// Read the current counters of `interface`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the interface does not exist (any more).
+    pub fn read(interface: &str) -> Result<Self> {
+        Self::read_dir(
+            &Path::new("/sys/class/net")
+                .join(interface)
+                .join("statistics"),
+        )
+    }
+
+    /// Read the counters from a `statistics` directory.
+    fn read_dir(dir: &Path) -> Result<Self> {
+        let counter = |name: &str| -> Result<u64> {
+            let path = dir.join(name);
+            fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {}", path.display()))?
+                .trim()
+                .parse()
+                .with_context(|| format!("Invalid counter in {}", path.display()))
+        };
+
+        Ok(Self {
+            rx_bytes: counter("rx_bytes")?,
+            tx_bytes: counter("tx_bytes")?,
+            rx_packets: counter("rx_packets")?,
+            tx_packets: counter("tx_packets")?,
+            rx_errors: counter("rx_errors")?,
+            tx_errors: counter("tx_errors")?,
+            rx_dropped: counter("rx_dropped")?,
+            tx_dropped: counter("tx_dropped")?,
+        })
+    }
+}
+
+/// Counters of the last sample and the throughput since the one before.
+#[derive(Debug, Clone, Default)]
+pub struct Traffic {
+    pub counters: Counters,
+    /// Received bytes per second
+    pub rx_rate: f64,
+    /// Sent bytes per second
+    pub tx_rate: f64,
+    sampled_at: Option<Instant>,
+}
+
+impl Traffic {
+    /// Take a new sample of `interface`, updating the throughput.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the counters cannot be read; the previous sample
+    /// is kept.
+    pub fn sample(&mut self, interface: &str) -> Result<()> {
+        self.update(Counters::read(interface)?, Instant::now());
+        Ok(())
+    }
+
+    /// Record `counters`, read at `now`.
+    fn update(&mut self, counters: Counters, now: Instant) {
+        if let Some(previous) = self.sampled_at {
+            let secs = now.duration_since(previous).as_secs_f64();
+            if secs > 0.0 {
+                // Counters restart if the interface is recreated
+                let rate = |new: u64, old: u64| new.saturating_sub(old) as f64 / secs;
+                self.rx_rate = rate(counters.rx_bytes, self.counters.rx_bytes);
+                self.tx_rate = rate(counters.tx_bytes, self.counters.tx_bytes);
+            }
+        }
+        self.counters = counters;
+        self.sampled_at = Some(now);
+    }
+}
+
+/// One tick of the history.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Sample {
+    /// Received bytes per second
+    pub rx_rate: f64,
+    /// Sent bytes per second
+    pub tx_rate: f64,
+    /// Latest probe round trip, `None` without a probe or if it failed
+    pub latency: Option<Duration>,
+}
+
+/// Ring buffer of the most recent samples.
+#[derive(Debug, Clone)]
+pub struct History {
+    samples: VecDeque<Sample>,
+    capacity: usize,
+}
+
+impl History {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            samples: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Number of samples the history holds when full
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Add a sample, dropping the oldest one if the history is full.
+    pub fn push(&mut self, sample: Sample) {
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    /// Samples, oldest first
+    pub fn samples(&self) -> impl Iterator<Item = &Sample> {
+        self.samples.iter()
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+}
+
+/// Measure the TCP connect time to `target`.
+///
+/// # Arguments
+///
+/// * `target` - `host[:port]`; the port defaults to 443
+///
+/// # Errors
+///
+/// Returns an error if the host cannot be resolved, refuses the connection
+/// or does not answer within the probe timeout.
+pub async fn probe(target: String) -> Result<Duration> {
+    let (host, port) = gateway_certificate::host_and_port(&target);
+
+    // Resolve first, so the measurement covers only the connection
+    let address = tokio::net::lookup_host((host.as_str(), port))
+        .await
+        .with_context(|| format!("Failed to resolve {}", host))?
+        .next()
+        .with_context(|| format!("No address for {}", host))?;
+
+    let started = Instant::now();
+    timeout(
+        Duration::from_secs(PROBE_TIMEOUT_SECS),
+        TcpStream::connect(address),
+    )
+    .await
+    .map_err(|_| anyhow::anyhow!("No answer within {} seconds", PROBE_TIMEOUT_SECS))?
+    .with_context(|| format!("Failed to connect to {}", address))?;
+    Ok(started.elapsed())
+}
+
+/// Format a byte count with binary units, e.g. "12.3 MiB".
+pub fn format_bytes(bytes: f64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+    let mut value = bytes;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", value as u64, UNITS[unit])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// Format a duration as "1d 02:03:04", or "02:03:04" below a day.
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (days, secs) = (secs / 86400, secs % 86400);
+    let clock = format!(
+        "{:02}:{:02}:{:02}",
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    );
+    if days > 0 {
+        format!("{}d {}", days, clock)
+    } else {
+        clock
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counters(rx_bytes: u64, tx_bytes: u64) -> Counters {
+        Counters {
+            rx_bytes,
+            tx_bytes,
+            ..Counters::default()
+        }
+    }
+
+    #[test]
+    fn reads_statistics_directory() {
+        let dir = std::env::temp_dir().join(format!("gp-gui-statistics-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let names = [
+            "rx_bytes",
+            "tx_bytes",
+            "rx_packets",
+            "tx_packets",
+            "rx_errors",
+            "tx_errors",
+            "rx_dropped",
+            "tx_dropped",
+        ];
+        for (value, name) in names.iter().enumerate() {
+            fs::write(dir.join(name), format!("{}\n", value + 1)).unwrap();
+        }
+
+        let counters = Counters::read_dir(&dir).unwrap();
+        assert_eq!(
+            counters,
+            Counters {
+                rx_bytes: 1,
+                tx_bytes: 2,
+                rx_packets: 3,
+                tx_packets: 4,
+                rx_errors: 5,
+                tx_errors: 6,
+                rx_dropped: 7,
+                tx_dropped: 8,
+            }
+        );
+
+        fs::write(dir.join("tx_dropped"), "lots").unwrap();
+        let error = Counters::read_dir(&dir).unwrap_err().to_string();
+        assert!(error.starts_with("Invalid counter in"), "{}", error);
+
+        fs::remove_file(dir.join("rx_bytes")).unwrap();
+        let error = Counters::read_dir(&dir).unwrap_err().to_string();
+        assert!(error.starts_with("Failed to read"), "{}", error);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn missing_interface_is_an_error() {
+        assert!(Counters::read("gp-gui-missing0").is_err());
+    }
+
+    #[test]
+    fn rate_is_bytes_per_second_between_samples() {
+        let start = Instant::now();
+        let mut traffic = Traffic::default();
+
+        traffic.update(counters(1000, 500), start);
+        assert_eq!((traffic.rx_rate, traffic.tx_rate), (0.0, 0.0));
+
+        traffic.update(counters(5000, 1500), start + Duration::from_secs(2));
+        assert_eq!((traffic.rx_rate, traffic.tx_rate), (2000.0, 500.0));
+        assert_eq!(traffic.counters, counters(5000, 1500));
+    }
+
+    #[test]
+    fn counter_reset_does_not_produce_huge_rates() {
+        let start = Instant::now();
+        let mut traffic = Traffic::default();
+        traffic.update(counters(5000, 5000), start);
+
+        traffic.update(counters(100, 6000), start + Duration::from_secs(1));
+        assert_eq!((traffic.rx_rate, traffic.tx_rate), (0.0, 1000.0));
+    }
+
+    #[test]
+    fn samples_at_the_same_instant_keep_the_rate() {
+        let start = Instant::now();
+        let mut traffic = Traffic::default();
+        traffic.update(counters(0, 0), start);
+        traffic.update(counters(1000, 0), start + Duration::from_secs(1));
+
+        traffic.update(counters(2000, 0), start + Duration::from_secs(1));
+        assert_eq!(traffic.rx_rate, 1000.0);
+        assert_eq!(traffic.counters.rx_bytes, 2000);
+    }
+
+    #[test]
+    fn formats_bytes() {
+        assert_eq!(format_bytes(0.0), "0 B");
+        assert_eq!(format_bytes(1023.0), "1023 B");
+        assert_eq!(format_bytes(1024.0), "1.0 KiB");
+        assert_eq!(format_bytes(1536.0 * 1024.0), "1.5 MiB");
+        assert_eq!(format_bytes(2048.0 * 1024f64.powi(4)), "2048.0 TiB");
+    }
+
+    #[test]
+    fn formats_durations() {
+        assert_eq!(format_duration(Duration::from_secs(0)), "00:00:00");
+        assert_eq!(format_duration(Duration::from_secs(3725)), "01:02:05");
+        assert_eq!(
+            format_duration(Duration::from_secs(86400 + 7384)),
+            "1d 02:03:04"
+        );
+    }
+
+    fn sample(rx_rate: f64) -> Sample {
+        Sample {
+            rx_rate,
+            ..Sample::default()
+        }
+    }
+
+    #[test]
+    fn history_drops_oldest_samples() {
+        let mut history = History::new(3);
+        for rate in 1..=5 {
+            history.push(sample(f64::from(rate)));
+        }
+
+        assert_eq!(history.capacity(), 3);
+        let rates: Vec<f64> = history.samples().map(|s| s.rx_rate).collect();
+        assert_eq!(rates, [3.0, 4.0, 5.0]);
+
+        history.clear();
+        assert_eq!(history.samples().count(), 0);
+        assert_eq!(history.capacity(), 3);
+    }
+
+    #[tokio::test]
+    async fn probe_measures_connect_time() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let target = listener.local_addr().unwrap().to_string();
+
+        let latency = probe(target).await.unwrap();
+        assert!(latency < Duration::from_secs(PROBE_TIMEOUT_SECS));
+    }
+
+    #[tokio::test]
+    async fn probe_reports_refused_connections() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let target = listener.local_addr().unwrap().to_string();
+        drop(listener);
+
+        let error = probe(target).await.unwrap_err().to_string();
+        assert!(
+            error.starts_with("Failed to connect to 127.0.0.1:"),
+            "{}",
+            error
+        );
+    }
+}
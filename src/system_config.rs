@@ -0,0 +1,404 @@
+//! System-Wide Configuration
+//!
+//! Administrators can provide connection profiles and defaults for all users
+//! of a machine, typically rendered by the NixOS module:
+//!
+//! - `/etc/gp-gui/config.json` - default profile, fields locked for all
+//!   system profiles, and profiles
+//! - `/etc/gp-gui/profiles.d/*.json` - one profile per file, read in file
+//!   name order; a later profile replaces an earlier one of the same name
+//!
+//! ```json
+//! {
+//!   "default_profile": "Corporate",
+//!   "locked": ["gateway", "csd_wrapper", "fix_openssl"],
+//!   "profiles": [{ "name": "Corporate", "gateway": "vpn.example.com" }]
+//! }
+//! ```
+//!
+//! A profile file holds a single profile in the user config format, with an
+//! optional `locked` list of its own.
+//!
+//! # Merging
+//!
+//! System profiles come first, followed by the user's own profiles. A user
+//! profile with the same name as a system profile overrides it, except for
+//! locked fields, which always keep the system value. When saving, system
+//! profiles the user has not changed are left out of the user config, so
+//! later changes by the administrator take effect.
+//!
+//! Unreadable or invalid files are logged and skipped; the system config never
+//! prevents gp-gui from starting.
+
+use crate::config::{LockedField, Profile, UserConfig};
+use anyhow::{Context, Result};
+use log::{info, warn};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Directory holding the system configuration
+const SYSTEM_CONFIG_DIR: &str = "/etc/gp-gui";
+
+/// A profile as written by the administrator.
+#[derive(Debug, Deserialize)]
+struct SystemProfile {
+    #[serde(flatten)]
+    profile: Profile,
+
+    /// Fields locked for this profile in addition to the global ones
+    #[serde(default)]
+    locked: Vec<LockedField>,
+}
+
+/// Contents of `/etc/gp-gui/config.json`.
+#[derive(Debug, Default, Deserialize)]
+struct SystemConfigFile {
+    /// Profile selected at startup unless the user chose another one
+    #[serde(default)]
+    default_profile: Option<String>,
+
+    /// Fields locked for every system profile
+    #[serde(default)]
+    locked: Vec<LockedField>,
+
+    #[serde(default)]
+    profiles: Vec<SystemProfile>,
+}
+
+/// Merged system configuration.
+#[derive(Debug, Default)]
+pub struct SystemConfig {
+    pub default_profile: Option<String>,
+
+    /// Profiles marked as managed, with their locked fields
+    pub profiles: Vec<Profile>,
+}
+
+impl SystemConfig {
+    fn profile(&self, name: &str) -> Option<&Profile> {
+        self.profiles.iter().find(|p| p.name == name)
+    }
+}
+
+/// The system configuration, read once on first use.
+pub fn system_config() -> &'static SystemConfig {
+    static CONFIG: OnceLock<SystemConfig> = OnceLock::new();
+    CONFIG.get_or_init(|| read_system_config(Path::new(SYSTEM_CONFIG_DIR)))
+}
+
+fn read_system_config(dir: &Path) -> SystemConfig {
+    let file = match read_json::<SystemConfigFile>(&dir.join("config.json")) {
+        Ok(file) => file.unwrap_or_default(),
+        Err(e) => {
+            warn!("Ignoring system config: {:#}", e);
+            SystemConfigFile::default()
+        }
+    };
+
+    let mut system_profiles = file.profiles;
+    for path in drop_in_files(&dir.join("profiles.d")) {
+        match read_json::<SystemProfile>(&path) {
+            Ok(Some(profile)) => system_profiles.push(profile),
+            Ok(None) => {}
+            Err(e) => warn!("Ignoring system profile: {:#}", e),
+        }
+    }
+
+    let mut profiles: Vec<Profile> = Vec::new();
+    for SystemProfile {
+        mut profile,
+        mut locked,
+    } in system_profiles
+    {
+        for field in &file.locked {
+            if !locked.contains(field) {
+                locked.push(*field);
+            }
+        }
+        profile.managed = true;
+        profile.locked = locked;
+
+        match profiles.iter_mut().find(|p| p.name == profile.name) {
+            Some(existing) => *existing = profile,
+            None => profiles.push(profile),
+        }
+    }
+
+    if !profiles.is_empty() {
+        info!("Loaded {} system profile(s) from {:?}", profiles.len(), dir);
+    }
+
+    SystemConfig {
+        default_profile: file.default_profile,
+        profiles,
+    }
+}
+
+/// `*.json` files in `dir`, sorted by name; empty if `dir` does not exist.
+fn drop_in_files(dir: &Path) -> Vec<PathBuf> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Vec::new(),
+        Err(e) => {
+            warn!("Failed to list {:?}: {}", dir, e);
+            return Vec::new();
+        }
+    };
+
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    files.sort();
+    files
+}
+
+/// Parse a JSON file, returning `Ok(None)` if it does not exist.
+fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> Result<Option<T>> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {:?}", path)),
+    };
+
+    serde_json::from_str(&content)
+        .map(Some)
+        .with_context(|| format!("Failed to parse {:?}", path))
+}
+
+/// Combine the user configuration with the system configuration.
+///
+/// See the module documentation for the rules.
+pub fn apply(user: UserConfig) -> UserConfig {
+    merge(system_config(), user)
+}
+
+fn merge(system: &SystemConfig, user: UserConfig) -> UserConfig {
+    let mut profiles: Vec<Profile> = system
+        .profiles
+        .iter()
+        .map(|base| match user.profile(&base.name) {
+            Some(own) => {
+                let mut profile = own.clone();
+                profile.managed = true;
+                profile.apply_locks(base);
+                profile
+            }
+            None => base.clone(),
+        })
+        .collect();
+
+    profiles.extend(
+        user.profiles
+            .into_iter()
+            .filter(|p| system.profile(&p.name).is_none()),
+    );
+
+    UserConfig {
+        version: user.version,
+        default_profile: user.default_profile.or(system.default_profile.clone()),
+        profiles,
+    }
+}
+
+/// The part of `config` that belongs in the user's config file.
+///
+/// Drops system profiles that are unchanged and a default profile that
+/// matches the system default.
+pub fn user_overrides(config: &UserConfig) -> UserConfig {
+    overrides(system_config(), config)
+}
+
+fn overrides(system: &SystemConfig, config: &UserConfig) -> UserConfig {
+    UserConfig {
+        version: config.version,
+        default_profile: config
+            .default_profile
+            .clone()
+            .filter(|name| system.default_profile.as_ref() != Some(name)),
+        profiles: config
+            .profiles
+            .iter()
+            .filter(|p| system.profile(&p.name) != Some(*p))
+            .cloned()
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::LockedField::{CsdWrapper, FixOpenssl, Gateway};
+
+    fn profile(name: &str, gateway: &str) -> Profile {
+        Profile::new(name.to_string(), gateway.to_string())
+    }
+
+    fn managed(name: &str, gateway: &str, locked: &[LockedField]) -> Profile {
+        Profile {
+            managed: true,
+            locked: locked.to_vec(),
+            ..profile(name, gateway)
+        }
+    }
+
+    fn system(default: Option<&str>, profiles: Vec<Profile>) -> SystemConfig {
+        SystemConfig {
+            default_profile: default.map(str::to_string),
+            profiles,
+        }
+    }
+
+    fn user(default: Option<&str>, profiles: Vec<Profile>) -> UserConfig {
+        UserConfig {
+            default_profile: default.map(str::to_string),
+            profiles,
+            ..UserConfig::empty()
+        }
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("gp-gui-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("profiles.d")).unwrap();
+        dir
+    }
+
+    #[test]
+    fn system_profiles_come_first() {
+        let system = system(None, vec![managed("Corp", "vpn.example.com", &[])]);
+        let merged = merge(
+            &system,
+            user(None, vec![profile("Home", "home.example.com")]),
+        );
+
+        assert_eq!(merged.profile_names(), ["Corp", "Home"]);
+        assert!(merged.profiles[0].managed);
+        assert!(!merged.profiles[1].managed);
+    }
+
+    #[test]
+    fn user_changes_override_unlocked_fields() {
+        let system = system(None, vec![managed("Corp", "vpn.example.com", &[])]);
+        let mut own = profile("Corp", "other.example.com");
+        own.username = "alice".to_string();
+
+        let merged = merge(&system, user(None, vec![own]));
+
+        assert_eq!(merged.profiles.len(), 1);
+        assert_eq!(merged.profiles[0].gateway, "other.example.com");
+        assert_eq!(merged.profiles[0].username, "alice");
+        assert!(merged.profiles[0].managed);
+    }
+
+    #[test]
+    fn locked_fields_keep_system_values() {
+        let mut base = managed(
+            "Corp",
+            "vpn.example.com",
+            &[Gateway, CsdWrapper, FixOpenssl],
+        );
+        base.csd_wrapper = Some("/etc/gp-gui/hip.sh".to_string());
+        let mut own = profile("Corp", "evil.example.com");
+        own.csd_wrapper = Some("/tmp/hip.sh".to_string());
+        own.fix_openssl = false;
+        own.username = "alice".to_string();
+
+        let merged = merge(&system(None, vec![base]), user(None, vec![own]));
+        let profile = &merged.profiles[0];
+
+        assert_eq!(profile.gateway, "vpn.example.com");
+        assert_eq!(profile.csd_wrapper.as_deref(), Some("/etc/gp-gui/hip.sh"));
+        assert!(profile.fix_openssl);
+        assert_eq!(profile.username, "alice");
+        assert!(profile.is_locked(Gateway));
+    }
+
+    #[test]
+    fn user_default_wins_over_system_default() {
+        let system = system(Some("Corp"), vec![managed("Corp", "vpn.example.com", &[])]);
+
+        let merged = merge(&system, user(None, Vec::new()));
+        assert_eq!(merged.default_profile.as_deref(), Some("Corp"));
+
+        let merged = merge(&system, user(Some("Home"), Vec::new()));
+        assert_eq!(merged.default_profile.as_deref(), Some("Home"));
+    }
+
+    #[test]
+    fn overrides_drop_unchanged_system_state() {
+        let system = system(Some("Corp"), vec![managed("Corp", "vpn.example.com", &[])]);
+        let merged = merge(
+            &system,
+            user(Some("Corp"), vec![profile("Home", "home.example.com")]),
+        );
+
+        let saved = overrides(&system, &merged);
+
+        assert_eq!(saved.default_profile, None);
+        assert_eq!(saved.profile_names(), ["Home"]);
+    }
+
+    #[test]
+    fn overrides_keep_changed_system_profiles() {
+        let system = system(None, vec![managed("Corp", "vpn.example.com", &[])]);
+        let mut merged = merge(&system, user(Some("Corp"), Vec::new()));
+        merged.profiles[0].username = "alice".to_string();
+
+        let saved = overrides(&system, &merged);
+
+        assert_eq!(saved.default_profile.as_deref(), Some("Corp"));
+        assert_eq!(saved.profiles[0].username, "alice");
+    }
+
+    #[test]
+    fn reads_config_and_drop_ins() {
+        let dir = temp_dir("system-config");
+        std::fs::write(
+            dir.join("config.json"),
+            r#"{
+                "default_profile": "Corp",
+                "locked": ["gateway"],
+                "profiles": [{"name": "Corp", "gateway": "old.example.com"}]
+            }"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("profiles.d/10-corp.json"),
+            r#"{"name": "Corp", "gateway": "vpn.example.com", "locked": ["csd_wrapper"]}"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("profiles.d/20-lab.json"),
+            r#"{"name": "Lab", "gateway": "lab.example.com"}"#,
+        )
+        .unwrap();
+        std::fs::write(dir.join("profiles.d/30-broken.json"), "{").unwrap();
+        std::fs::write(dir.join("profiles.d/README"), "not a profile").unwrap();
+
+        let config = read_system_config(&dir);
+
+        assert_eq!(config.default_profile.as_deref(), Some("Corp"));
+        assert_eq!(
+            config.profiles,
+            [
+                managed("Corp", "vpn.example.com", &[CsdWrapper, Gateway]),
+                managed("Lab", "lab.example.com", &[Gateway]),
+            ]
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn missing_or_invalid_config_is_ignored() {
+        let dir = temp_dir("system-config-invalid");
+        assert!(read_system_config(&dir).profiles.is_empty());
+
+        std::fs::write(dir.join("config.json"), r#"{"locked": ["username"]}"#).unwrap();
+        let config = read_system_config(&dir);
+        assert!(config.profiles.is_empty());
+        assert_eq!(config.default_profile, None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,179 @@
+//! Tunnel Interface
+//!
+//! openconnect, running inside gpclient, creates a tun device for the tunnel.
+//! Its name depends on the openconnect version and vpnc-script ("tun0",
+//! "gpd0", ...), so it is looked up instead of assumed:
+//!
+//! 1. the tun device opened by the gpclient process, read from the `iff:`
+//!    line of `/proc/<pid>/fdinfo` for its `/dev/net/tun` descriptor
+//! 2. otherwise the only tun device on the system, if there is exactly one
+
+use log::{info, warn};
+use nix::ifaddrs::getifaddrs;
+use std::fs;
+use std::net::IpAddr;
+use std::path::Path;
+use tokio::time::{Duration, sleep};
+
+/// Character device used to create tun interfaces
+const TUN_DEVICE: &str = "/dev/net/tun";
+
+/// How long to wait for the tunnel to be configured after connecting
+const DETECT_TIMEOUT_MS: u64 = 5000;
+const DETECT_INTERVAL_MS: u64 = 500;
+
+/// The tunnel interface and its addresses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TunnelInfo {
+    pub interface: String,
+    pub addresses: Vec<IpAddr>,
+}
+
+impl TunnelInfo {
+    /// First IPv4 address of the tunnel
+    pub fn ipv4(&self) -> Option<IpAddr> {
+        self.addresses.iter().copied().find(IpAddr::is_ipv4)
+    }
+
+    /// First IPv6 address of the tunnel
+    pub fn ipv6(&self) -> Option<IpAddr> {
+        self.addresses.iter().copied().find(IpAddr::is_ipv6)
+    }
+}
+
+/// Find the tunnel of the gpclient process `pid`.
+///
+/// Waits briefly for the interface to receive its addresses, since the
+/// vpnc-script may still be configuring it when gpclient reports success.
+///
+/// # Returns
+///
+/// `None` if no tunnel interface can be identified.
+pub async fn detect(pid: Option<u32>) -> Option<TunnelInfo> {
+    let mut waited = 0;
+    loop {
+        let tunnel = find(pid);
+        let configured = tunnel.as_ref().is_some_and(|t| !t.addresses.is_empty());
+        if configured || waited >= DETECT_TIMEOUT_MS {
+            match &tunnel {
+                Some(tunnel) => info!(
+                    "Tunnel interface {} with addresses {:?}",
+                    tunnel.interface, tunnel.addresses
+                ),
+                None => warn!("Could not identify the tunnel interface"),
+            }
+            return tunnel;
+        }
+
+        sleep(Duration::from_millis(DETECT_INTERVAL_MS)).await;
+        waited += DETECT_INTERVAL_MS;
+    }
+}
+
+/// Find the tunnel interface right now.
+pub fn find(pid: Option<u32>) -> Option<TunnelInfo> {
+    let interface = pid
+        .and_then(interface_of_process)
+        .or_else(only_tun_device)?;
+
+    Some(TunnelInfo {
+        addresses: addresses(&interface),
+        interface,
+    })
+}
+
+/// Name of the tun interface opened by process `pid`.
+fn interface_of_process(pid: u32) -> Option<String> {
+    let proc_dir = Path::new("/proc").join(pid.to_string());
+    let fds = fs::read_dir(proc_dir.join("fd")).ok()?;
+
+    fds.filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            fs::read_link(entry.path()).is_ok_and(|target| target == Path::new(TUN_DEVICE))
+        })
+        .find_map(|entry| {
+            let fdinfo =
+                fs::read_to_string(proc_dir.join("fdinfo").join(entry.file_name())).ok()?;
+            fdinfo
+                .lines()
+                .find_map(|line| line.strip_prefix("iff:"))
+                .map(|name| name.trim().to_string())
+        })
+}
+
+/// The only tun device on the system, if there is exactly one.
+fn only_tun_device() -> Option<String> {
+    let devices: Vec<String> = fs::read_dir("/sys/class/net")
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().join("tun_flags").exists())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .collect();
+
+    match devices.as_slice() {
+        [device] => Some(device.clone()),
+        _ => None,
+    }
+}
+
+/// IPv4 and IPv6 addresses of `interface`, without IPv6 link-local ones.
+fn addresses(interface: &str) -> Vec<IpAddr> {
+    let Ok(interfaces) = getifaddrs() else {
+        return Vec::new();
+    };
+
+    interfaces
+        .filter(|ifaddr| ifaddr.interface_name == interface)
+        .filter_map(|ifaddr| {
+            let address = ifaddr.address?;
+            if let Some(v4) = address.as_sockaddr_in() {
+                Some(IpAddr::V4(v4.ip()))
+            } else {
+                address
+                    .as_sockaddr_in6()
+                    .map(|v6| v6.ip())
+                    .filter(|ip| !ip.is_unicast_link_local())
+                    .map(IpAddr::V6)
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_first_address_of_each_family() {
+        let tunnel = TunnelInfo {
+            interface: "tun0".to_string(),
+            addresses: ["fd00::2", "10.0.0.2", "10.0.0.3"]
+                .iter()
+                .map(|ip| ip.parse().unwrap())
+                .collect(),
+        };
+
+        assert_eq!(tunnel.ipv4(), Some("10.0.0.2".parse().unwrap()));
+        assert_eq!(tunnel.ipv6(), Some("fd00::2".parse().unwrap()));
+    }
+
+    #[test]
+    fn missing_families_are_none() {
+        let tunnel = TunnelInfo {
+            interface: "tun0".to_string(),
+            addresses: Vec::new(),
+        };
+        assert_eq!((tunnel.ipv4(), tunnel.ipv6()), (None, None));
+    }
+
+    #[test]
+    fn reads_interface_addresses() {
+        assert!(addresses("lo").contains(&"127.0.0.1".parse().unwrap()));
+        assert!(addresses("gp-gui-missing0").is_empty());
+    }
+
+    #[test]
+    fn processes_without_tun_device_have_no_interface() {
+        assert_eq!(interface_of_process(std::process::id()), None);
+    }
+}
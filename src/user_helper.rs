@@ -0,0 +1,294 @@
+//! Helper Processes Running as the Invoking User
+//!
+//! Some services only accept connections from the user that owns them, most
+//! notably the session bus (Secret Service keyring, desktop portals). When
+//! gp-gui runs as root on behalf of a user, such operations are delegated to
+//! a helper: gp-gui re-executes itself with `--user-helper` and the invoking
+//! user's uid/gid. Input and output travel over the helper's stdin/stdout
+//! pipes only.
+//!
+//! # Operations
+//!
+//! - `keyring <store|lookup|forget> <password|proxy> <profile>` (see the
+//!   `keyring` module)
+//! - `choose-file <title>` and `save-file <title> <name>` (see the
+//!   `file_chooser` module)
+//! - `notify <summary> <body>` (see the `notification` module)
+//! - `file`, reading a [`FileOperation`] as JSON from stdin and writing its
+//!   JSON result to stdout (see [`file_operation`])
+
+use crate::config::{self, UserConfig};
+use crate::secret::Secret;
+use crate::{file_chooser, history, keyring, notification, privileges};
+use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+/// First argument selecting helper mode
+pub const HELPER_ARG: &str = "--user-helper";
+
+/// Run a helper operation as the invoking user.
+///
+/// # Arguments
+///
+/// * `args` - Operation and its arguments
+/// * `input` - Written to the helper's stdin, if any
+///
+/// # Returns
+///
+/// The helper's stdout.
+pub async fn run(args: &[&str], input: Option<&Secret>) -> Result<Vec<u8>> {
+    let exe = std::env::current_exe().context("Failed to locate gp-gui executable")?;
+
+    let mut cmd = Command::new(exe);
+    cmd.arg(HELPER_ARG)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    privileges::run_as_invoking_user(&mut cmd)?;
+
+    let mut child = cmd.spawn().context("Failed to start helper")?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        if let Some(input) = input {
+            stdin.write_all(input.expose().as_bytes()).await?;
+        }
+        drop(stdin);
+    }
+
+    let output = child.wait_with_output().await?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow::anyhow!("Helper failed: {}", stderr.trim()));
+    }
+
+    Ok(output.stdout)
+}
+
+/// Work on files owned by the invoking user.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum FileOperation {
+    /// [`config::read_config`]
+    LoadConfig,
+    /// [`config::write_config`]
+    SaveConfig(UserConfig),
+    /// [`history::append_line`]
+    AppendHistory(String),
+    /// [`history::read_file`]
+    LoadHistory,
+    /// Replace the file at `path` with `content`
+    Write { path: String, content: String },
+    /// Fail unless the file at the path can be opened for reading
+    CheckReadable(String),
+    /// Content of the file at the path
+    Read(String),
+}
+
+impl FileOperation {
+    /// Perform the operation in this process.
+    fn perform(self) -> Result<serde_json::Value> {
+        let value = match self {
+            FileOperation::LoadConfig => serde_json::to_value(config::read_config())?,
+            FileOperation::SaveConfig(config) => {
+                serde_json::to_value(config::write_config(&config)?)?
+            }
+            FileOperation::AppendHistory(line) => {
+                serde_json::to_value(history::append_line(&line)?)?
+            }
+            FileOperation::LoadHistory => serde_json::to_value(history::read_file()?)?,
+            FileOperation::Write { path, content } => {
+                std::fs::write(&path, content)
+                    .with_context(|| format!("Failed to write {}", path))?;
+                serde_json::Value::Null
+            }
+            FileOperation::CheckReadable(path) => {
+                std::fs::File::open(&path).with_context(|| format!("Cannot read {}", path))?;
+                serde_json::Value::Null
+            }
+            FileOperation::Read(path) => serde_json::to_value(
+                std::fs::read(&path).with_context(|| format!("Cannot read {}", path))?,
+            )?,
+        };
+        Ok(value)
+    }
+}
+
+/// Perform `operation` with the identity of the invoking user.
+///
+/// When gp-gui runs as root on behalf of a user, the operation runs in a
+/// `--user-helper file` process, so files are created with the user's
+/// ownership and permissions are checked as for the user. Otherwise it runs
+/// in-process. Either way effects on this process's memory are lost, as the
+/// result is passed back as JSON.
+///
+/// Blocks until the operation is done.
+pub fn file_operation<T: DeserializeOwned>(operation: FileOperation) -> Result<T> {
+    let value = if privileges::is_acting_for_user() {
+        run_file_helper(&operation)?
+    } else {
+        operation.perform()?
+    };
+    serde_json::from_value(value).context("Unexpected result of file operation")
+}
+
+fn run_file_helper(operation: &FileOperation) -> Result<serde_json::Value> {
+    let exe = std::env::current_exe().context("Failed to locate gp-gui executable")?;
+
+    let mut cmd = std::process::Command::new(exe);
+    cmd.arg(HELPER_ARG)
+        .arg("file")
+        // Resolve the user's directories from the home directory, not
+        // variables meant for root
+        .env_remove("XDG_CONFIG_HOME")
+        .env_remove("XDG_STATE_HOME")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit());
+    privileges::run_std_as_invoking_user(&mut cmd)?;
+
+    let mut child = cmd.spawn().context("Failed to start file helper")?;
+    if let Some(mut stdin) = child.stdin.take() {
+        serde_json::to_writer(&mut stdin, operation)?;
+    }
+    let output = child
+        .wait_with_output()
+        .context("Failed to wait for file helper")?;
+    if !output.status.success() {
+        anyhow::bail!("File helper failed with {}", output.status);
+    }
+
+    let result: std::result::Result<serde_json::Value, String> =
+        serde_json::from_slice(&output.stdout).context("Invalid result from file helper")?;
+    result.map_err(|e| anyhow::anyhow!(e))
+}
+
+/// Helper side of [`file_operation`].
+fn file_helper() -> Result<()> {
+    let mut input = Vec::new();
+    std::io::stdin().read_to_end(&mut input)?;
+    let operation: FileOperation =
+        serde_json::from_slice(&input).context("Invalid file operation")?;
+
+    let result = operation.perform().map_err(|e| format!("{:#}", e));
+    let mut stdout = std::io::stdout();
+    serde_json::to_writer(&mut stdout, &result)?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// Entry point for `gp-gui --user-helper <operation> [args...]`.
+///
+/// Returns `None` if `args` do not select helper mode, otherwise the process
+/// exit code. Errors go to stderr.
+pub fn helper_main(args: &[String]) -> Option<i32> {
+    if args.first().map(String::as_str) != Some(HELPER_ARG) {
+        return None;
+    }
+
+    // The helper exists to drop root; refuse to talk to root's session bus
+    if nix::unistd::getuid().is_root() {
+        eprintln!("{} must not run as root", HELPER_ARG);
+        return Some(1);
+    }
+
+    let Some(operation) = args.get(1) else {
+        eprintln!("usage: gp-gui {} <operation> [args...]", HELPER_ARG);
+        return Some(2);
+    };
+
+    let runtime = match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            eprintln!("Failed to start runtime: {}", e);
+            return Some(1);
+        }
+    };
+
+    let result = runtime.block_on(async {
+        match operation.as_str() {
+            "keyring" => keyring::helper(&args[2..]).await,
+            "choose-file" => file_chooser::helper(&args[2..]).await,
+            "save-file" => file_chooser::save_helper(&args[2..]).await,
+            "notify" => notification::helper(&args[2..]).await,
+            "file" => file_helper(),
+            other => Err(anyhow::anyhow!("Unknown helper operation: {}", other)),
+        }
+    });
+
+    match result {
+        Ok(()) => Some(0),
+        Err(e) => {
+            eprintln!("{:#}", e);
+            Some(1)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn helper_mode_needs_its_argument() {
+        assert_eq!(helper_main(&[]), None);
+        assert_eq!(helper_main(&["--connect".to_string()]), None);
+    }
+
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("gp-gui-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn file_operations_round_trip() {
+        let dir = temp_dir("file-operation");
+        let path = dir.join("export.csv").to_string_lossy().into_owned();
+
+        file_operation::<()>(FileOperation::Write {
+            path: path.clone(),
+            content: "a,b\n".to_string(),
+        })
+        .unwrap();
+        file_operation::<()>(FileOperation::CheckReadable(path.clone())).unwrap();
+        let content: Vec<u8> = file_operation(FileOperation::Read(path)).unwrap();
+
+        assert_eq!(content, b"a,b\n");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn file_operation_errors_name_the_file() {
+        let error = file_operation::<()>(FileOperation::CheckReadable(
+            "/nonexistent/key.pem".to_string(),
+        ))
+        .unwrap_err();
+        assert!(
+            error.to_string().contains("/nonexistent/key.pem"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn file_operations_survive_the_pipe() {
+        let operation = FileOperation::Write {
+            path: "/tmp/x".to_string(),
+            content: "\u{0}\n\"".to_string(),
+        };
+        let json = serde_json::to_string(&operation).unwrap();
+        assert_eq!(
+            serde_json::from_str::<FileOperation>(&json).unwrap(),
+            operation
+        );
+    }
+}